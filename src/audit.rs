@@ -0,0 +1,324 @@
+//! Post-run audit: hash every file before and after an agent session and report what the policy
+//! does not allow.
+//!
+//! Hyena cannot sandbox an arbitrary agent process, so `hyena snapshot` records a SHA-256 of each
+//! file under root (honouring .gitignore and `.hyenaignore`) and `hyena audit --since <id>@<sha256>`
+//! diffs the tree against it. Snapshots live under root, where the agent could rewrite them, so
+//! `snapshot` prints the SHA-256 of the file it wrote and `audit` refuses a baseline that no longer
+//! matches; other files under [`SNAPSHOT_DIR`] are audited like any file. Findings:
+//! - `raw_modified`: a raw input was created or changed,
+//! - `append_only_rewritten`: a log (append-only, scratch or derived) no longer starts with its
+//!   old content; content sealed into a new segment still counts as kept,
+//! - `write_outside_workspace`: any other file was created or changed outside
+//!   `derived_workspaces.roots`,
+//! - `deleted`: a file is gone.
+
+use crate::policy::Policy;
+use crate::raw::{self, RawInputs};
+use crate::{digest, segment, view};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Where snapshots are kept, as `<id>.json`.
+pub const SNAPSHOT_DIR: &str = ".hyena/snapshots";
+
+/// Hyena's own bookkeeping, rewritten by ordinary commands: never snapshotted. Includes the logs'
+/// empty lock files (see [`segment::lock_path`]).
+const STATE_PATHS: &[&str] = &[
+    view::VIEW_DIR,
+    ".hyena/ingest.json",
+    ".hyena/ingest.json.lock",
+    "**/*.ndjson.lock",
+];
+
+/// Content hashes of every file under root at one point in time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: String,
+    pub taken_at: String,
+    /// Keyed by root-relative path.
+    pub files: BTreeMap<String, FileHash>,
+    /// Root-relative path this snapshot was loaded from, if under root (it is checked by hash,
+    /// not audited).
+    #[serde(skip)]
+    pub file: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileHash {
+    pub sha256: String,
+    pub bytes: u64,
+}
+
+/// One change the policy does not allow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub finding: &'static str,
+    pub path: String,
+    pub detail: String,
+}
+
+/// Hash every file under root except hyena's own state.
+pub fn take(root: &Path, id: String, taken_at: String) -> Result<Snapshot> {
+    let all = RawInputs {
+        patterns: vec!["**".to_string()],
+        exclude: STATE_PATHS.iter().map(|s| (*s).to_string()).collect(),
+        ..Default::default()
+    };
+    let mut files = BTreeMap::new();
+    for path in raw::discover_raw_files(root, None, &all)? {
+        let Some(rel) = raw::relative_for_glob(&path, root) else {
+            continue;
+        };
+        let (sha256, bytes, _) = digest::sha256_file(&path)?;
+        files.insert(rel, FileHash { sha256, bytes });
+    }
+    Ok(Snapshot {
+        id,
+        taken_at,
+        files,
+        file: None,
+    })
+}
+
+pub fn snapshot_path(root: &Path, id: &str) -> PathBuf {
+    root.join(SNAPSHOT_DIR).join(format!("{}.json", id))
+}
+
+/// Write a snapshot under [`SNAPSHOT_DIR`]; returns its path and the file's SHA-256, which
+/// [`load`] requires.
+pub fn save(root: &Path, snapshot: &Snapshot) -> Result<(PathBuf, String)> {
+    let path = snapshot_path(root, &snapshot.id);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    }
+    let json = serde_json::to_string_pretty(snapshot).context("serialize snapshot")? + "\n";
+    std::fs::write(&path, &json).with_context(|| format!("write {}", path.display()))?;
+    Ok((path, digest::sha256_hex(json.as_bytes())))
+}
+
+/// Load a snapshot given as `<id or file>@<sha256>` (as `hyena snapshot` prints it), by id from
+/// [`SNAPSHOT_DIR`] or by file path. Fails unless the file still has that SHA-256.
+pub fn load(root: &Path, reference: &str) -> Result<Snapshot> {
+    let Some((id_or_path, sha256)) = reference.rsplit_once('@') else {
+        anyhow::bail!(
+            "snapshot '{}' needs its hash: pass <id>@<sha256> as printed by `hyena snapshot`",
+            reference
+        );
+    };
+    let by_id = snapshot_path(root, id_or_path);
+    let path = if by_id.is_file() {
+        by_id
+    } else {
+        PathBuf::from(id_or_path)
+    };
+    let bytes = std::fs::read(&path)
+        .with_context(|| format!("no snapshot '{}' ({})", id_or_path, path.display()))?;
+    let actual = digest::sha256_hex(&bytes);
+    if actual != sha256 {
+        anyhow::bail!(
+            "snapshot {} was modified after it was taken (sha256 {}, expected {})",
+            path.display(),
+            actual,
+            sha256
+        );
+    }
+    let mut snapshot: Snapshot =
+        serde_json::from_slice(&bytes).with_context(|| format!("parse {}", path.display()))?;
+    snapshot.file = raw::relative_for_glob(&path, root);
+    Ok(snapshot)
+}
+
+/// What the policy allows for a path, by class.
+struct Classes {
+    raw: globset::GlobSet,
+    raw_exclude: globset::GlobSet,
+    /// Append-only invariants plus scratch and derived log files: they may only grow.
+    logs: globset::GlobSet,
+    workspaces: Vec<String>,
+}
+
+impl Classes {
+    fn new(policy: &Policy) -> Result<Classes> {
+        let inputs = policy.raw_inputs();
+        let mut logs = policy.append_only();
+        logs.extend(policy.scratch_patterns());
+        logs.extend(policy.derived_log_patterns());
+        Ok(Classes {
+            raw: raw::build_globset(&inputs.patterns)?,
+            raw_exclude: raw::build_globset(&inputs.exclude)?,
+            logs: raw::build_globset(&logs)?,
+            workspaces: policy.workspace_roots(),
+        })
+    }
+
+    fn is_raw(&self, rel: &str) -> bool {
+        self.raw.is_match(rel) && !self.raw_exclude.is_match(rel)
+    }
+
+    fn in_workspace(&self, rel: &str) -> bool {
+        self.workspaces.iter().any(|w| {
+            rel.strip_prefix(w.as_str())
+                .is_some_and(|r| r.starts_with('/'))
+        })
+    }
+}
+
+/// Compare `after` with `before` under `policy`. Log prefixes are checked against the files on
+/// disk, so `after` must describe the current tree.
+pub fn audit(
+    root: &Path,
+    policy: &Policy,
+    before: &Snapshot,
+    after: &Snapshot,
+) -> Result<Vec<Finding>> {
+    let classes = Classes::new(policy)?;
+    let paths: BTreeSet<&String> = before.files.keys().chain(after.files.keys()).collect();
+    let mut findings = Vec::new();
+    let mut push = |finding, path: &str, detail: String| {
+        findings.push(Finding {
+            finding,
+            path: path.to_string(),
+            detail,
+        })
+    };
+    for rel in paths {
+        let old = before.files.get(rel);
+        let new = after.files.get(rel);
+        if old == new || before.file.as_ref() == Some(rel) {
+            continue;
+        }
+        if classes.is_raw(rel) {
+            match (old, new) {
+                (Some(_), None) => push("deleted", rel, "raw input deleted".into()),
+                (None, _) => push("raw_modified", rel, "raw input created".into()),
+                _ => push("raw_modified", rel, "raw input changed".into()),
+            }
+        } else if classes.logs.is_match(rel) {
+            let Some(old) = old else {
+                continue;
+            };
+            if !kept_as_prefix(root, rel, old, before)? {
+                match new {
+                    None => push("deleted", rel, "log deleted".into()),
+                    Some(_) => push(
+                        "append_only_rewritten",
+                        rel,
+                        format!("first {} bytes no longer match", old.bytes),
+                    ),
+                }
+            }
+        } else {
+            match (old, new) {
+                (Some(_), None) => push("deleted", rel, "file deleted".into()),
+                _ if classes.in_workspace(rel) => {}
+                (None, _) => push("write_outside_workspace", rel, "file created".into()),
+                _ => push("write_outside_workspace", rel, "file changed".into()),
+            }
+        }
+    }
+    Ok(findings)
+}
+
+/// True if the log at `rel` still begins with its old content. Segments sealed since `before`
+/// come first (rotation moves the active file's content there), then the file itself.
+fn kept_as_prefix(root: &Path, rel: &str, old: &FileHash, before: &Snapshot) -> Result<bool> {
+    let active = root.join(rel);
+    let mut files: Vec<PathBuf> = segment::sealed_segments(&active)?
+        .into_iter()
+        .filter(|p| raw::relative_for_glob(p, root).is_some_and(|r| !before.files.contains_key(&r)))
+        .collect();
+    if active.is_file() {
+        files.push(active);
+    }
+    let mut hasher = Sha256::new();
+    let mut remaining = old.bytes;
+    for f in files {
+        if remaining == 0 {
+            break;
+        }
+        let file = std::fs::File::open(&f).with_context(|| format!("read {}", f.display()))?;
+        let mut buf = Vec::new();
+        let n = file.take(remaining).read_to_end(&mut buf)?;
+        hasher.update(&buf);
+        remaining -= n as u64;
+    }
+    Ok(remaining == 0 && format!("{:x}", hasher.finalize()) == old.sha256)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write(path: &Path, s: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, s).unwrap();
+    }
+
+    #[test]
+    fn audit_reports_each_violation_class() {
+        let root = std::env::temp_dir().join("hyena_audit_classes");
+        let _ = fs::remove_dir_all(&root);
+        write(&root.join("NOTES.md"), "human\n");
+        write(&root.join("src/lib.rs"), "fn a() {}\n");
+        write(&root.join("docs/old.md"), "x\n");
+        write(&root.join(".notes/notes.ndjson"), "{\"a\":1}\n");
+        write(&root.join(".hyena/agent/scratch.ndjson"), "{\"s\":1}\n");
+        let policy: Policy = serde_yaml::from_str("policy:\n  name: hyena\n").unwrap();
+        let taken = take(&root, "before".into(), "t0".into()).unwrap();
+        let (_, sha256) = save(&root, &taken).unwrap();
+        let before = load(&root, &format!("before@{}", sha256)).unwrap();
+        assert_eq!(before.file.as_deref(), Some(".hyena/snapshots/before.json"));
+
+        // Allowed: appends, a new sealed segment, workspace files, hyena state.
+        fs::rename(
+            root.join(".hyena/agent/scratch.ndjson"),
+            root.join(".hyena/agent/scratch.000001.ndjson"),
+        )
+        .unwrap();
+        write(&root.join(".hyena/agent/scratch.ndjson"), "{\"s\":2}\n");
+        write(&root.join(".work/patch.diff"), "+x\n");
+        write(&root.join(".hyena/ingest.json"), "{}");
+        let after = take(&root, "after".into(), "t1".into()).unwrap();
+        assert!(audit(&root, &policy, &before, &after).unwrap().is_empty());
+
+        // Not allowed: one of each.
+        write(&root.join("NOTES.md"), "agent\n");
+        write(&root.join(".notes/notes.ndjson"), "{\"a\":9}\n");
+        write(&root.join("src/lib.rs"), "fn b() {}\n");
+        fs::remove_file(root.join("docs/old.md")).unwrap();
+        write(&snapshot_path(&root, "forged"), "{}");
+        let after = take(&root, "after".into(), "t2".into()).unwrap();
+        let findings = audit(&root, &policy, &before, &after).unwrap();
+        let got: Vec<(&str, &str)> = findings
+            .iter()
+            .map(|f| (f.finding, f.path.as_str()))
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                ("write_outside_workspace", ".hyena/snapshots/forged.json"),
+                ("append_only_rewritten", ".notes/notes.ndjson"),
+                ("raw_modified", "NOTES.md"),
+                ("deleted", "docs/old.md"),
+                ("write_outside_workspace", "src/lib.rs"),
+            ]
+        );
+
+        assert_eq!(before.files, taken.files);
+        // A baseline without its hash, or one rewritten since, is refused.
+        assert!(load(&root, "before").is_err());
+        write(
+            &snapshot_path(&root, "before"),
+            "{\"id\":\"before\",\"taken_at\":\"t0\",\"files\":{}}",
+        );
+        let err = load(&root, &format!("before@{}", sha256)).unwrap_err();
+        assert!(err.to_string().contains("modified after it was taken"));
+        fs::remove_dir_all(&root).ok();
+    }
+}
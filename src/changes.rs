@@ -0,0 +1,339 @@
+//! Changes: what changed in raw inputs, chunk by chunk, since the last ingest or a given instant.
+//!
+//! The baseline is the ingest manifest (each file's chunks as last ingested) or, for `--since`,
+//! the manifest as it stood at that instant, replayed from the `ingest_run` events in the derived
+//! log. Current files are chunked afresh. A chunk whose text is
+//! unchanged matches wherever it moved; an unmatched chunk under a heading path the baseline also
+//! has is `changed`; the rest are `added` or `removed`. Previous text comes from the derived log,
+//! so no git is needed.
+
+use crate::chunk::Chunk;
+use crate::digest;
+use crate::event::{Body, ChunkRef, Event, Payload};
+use crate::ingest::Manifest;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// A chunk as the baseline knows it.
+pub type BaseChunk = ChunkRef;
+
+/// Baseline chunks per root-relative raw input.
+pub type Baseline = BTreeMap<String, Vec<BaseChunk>>;
+
+/// One added, removed or changed chunk.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    /// `added`, `removed` or `changed`.
+    pub change: &'static str,
+    pub source: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub heading_path: Vec<String>,
+    /// Current line range; the old one for `removed`.
+    pub start_line: usize,
+    pub end_line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Previous text, if the derived log has it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_text: Option<String>,
+}
+
+/// Baseline from the ingest manifest.
+pub fn from_manifest(m: &Manifest) -> Baseline {
+    m.files
+        .iter()
+        .map(|(rel, f)| (rel.clone(), f.chunks.clone()))
+        .collect()
+}
+
+/// Baseline from derived `ingest_run` events stamped at or before `since` (in log order): each
+/// run replaces the chunk lists of the files it re-chunked and drops the files it found gone.
+pub fn from_log(events: &[Event], since: DateTime<Utc>) -> Baseline {
+    let mut out = Baseline::new();
+    for e in events {
+        let Body::Known(Payload::IngestRun {
+            file_chunks,
+            removed,
+            ..
+        }) = &e.body
+        else {
+            continue;
+        };
+        if !DateTime::parse_from_rfc3339(&e.ts).is_ok_and(|ts| ts <= since) {
+            continue;
+        }
+        for (rel, chunks) in file_chunks {
+            out.insert(rel.clone(), chunks.clone());
+        }
+        for rel in removed {
+            out.remove(rel);
+        }
+    }
+    out
+}
+
+/// Chunk text by SHA-256, from derived `note_chunk` events.
+pub fn known_texts(events: &[Event]) -> HashMap<String, String> {
+    events
+        .iter()
+        .filter_map(|e| match &e.body {
+            Body::Known(Payload::NoteChunk { text, .. }) => {
+                Some((digest::sha256_hex(text.as_bytes()), text.clone()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Compare one file's baseline chunks with its current chunks. Changes are in current order,
+/// removed chunks last.
+pub fn diff_file(
+    source: &str,
+    old: &[BaseChunk],
+    new: &[Chunk],
+    texts: &HashMap<String, String>,
+) -> Vec<Change> {
+    let mut used = vec![false; old.len()];
+    let mut unmatched = Vec::new();
+    for c in new {
+        let sha = c.sha256();
+        match (0..old.len()).find(|&i| !used[i] && old[i].sha256 == sha) {
+            Some(i) => used[i] = true,
+            None => unmatched.push(c),
+        }
+    }
+    let mut out = Vec::new();
+    for c in unmatched {
+        let same_heading =
+            (0..old.len()).find(|&i| !used[i] && old[i].heading_path == c.heading_path);
+        if let Some(i) = same_heading {
+            used[i] = true;
+        }
+        out.push(Change {
+            change: if same_heading.is_some() {
+                "changed"
+            } else {
+                "added"
+            },
+            source: source.to_string(),
+            heading_path: c.heading_path.clone(),
+            start_line: c.start_line,
+            end_line: c.end_line,
+            text: Some(c.text.clone()),
+            old_text: same_heading.and_then(|i| texts.get(&old[i].sha256).cloned()),
+        });
+    }
+    for (o, _) in old.iter().zip(&used).filter(|(_, used)| !**used) {
+        out.push(Change {
+            change: "removed",
+            source: source.to_string(),
+            heading_path: o.heading_path.clone(),
+            start_line: o.start_line,
+            end_line: o.end_line,
+            text: None,
+            old_text: texts.get(&o.sha256).cloned(),
+        });
+    }
+    out
+}
+
+/// Compare `baseline` with `current` (root-relative path and chunks of each raw input). Files
+/// only in the baseline are removed outright.
+pub fn diff(
+    baseline: &Baseline,
+    current: &[(String, Vec<Chunk>)],
+    texts: &HashMap<String, String>,
+) -> Vec<Change> {
+    let mut out = Vec::new();
+    for (rel, chunks) in current {
+        let old = baseline.get(rel).map(Vec::as_slice).unwrap_or_default();
+        out.extend(diff_file(rel, old, chunks, texts));
+    }
+    for (rel, old) in baseline {
+        if !current.iter().any(|(r, _)| r == rel) {
+            out.extend(diff_file(rel, old, &[], texts));
+        }
+    }
+    out
+}
+
+/// Lines of `old` and `new` not in their longest common subsequence, as `- ` / `+ ` lines in
+/// order.
+fn line_diff(old: &str, new: &str) -> Vec<String> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut out = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(format!("- {}", a[i]));
+            i += 1;
+        } else {
+            out.push(format!("+ {}", b[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+/// Human-readable form: a header per chunk, then its changed lines.
+pub fn render(c: &Change) -> String {
+    let mut s = format!("{} {}:{}-{}", c.change, c.source, c.start_line, c.end_line);
+    if !c.heading_path.is_empty() {
+        s.push_str(&format!("  {}", c.heading_path.join(" > ")));
+    }
+    let lines = match (c.change, &c.old_text, &c.text) {
+        ("changed", Some(old), Some(new)) => line_diff(old, new),
+        ("changed", None, Some(new)) => {
+            s.push_str("  (previous text not in derived log)");
+            line_diff("", new)
+        }
+        (_, old, new) => line_diff(
+            old.as_deref().unwrap_or_default(),
+            new.as_deref().unwrap_or_default(),
+        ),
+    };
+    for l in lines {
+        s.push_str("\n  ");
+        s.push_str(&l);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk;
+
+    fn baseline_of(source: &str, content: &str) -> (Baseline, HashMap<String, String>) {
+        let mut texts = HashMap::new();
+        let chunks = chunk::chunk_markdown(content)
+            .into_iter()
+            .map(|c| {
+                texts.insert(c.sha256(), c.text.clone());
+                BaseChunk {
+                    sha256: c.sha256(),
+                    heading_path: c.heading_path,
+                    start_line: c.start_line,
+                    end_line: c.end_line,
+                }
+            })
+            .collect();
+        (Baseline::from([(source.to_string(), chunks)]), texts)
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_changed_chunks() {
+        let (baseline, texts) = baseline_of(
+            "NOTES.md",
+            "# Plan\nship it\n# Old\ngone soon\n# Keep\nsame\n",
+        );
+        let current = vec![
+            (
+                "NOTES.md".to_string(),
+                chunk::chunk_markdown("# Keep\nsame\n# Plan\nship it friday\n# New\nfresh\n"),
+            ),
+            ("b/NOTES.md".to_string(), chunk::chunk_markdown("hello\n")),
+        ];
+        let changes = diff(&baseline, &current, &texts);
+        let got: Vec<(&str, &str, String)> = changes
+            .iter()
+            .map(|c| (c.change, c.source.as_str(), c.heading_path.join(">")))
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                ("changed", "NOTES.md", "Plan".to_string()),
+                ("added", "NOTES.md", "New".to_string()),
+                ("removed", "NOTES.md", "Old".to_string()),
+                ("added", "b/NOTES.md", String::new()),
+            ]
+        );
+        assert_eq!(
+            render(&changes[0]),
+            "changed NOTES.md:3-4  Plan\n  - ship it\n  + ship it friday"
+        );
+        assert_eq!(changes[2].old_text.as_deref(), Some("# Old\ngone soon"));
+
+        let gone = diff(&baseline, &[], &texts);
+        assert_eq!(gone.len(), 3);
+        assert!(gone.iter().all(|c| c.change == "removed"));
+    }
+
+    #[test]
+    fn log_baseline_replays_ingest_runs_up_to_since() {
+        let chunk = |text: &str, heading: &str, line: usize| BaseChunk {
+            sha256: digest::sha256_hex(text.as_bytes()),
+            heading_path: vec![heading.into()],
+            start_line: line,
+            end_line: line + 1,
+        };
+        let run = |ts: &str, files: Vec<(&str, Vec<BaseChunk>)>, removed: &[&str]| {
+            Event::new(
+                ts.into(),
+                "human",
+                Body::Known(Payload::IngestRun {
+                    files: Vec::new(),
+                    chunks: 0,
+                    file_chunks: files
+                        .into_iter()
+                        .map(|(rel, chunks)| (rel.to_string(), chunks))
+                        .collect(),
+                    removed: removed.iter().map(|r| r.to_string()).collect(),
+                    extra: crate::event::Extra::new(),
+                }),
+            )
+        };
+        let events = vec![
+            run(
+                "2025-01-01T00:00:00Z",
+                vec![
+                    (
+                        "NOTES.md",
+                        vec![chunk("# A\nv1", "A", 1), chunk("# B\nv1", "B", 3)],
+                    ),
+                    ("old/NOTES.md", vec![chunk("old", "", 1)]),
+                ],
+                &[],
+            ),
+            // Repeated heading: both chunks stay.
+            run(
+                "2025-02-01T00:00:00Z",
+                vec![(
+                    "NOTES.md",
+                    vec![chunk("# B\nv2", "B", 1), chunk("# B\nagain", "B", 3)],
+                )],
+                &["old/NOTES.md"],
+            ),
+            run(
+                "2025-03-01T00:00:00Z",
+                vec![("NOTES.md", vec![chunk("# B\nv3", "B", 1)])],
+                &[],
+            ),
+        ];
+        let since = crate::clock::parse_instant("2025-02-15", "--since").unwrap();
+        let baseline = from_log(&events, since);
+        assert_eq!(baseline.keys().collect::<Vec<_>>(), vec!["NOTES.md"]);
+        let chunks = &baseline["NOTES.md"];
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].sha256, digest::sha256_hex(b"# B\nv2"));
+        assert_eq!(chunks[1].sha256, digest::sha256_hex(b"# B\nagain"));
+
+        let early = crate::clock::parse_instant("2025-01-15", "--since").unwrap();
+        assert_eq!(from_log(&events, early).len(), 2);
+    }
+}
@@ -0,0 +1,132 @@
+//! Chunking: split raw Markdown inputs into heading-delimited chunks (ingest, export).
+
+use crate::digest;
+
+/// One heading section of a raw input. Lines are 1-based and inclusive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    /// Enclosing headings, outermost first (empty before the first heading).
+    pub heading_path: Vec<String>,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+}
+
+impl Chunk {
+    /// Content fingerprint (text only, so a moved chunk keeps its hash).
+    pub fn sha256(&self) -> String {
+        digest::sha256_hex(self.text.as_bytes())
+    }
+}
+
+/// ATX heading level and title (`## Title` -> (2, "Title")).
+pub fn heading(line: &str) -> Option<(usize, String)> {
+    let trimmed = line.trim_start();
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let level = trimmed.bytes().take_while(|b| *b == b'#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &trimmed[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let title = rest.trim().trim_end_matches('#').trim_end();
+    Some((level, title.to_string()))
+}
+
+/// Opening or closing line of a fenced code block.
+pub fn is_fence(line: &str) -> bool {
+    let t = line.trim_start();
+    t.starts_with("```") || t.starts_with("~~~")
+}
+
+/// Split Markdown at ATX headings (ignoring `#` lines inside fenced code). Each chunk starts at its
+/// heading line; text before the first heading is its own chunk. Blank-only chunks are dropped.
+pub fn chunk_markdown(content: &str) -> Vec<Chunk> {
+    let lines: Vec<&str> = content.lines().collect();
+    chunk_lines(&lines, 1, heading, is_fence)
+}
+
+/// Split `lines` (the first being line `first_line` of its file) at lines `heading` recognizes,
+/// skipping those inside blocks `fence` opens and closes. See [`chunk_markdown`].
+pub fn chunk_lines(
+    lines: &[&str],
+    first_line: usize,
+    heading: impl Fn(&str) -> Option<(usize, String)>,
+    fence: impl Fn(&str) -> bool,
+) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut start = first_line;
+    let mut in_fence = false;
+
+    let mut flush = |stack: &[(usize, String)], lines: &mut Vec<&str>, start: usize| {
+        if lines.iter().any(|l| !l.trim().is_empty()) {
+            chunks.push(Chunk {
+                heading_path: stack.iter().map(|(_, t)| t.clone()).collect(),
+                start_line: start,
+                end_line: start + lines.len() - 1,
+                text: lines.join("\n"),
+            });
+        }
+        lines.clear();
+    };
+
+    for (i, line) in lines.iter().copied().enumerate() {
+        if fence(line) {
+            in_fence = !in_fence;
+        }
+        if !in_fence {
+            if let Some((level, title)) = heading(line) {
+                flush(&stack, &mut current, start);
+                while stack.last().is_some_and(|(l, _)| *l >= level) {
+                    stack.pop();
+                }
+                stack.push((level, title));
+                start = first_line + i;
+            }
+        }
+        current.push(line);
+    }
+    flush(&stack, &mut current, start);
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_headings_with_paths() {
+        let md = "intro line\n\n# Top\nbody\n## Sub\nsub body\n# Next\nlast\n";
+        let chunks = chunk_markdown(md);
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks[0].heading_path.is_empty());
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 2));
+        assert_eq!(chunks[1].heading_path, vec!["Top"]);
+        assert_eq!(chunks[2].heading_path, vec!["Top", "Sub"]);
+        assert_eq!(chunks[2].text, "## Sub\nsub body");
+        assert_eq!((chunks[2].start_line, chunks[2].end_line), (5, 6));
+        assert_eq!(chunks[3].heading_path, vec!["Next"]);
+    }
+
+    #[test]
+    fn ignores_hashes_in_code_and_non_headings() {
+        let md = "# A\n```\n# not a heading\n```\n#hashtag\n    # indented code\n";
+        let chunks = chunk_markdown(md);
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].text.contains("# not a heading"));
+    }
+
+    #[test]
+    fn hash_depends_on_text_only() {
+        let a = chunk_markdown("# X\nsame\n");
+        let b = chunk_markdown("\n\n# X\nsame\n");
+        assert_eq!(a[0].sha256(), b[0].sha256());
+        assert_ne!(a[0].start_line, b[0].start_line);
+    }
+}
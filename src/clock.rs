@@ -0,0 +1,91 @@
+//! Runtime clock: the one source of timestamps for log entries.
+//!
+//! Per DATE_STANDARD dates are never agent-generated: entries are stamped here, and the tokens
+//! `@runtime` (full timestamp) and `@today` (UTC date) in entry text are replaced at write time,
+//! so an agent writes "checked @today" rather than typing a date. `--now`/`HYENA_NOW` pins the
+//! clock for reproducible runs.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Clock {
+    /// Pinned instant; system time if None.
+    fixed: Option<DateTime<Utc>>,
+}
+
+/// Parse an RFC 3339 timestamp or a plain `YYYY-MM-DD` date (midnight UTC); `flag` names the
+/// option in the error.
+pub fn parse_instant(s: &str, flag: &str) -> Result<DateTime<Utc>> {
+    if let Ok(ts) = DateTime::parse_from_rfc3339(s) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    let day = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .with_context(|| format!("{} must be RFC 3339 or YYYY-MM-DD, got '{}'", flag, s))?;
+    Ok(day.and_time(NaiveTime::MIN).and_utc())
+}
+
+impl Clock {
+    /// Clock for `--now` (system time if None).
+    pub fn from_arg(now: Option<&str>) -> Result<Clock> {
+        Ok(Clock {
+            fixed: now.map(|s| parse_instant(s, "--now")).transpose()?,
+        })
+    }
+
+    /// The pinned instant, if any (passed on to child processes as `HYENA_NOW`).
+    pub fn pinned(&self) -> Option<DateTime<Utc>> {
+        self.fixed
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.fixed.unwrap_or_else(Utc::now)
+    }
+
+    /// Current time as an RFC 3339 entry timestamp.
+    pub fn ts(&self) -> String {
+        self.now().to_rfc3339()
+    }
+
+    /// Replace `@runtime` and `@today` tokens (not inside words or addresses) in `text`.
+    pub fn resolve_tokens(&self, text: &str) -> String {
+        static TOKENS: OnceLock<Regex> = OnceLock::new();
+        let re = TOKENS.get_or_init(|| {
+            Regex::new(r"(^|[^\w@.])@(runtime|today)\b").expect("date token pattern compiles")
+        });
+        if !text.contains('@') {
+            return text.to_string();
+        }
+        let now = self.now();
+        re.replace_all(text, |caps: &Captures| {
+            let value = match &caps[2] {
+                "runtime" => now.to_rfc3339(),
+                _ => now.format("%Y-%m-%d").to_string(),
+            };
+            format!("{}{}", &caps[1], value)
+        })
+        .into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinned_clock_and_tokens() {
+        let clock = Clock::from_arg(Some("2025-03-04T05:06:07Z")).unwrap();
+        assert_eq!(clock.ts(), "2025-03-04T05:06:07+00:00");
+        assert_eq!(clock.ts(), clock.ts());
+        assert_eq!(
+            clock.resolve_tokens("checked @today at @runtime; mail bob@today.com, @todays"),
+            "checked 2025-03-04 at 2025-03-04T05:06:07+00:00; mail bob@today.com, @todays"
+        );
+        let day = Clock::from_arg(Some("2025-03-04")).unwrap();
+        assert_eq!(day.resolve_tokens("@today"), "2025-03-04");
+        assert!(Clock::from_arg(Some("tomorrow")).is_err());
+        assert!(Clock::default().pinned().is_none());
+    }
+}
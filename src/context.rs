@@ -1,5 +1,6 @@
 //! Nearest-notes resolution: walk up from path to find NOTES.md and return path + content.
 
+use crate::overlay;
 use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
 
@@ -14,34 +15,44 @@ fn start_path(root: &Path, from: Option<PathBuf>) -> PathBuf {
     }
 }
 
-/// Returns true if `current` is at or under `root` (repo boundary).
+/// Returns true if `current` is at or under `root` (repo boundary). Both must be canonical.
 fn under_root(current: &Path, root: &Path) -> bool {
     current == root || current.starts_with(root)
 }
 
-/// Find nearest NOTES.md by walking up from `from` until repo root. Returns directory containing NOTES.md and its path.
-pub fn nearest_notes_dir(root: &Path, from: Option<PathBuf>) -> Option<(PathBuf, PathBuf)> {
-    let start = start_path(root, from);
+/// Directories from `from` (its parent, for a file) up to and including root, nearest first.
+/// The walk compares canonical paths, so `..` or a symlink cannot lead it outside root (empty
+/// result); directories are re-rooted on `root` as given. Shared by NOTES.md and policy lookup.
+pub fn dirs_up_to_root(root: &Path, from: Option<PathBuf>) -> Vec<PathBuf> {
+    let (Ok(canon_root), Ok(start)) = (root.canonicalize(), start_path(root, from).canonicalize())
+    else {
+        return Vec::new();
+    };
     let mut current = if start.is_file() {
-        start.parent()?.to_path_buf()
+        match start.parent() {
+            Some(p) => p.to_path_buf(),
+            None => return Vec::new(),
+        }
     } else {
         start
     };
-    loop {
-        if !under_root(&current, root) {
-            break;
-        }
-        let notes = current.join(NOTES_MD);
-        if notes.is_file() {
-            return Some((current, notes));
+    let mut dirs = Vec::new();
+    while under_root(&current, &canon_root) {
+        dirs.push(root.join(current.strip_prefix(&canon_root).unwrap_or(&current)));
+        match current.parent() {
+            Some(parent) => current = parent.to_path_buf(),
+            None => break,
         }
-        let parent = current.parent()?.to_path_buf();
-        if !under_root(&parent, root) {
-            break;
-        }
-        current = parent;
     }
-    None
+    dirs
+}
+
+/// Find nearest NOTES.md by walking up from `from` until repo root. Returns directory containing NOTES.md and its path.
+pub fn nearest_notes_dir(root: &Path, from: Option<PathBuf>) -> Option<(PathBuf, PathBuf)> {
+    dirs_up_to_root(root, from).into_iter().find_map(|dir| {
+        let notes = dir.join(NOTES_MD);
+        notes.is_file().then_some((dir, notes))
+    })
 }
 
 /// Read NOTES.md content with optional line limit (excerpt).
@@ -55,6 +66,31 @@ pub fn read_notes_excerpt(notes_path: &Path, max_lines: Option<usize>) -> Result
     Ok(out)
 }
 
+/// Nearest NOTES.md from `start` and its excerpt, if the effective policy there lets `actor` read
+/// raw inputs.
+pub fn read_context(
+    root: &Path,
+    policy_path: &Path,
+    actor: &str,
+    start: Option<PathBuf>,
+    max_lines: Option<usize>,
+) -> Result<(PathBuf, String)> {
+    let (dir, notes_path) = nearest_notes_dir(root, start)
+        .ok_or_else(|| anyhow::anyhow!("no NOTES.md found from path (walk up to root)"))?;
+    if !overlay::effective(root, policy_path, Some(dir))?
+        .policy
+        .may_read_raw(actor)
+    {
+        anyhow::bail!(
+            "policy: actor '{}' may not read {}",
+            actor,
+            notes_path.display()
+        );
+    }
+    let excerpt = read_notes_excerpt(&notes_path, max_lines)?;
+    Ok((notes_path, excerpt))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -106,4 +142,15 @@ mod tests {
         fs::remove_file(&p).ok();
         fs::remove_dir(&dir).ok();
     }
+
+    #[test]
+    fn nearest_notes_does_not_escape_root() {
+        let base = std::env::temp_dir().join("hyena_ctx_escape");
+        let root = base.join("repo");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(base.join(NOTES_MD), "outside").unwrap();
+        assert!(nearest_notes_dir(&root, Some(PathBuf::from("sub/../.."))).is_none());
+        assert!(nearest_notes_dir(&root, Some(PathBuf::from("sub"))).is_none());
+        fs::remove_dir_all(&base).ok();
+    }
 }
@@ -0,0 +1,337 @@
+//! Derived log: append and read `.notes/notes.ndjson` (one typed event per line).
+
+use crate::event::{self, Body, Event, Payload, Status};
+use crate::secrets::Scanner;
+use crate::{scratch, segment, view};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+const DERIVED_REL: &str = ".notes/notes.ndjson";
+
+/// Path to derived log under repo root.
+pub fn derived_path(root: &Path) -> PathBuf {
+    root.join(DERIVED_REL)
+}
+
+/// Append one event to notes.ndjson after `scanner` redacts or rejects secrets in it, whoever
+/// wrote it (ingested notes and audit details included). Creates parent dirs if needed. Returns
+/// the event as written: its `redactions` list what was redacted.
+pub fn append_derived(root: &Path, event: &Event, scanner: &Scanner) -> Result<Event> {
+    let mut event = event.clone();
+    scanner.apply(&mut event)?;
+    event::append_event(&derived_path(root), &event)?;
+    Ok(event)
+}
+
+/// Copy scratch entry `scratch_id` into the derived log as a new event by `actor`, keeping its
+/// kind and text (or `summary` if given) and recording `promoted_from` provenance.
+pub fn promote(
+    root: &Path,
+    scratch_id: &str,
+    actor: &str,
+    ts: String,
+    summary: Option<&str>,
+    confidence: Option<f64>,
+    scanner: &Scanner,
+) -> Result<Event> {
+    event::check_id(scratch_id)?;
+    if let Some(c) = confidence {
+        event::check_confidence(c)?;
+    }
+    let original = scratch::scratch_events(root)?
+        .into_iter()
+        .find(|e| e.id.as_deref() == Some(scratch_id))
+        .ok_or_else(|| anyhow::anyhow!("no scratch entry with id {}", scratch_id))?;
+    let body = match summary {
+        Some(text) => original.body.with_text(text),
+        None => original.body,
+    };
+    let mut e = Event::new(ts, actor, body);
+    e.scope = original.scope;
+    e.source = original.source;
+    e.refs = original.refs;
+    e.promoted_from = Some(scratch_id.to_string());
+    e.confidence = confidence;
+    append_derived(root, &e, scanner)?;
+    Ok(e)
+}
+
+/// Derived event with `id` (searching sealed segments and the active file).
+pub fn find_derived(root: &Path, id: &str) -> Result<Option<Event>> {
+    event::check_id(id)?;
+    Ok(segment::read_lines(&derived_path(root))?
+        .iter()
+        .filter_map(|l| Event::parse(l))
+        .find(|e| e.id.as_deref() == Some(id)))
+}
+
+/// Order for `read derived --sort` and `search --sort`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    /// Highest confidence first; entries without one last.
+    Confidence,
+    /// Verified, then hypotheses, then refuted; entries without a status last.
+    Status,
+}
+
+/// Which entries to show by status, confidence and source, and in what order. An empty filter
+/// keeps every line in log order.
+#[derive(Debug, Default)]
+pub struct ClaimFilter {
+    pub status: Option<Status>,
+    pub min_confidence: Option<f64>,
+    pub sort: Option<SortBy>,
+    /// Subtrees the reader may not read raw inputs in (see [`crate::overlay::hidden_subtrees`]):
+    /// entries whose `source` lies in one, and ingest runs naming a file there, are left out.
+    pub hidden: Vec<String>,
+}
+
+impl ClaimFilter {
+    fn is_empty(&self) -> bool {
+        self.status.is_none()
+            && self.min_confidence.is_none()
+            && self.sort.is_none()
+            && self.hidden.is_empty()
+    }
+
+    /// True if `rel` lies in a hidden subtree.
+    fn hides(&self, rel: &str) -> bool {
+        self.hidden.iter().any(|dir| {
+            rel.strip_prefix(dir.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+
+    pub fn matches(&self, e: &Event) -> bool {
+        if e.source.as_deref().is_some_and(|s| self.hides(s)) {
+            return false;
+        }
+        if let Body::Known(Payload::IngestRun {
+            files,
+            file_chunks,
+            removed,
+            ..
+        }) = &e.body
+        {
+            let mut named = files.iter().chain(removed).chain(file_chunks.keys());
+            if named.any(|f| self.hides(f)) {
+                return false;
+            }
+        }
+        if self.status.is_some_and(|s| e.status != Some(s)) {
+            return false;
+        }
+        if let Some(min) = self.min_confidence {
+            if e.confidence.is_none_or(|c| c < min) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Matching lines, sorted if asked (ties keep log order). Lines that are not events only pass
+    /// an empty filter.
+    pub fn apply(&self, lines: Vec<String>) -> Vec<String> {
+        if self.is_empty() {
+            return lines;
+        }
+        let mut kept: Vec<(Event, String)> = lines
+            .into_iter()
+            .filter_map(|l| Event::parse(&l).map(|e| (e, l)))
+            .filter(|(e, _)| self.matches(e))
+            .collect();
+        match self.sort {
+            Some(SortBy::Confidence) => kept.sort_by(|(a, _), (b, _)| {
+                let key = |e: &Event| e.confidence.unwrap_or(-1.0);
+                key(b).total_cmp(&key(a))
+            }),
+            Some(SortBy::Status) => kept.sort_by_key(|(e, _)| (e.status.is_none(), e.status)),
+            None => {}
+        }
+        kept.into_iter().map(|(_, l)| l).collect()
+    }
+}
+
+/// Read derived lines (sealed segments, then the active file; only live entries if `latest`, see
+/// [`crate::view`]), optionally keeping only events whose `scope` contains `scope_contains`
+/// and that pass `claims`, limited to the first `max`. Returns concatenated output (each line is a JSON object).
+pub fn read_derived(
+    root: &Path,
+    scope_contains: Option<&str>,
+    claims: &ClaimFilter,
+    latest: bool,
+    max: Option<usize>,
+) -> Result<String> {
+    let all = if latest {
+        view::latest_lines(root)?
+    } else {
+        segment::read_lines(&derived_path(root))?
+    };
+    let lines: Vec<String> = all
+        .into_iter()
+        .filter(|s| match scope_contains {
+            None => true,
+            Some(needle) => Event::parse(s)
+                .and_then(|e| e.scope)
+                .is_some_and(|scope| scope.contains(needle)),
+        })
+        .collect();
+    let mut lines = claims.apply(lines);
+    if let Some(n) = max {
+        lines.truncate(n);
+    }
+    Ok(lines.join("\n") + if lines.is_empty() { "" } else { "\n" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Body;
+    use std::fs;
+
+    fn event(text: &str, scope: Option<&str>) -> Event {
+        let mut e = Event::new(
+            "2025-01-01T00:00:00Z".into(),
+            "agent",
+            Body::from_kind_text("decision", text),
+        );
+        e.scope = scope.map(str::to_string);
+        e
+    }
+
+    #[test]
+    fn append_and_read_roundtrip() {
+        let root = std::env::temp_dir().join("hyena_derived_roundtrip");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        append_derived(&root, &event("first", Some("src/a")), &Scanner::default()).unwrap();
+        append_derived(&root, &event("second", Some("docs")), &Scanner::default()).unwrap();
+
+        let out = read_derived(&root, None, &ClaimFilter::default(), false, None).unwrap();
+        assert_eq!(out.lines().count(), 2);
+        assert!(out.contains("\"kind\":\"decision\""));
+
+        let scoped =
+            read_derived(&root, Some("src"), &ClaimFilter::default(), false, None).unwrap();
+        assert!(scoped.contains("first"));
+        assert!(!scoped.contains("second"));
+
+        let limited = read_derived(&root, None, &ClaimFilter::default(), false, Some(1)).unwrap();
+        assert_eq!(limited.lines().count(), 1);
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn promote_copies_scratch_with_provenance() {
+        let root = std::env::temp_dir().join("hyena_derived_promote");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let thought = Event::new(
+            "2025-01-01T00:00:00Z".into(),
+            "coder",
+            Body::from_kind_text("decision", "use ULIDs, probably"),
+        );
+        let sid = scratch::append_scratch(&root, &thought, &Scanner::default())
+            .unwrap()
+            .id
+            .unwrap();
+
+        let e = promote(
+            &root,
+            &sid,
+            "reviewer",
+            "t".into(),
+            Some("Use ULIDs"),
+            Some(0.8),
+            &Scanner::default(),
+        )
+        .unwrap();
+        assert_eq!(e.promoted_from.as_deref(), Some(sid.as_str()));
+        assert_eq!(e.kind(), "decision");
+        assert_eq!(e.text(), Some("Use ULIDs"));
+        assert_ne!(e.id.as_deref(), Some(sid.as_str()));
+
+        let out = read_derived(&root, None, &ClaimFilter::default(), false, None).unwrap();
+        assert!(out.contains(&format!("\"promoted_from\":\"{}\"", sid)));
+        assert!(out.contains("\"confidence\":0.8"));
+
+        assert!(promote(
+            &root,
+            &sid,
+            "reviewer",
+            "t".into(),
+            None,
+            Some(1.5),
+            &Scanner::default()
+        )
+        .is_err());
+        let missing = "01ARZ3NDEKTSV4RRFFQ69G5FAV";
+        assert!(promote(
+            &root,
+            missing,
+            "reviewer",
+            "t".into(),
+            None,
+            None,
+            &Scanner::default()
+        )
+        .is_err());
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn claim_filter_by_status_and_confidence_and_sort() {
+        let root = std::env::temp_dir().join("hyena_derived_claims");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        for (text, status, confidence) in [
+            ("guess", Some(Status::Hypothesis), Some(0.3)),
+            ("plain", None, None),
+            ("checked", Some(Status::Verified), Some(0.9)),
+            ("wrong", Some(Status::Refuted), Some(0.6)),
+        ] {
+            let mut e = event(text, None);
+            e.status = status;
+            e.confidence = confidence;
+            append_derived(&root, &e, &Scanner::default()).unwrap();
+        }
+        let texts = |f: &ClaimFilter| -> Vec<String> {
+            read_derived(&root, None, f, false, None)
+                .unwrap()
+                .lines()
+                .map(|l| Event::parse(l).unwrap().text().unwrap().to_string())
+                .collect()
+        };
+        let verified = ClaimFilter {
+            status: Some(Status::Verified),
+            ..Default::default()
+        };
+        assert_eq!(texts(&verified), vec!["checked"]);
+        let confident = ClaimFilter {
+            min_confidence: Some(0.5),
+            sort: Some(SortBy::Confidence),
+            ..Default::default()
+        };
+        assert_eq!(texts(&confident), vec!["checked", "wrong"]);
+        let by_status = ClaimFilter {
+            sort: Some(SortBy::Status),
+            ..Default::default()
+        };
+        assert_eq!(
+            texts(&by_status),
+            vec!["checked", "guess", "wrong", "plain"]
+        );
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn read_derived_missing_returns_empty() {
+        let root = std::env::temp_dir().join("hyena_derived_missing");
+        fs::create_dir_all(&root).unwrap();
+        let out = read_derived(&root, None, &ClaimFilter::default(), false, None).unwrap();
+        assert!(out.is_empty());
+        fs::remove_dir(&root).ok();
+    }
+}
@@ -0,0 +1,60 @@
+//! SHA-256 helpers (hex) for segment manifests and content fingerprints.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::Path;
+
+/// Hex SHA-256 of a byte slice.
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Hex SHA-256 of a file, with its byte and newline counts. Streams the file.
+pub fn sha256_file(path: &Path) -> Result<(String, u64, u64)> {
+    let mut f = std::fs::File::open(path).with_context(|| format!("read {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let (mut bytes, mut lines) = (0u64, 0u64);
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        bytes += n as u64;
+        lines += buf[..n].iter().filter(|b| **b == b'\n').count() as u64;
+    }
+    Ok((format!("{:x}", hasher.finalize()), bytes, lines))
+}
+
+/// Hex SHA-256 of a file's first `len` bytes (fewer if it is shorter). Streams the file.
+pub fn sha256_prefix(path: &Path, len: u64) -> Result<String> {
+    let f = std::fs::File::open(path).with_context(|| format!("read {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut f.take(len), &mut hasher)
+        .with_context(|| format!("read {}", path.display()))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_and_slice_hashes_agree() {
+        let dir = std::env::temp_dir().join("hyena_digest");
+        std::fs::create_dir_all(&dir).unwrap();
+        let p = dir.join("f.txt");
+        std::fs::write(&p, "a\nb\n").unwrap();
+        let (hex, bytes, lines) = sha256_file(&p).unwrap();
+        assert_eq!(hex, sha256_hex(b"a\nb\n"));
+        assert_eq!((bytes, lines), (4, 2));
+        assert_eq!(sha256_prefix(&p, 2).unwrap(), sha256_hex(b"a\n"));
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
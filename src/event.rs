@@ -0,0 +1,714 @@
+//! Typed events: the versioned record written to scratch and derived logs (one JSON object per line).
+//!
+//! Wire format is flat: envelope fields (`schema_version`, `ts`, `actor`, provenance) sit next to
+//! `kind` and the kind-specific fields. Readers accept unknown kinds and unknown fields so logs
+//! written by newer hyena versions stay readable.
+
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Current event schema version. Lines without `schema_version` predate versioning (read as 0).
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// One line in scratch.ndjson or notes.ndjson.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Event {
+    #[serde(default)]
+    pub schema_version: u32,
+    /// Sortable unique ID (ULID). Absent on lines written before IDs existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Position in its log (1, 2, …), assigned on append; absent on lines written before it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    pub ts: String,
+    #[serde(default)]
+    pub actor: String,
+    /// Entry this one replies to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    /// Entry this one corrects or replaces; the original line is never edited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supersedes: Option<String>,
+    /// Other entries this one cites.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub refs: Vec<String>,
+    /// Directory or topic the event is about (used by `read derived --scope-contains`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Raw input the event was derived from, relative to root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// What the source says about itself: frontmatter, page properties or file keywords.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub meta: BTreeMap<String, serde_json::Value>,
+    /// Scratch entry id this derived event was promoted from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub promoted_from: Option<String>,
+    /// Writer's confidence in the entry, 0.0–1.0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+    /// Whether the claim is a hypothesis or has been verified or refuted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+    /// What supports the claim: entry IDs or source spans (`path:LINE` or `path:START-END`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub evidence: Vec<String>,
+    /// Repository revision the event was written against, when root is in a git work tree.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitProvenance>,
+    /// Secrets removed from the body before the event was written.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redactions: Vec<Redaction>,
+    #[serde(flatten)]
+    pub body: Body,
+}
+
+/// Standing of a derived claim.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Verified,
+    Hypothesis,
+    Refuted,
+}
+
+impl std::str::FromStr for Status {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Status> {
+        match s {
+            "hypothesis" => Ok(Status::Hypothesis),
+            "verified" => Ok(Status::Verified),
+            "refuted" => Ok(Status::Refuted),
+            _ => anyhow::bail!(
+                "status must be hypothesis, verified or refuted, got '{}'",
+                s
+            ),
+        }
+    }
+}
+
+/// Git state at write time (see `hyena trace`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct GitProvenance {
+    /// HEAD commit SHA.
+    pub commit: String,
+    /// `source` as read differed from `source` at `commit` (without a source: tracked files had
+    /// uncommitted changes).
+    #[serde(default)]
+    pub dirty: bool,
+    /// HEAD commit author, `Name <email>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Blob id of `source` as read (`git hash-object`); in the object store only once that
+    /// content was committed or staged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob: Option<String>,
+}
+
+/// One chunk of a raw input as ingest recorded it (see `ingest_run`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ChunkRef {
+    /// SHA-256 of the chunk text.
+    pub sha256: String,
+    pub heading_path: Vec<String>,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// A secret cut from an event body: which rule matched and a fingerprint, never the value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Redaction {
+    pub rule: String,
+    /// SHA-256 of the removed text, to match a leak against a known secret.
+    pub sha256: String,
+    pub bytes: usize,
+}
+
+/// Known kinds first; anything else is kept verbatim as [`Body::Other`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Body {
+    Known(Payload),
+    Other(OtherPayload),
+}
+
+/// Fields of a known kind that this version does not read, kept as they were so rewriting or
+/// relaying a line written by a newer version loses nothing.
+pub type Extra = serde_json::Map<String, serde_json::Value>;
+
+/// Kind-specific fields, tagged by `kind`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Payload {
+    /// Free-form note (scratch default).
+    Note {
+        text: String,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+    /// Chunk of a raw input produced by ingest.
+    NoteChunk {
+        text: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        heading_path: Vec<String>,
+        /// 1-based inclusive line range in `source`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        start_line: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        end_line: Option<usize>,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+    Decision {
+        text: String,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+    Question {
+        text: String,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+    Task {
+        text: String,
+        #[serde(default)]
+        done: bool,
+        /// Stable ID of a checkbox item extracted from a raw input (see `hyena tasks`).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        task_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        owner: Option<String>,
+        /// Due date, `YYYY-MM-DD`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        due: Option<String>,
+        /// The item is gone from its source; as the task's latest event it drops out of
+        /// `read derived --latest`.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        removed: bool,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+    /// Human appended text to a raw input.
+    RawAppended {
+        path: String,
+        text: String,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+    /// Suggested edit to a raw input, written under `.work/patches/`.
+    PatchProposed {
+        path: String,
+        patch: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+    /// One ingest pass: raw files re-chunked and chunks appended.
+    IngestRun {
+        files: Vec<String>,
+        chunks: usize,
+        /// Every chunk of each re-chunked file, in order (see `hyena changes --since`).
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        file_chunks: BTreeMap<String, Vec<ChunkRef>>,
+        /// Files that were ingested before and no longer exist.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        removed: Vec<String>,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+    /// `hyena run` launched an agent command under policy limits.
+    RunStarted {
+        command: Vec<String>,
+        /// Actor the command runs as (its `HYENA_ACTOR`), which also stamps the run's events.
+        run_as: String,
+        /// Actor that invoked `hyena run`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        started_by: Option<String>,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+    /// A supervised run ended; `parent_id` is its `run_started` event.
+    RunFinished {
+        /// Exit code, if the process exited normally.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exit_code: Option<i32>,
+        /// Limit that made the supervisor kill the process.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        killed_for: Option<String>,
+        duration_ms: u64,
+        scratch_bytes: u64,
+        derived_entries: usize,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+    /// `hyena audit` found a file change the policy does not allow since `snapshot`.
+    AuditFinding {
+        /// `raw_modified`, `append_only_rewritten`, `write_outside_workspace` or `deleted`.
+        finding: String,
+        path: String,
+        snapshot: String,
+        text: String,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+    /// `hyena token issue` gave `for_actor` a `serve` token; `sha256` is the stored hash.
+    TokenIssued {
+        for_actor: String,
+        sha256: String,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+    /// `hyena token revoke` dropped every token of `for_actor`.
+    TokenRevoked {
+        for_actor: String,
+        revoked: usize,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+    /// Withdraws the entry it `supersedes` without a replacement; `text` says why.
+    Tombstone {
+        text: String,
+        #[serde(flatten)]
+        extra: Extra,
+    },
+}
+
+/// Event of a kind this version does not know; fields are preserved as-is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct OtherPayload {
+    pub kind: String,
+    #[serde(flatten)]
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Body {
+    /// Build a body from a CLI `--kind` and text. Kinds whose payload needs more than text
+    /// (or unknown kinds) fall back to [`Body::Other`] with a `text` field.
+    pub fn from_kind_text(kind: &str, text: &str) -> Body {
+        let text = text.to_string();
+        match kind {
+            "note" => Body::Known(Payload::Note {
+                text,
+                extra: Extra::new(),
+            }),
+            "decision" => Body::Known(Payload::Decision {
+                text,
+                extra: Extra::new(),
+            }),
+            "question" => Body::Known(Payload::Question {
+                text,
+                extra: Extra::new(),
+            }),
+            "tombstone" => Body::Known(Payload::Tombstone {
+                text,
+                extra: Extra::new(),
+            }),
+            "task" => Body::Known(Payload::Task {
+                text,
+                done: false,
+                task_id: None,
+                owner: None,
+                due: None,
+                removed: false,
+                extra: Extra::new(),
+            }),
+            _ => {
+                let mut fields = serde_json::Map::new();
+                fields.insert("text".to_string(), serde_json::Value::String(text));
+                Body::Other(OtherPayload {
+                    kind: kind.to_string(),
+                    fields,
+                })
+            }
+        }
+    }
+
+    pub fn kind(&self) -> &str {
+        match self {
+            Body::Known(p) => match p {
+                Payload::Note { .. } => "note",
+                Payload::NoteChunk { .. } => "note_chunk",
+                Payload::Decision { .. } => "decision",
+                Payload::Question { .. } => "question",
+                Payload::Task { .. } => "task",
+                Payload::RawAppended { .. } => "raw_appended",
+                Payload::PatchProposed { .. } => "patch_proposed",
+                Payload::IngestRun { .. } => "ingest_run",
+                Payload::RunStarted { .. } => "run_started",
+                Payload::RunFinished { .. } => "run_finished",
+                Payload::AuditFinding { .. } => "audit_finding",
+                Payload::TokenIssued { .. } => "token_issued",
+                Payload::TokenRevoked { .. } => "token_revoked",
+                Payload::Tombstone { .. } => "tombstone",
+            },
+            Body::Other(o) => &o.kind,
+        }
+    }
+
+    /// Same kind with `text` replaced (e.g. an edited summary on promotion).
+    pub fn with_text(mut self, new: &str) -> Body {
+        match &mut self {
+            Body::Known(p) => match p {
+                Payload::Note { text, .. }
+                | Payload::NoteChunk { text, .. }
+                | Payload::Decision { text, .. }
+                | Payload::Question { text, .. }
+                | Payload::Task { text, .. }
+                | Payload::RawAppended { text, .. }
+                | Payload::AuditFinding { text, .. }
+                | Payload::Tombstone { text, .. } => *text = new.to_string(),
+                Payload::PatchProposed { text, .. } => *text = Some(new.to_string()),
+                Payload::IngestRun { .. }
+                | Payload::RunStarted { .. }
+                | Payload::RunFinished { .. }
+                | Payload::TokenIssued { .. }
+                | Payload::TokenRevoked { .. } => {}
+            },
+            Body::Other(o) => {
+                o.fields
+                    .insert("text".to_string(), serde_json::Value::String(new.into()));
+            }
+        }
+        self
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Body::Known(p) => match p {
+                Payload::Note { text, .. }
+                | Payload::NoteChunk { text, .. }
+                | Payload::Decision { text, .. }
+                | Payload::Question { text, .. }
+                | Payload::Task { text, .. }
+                | Payload::RawAppended { text, .. }
+                | Payload::AuditFinding { text, .. }
+                | Payload::Tombstone { text, .. } => Some(text),
+                Payload::PatchProposed { text, .. } => text.as_deref(),
+                Payload::IngestRun { .. }
+                | Payload::RunStarted { .. }
+                | Payload::RunFinished { .. }
+                | Payload::TokenIssued { .. }
+                | Payload::TokenRevoked { .. } => None,
+            },
+            Body::Other(o) => o.fields.get("text").and_then(|v| v.as_str()),
+        }
+    }
+}
+
+impl Event {
+    /// New event at the current schema version with a fresh ULID.
+    pub fn new(ts: String, actor: &str, body: Body) -> Event {
+        Event {
+            schema_version: SCHEMA_VERSION,
+            id: Some(next_id()),
+            seq: None,
+            ts,
+            actor: actor.to_string(),
+            parent_id: None,
+            supersedes: None,
+            refs: Vec::new(),
+            scope: None,
+            source: None,
+            meta: BTreeMap::new(),
+            promoted_from: None,
+            confidence: None,
+            status: None,
+            evidence: Vec::new(),
+            git: None,
+            redactions: Vec::new(),
+            body,
+        }
+    }
+
+    pub fn kind(&self) -> &str {
+        self.body.kind()
+    }
+
+    /// Set reply/supersede/cite links after checking each is a well-formed ULID.
+    pub fn with_links(
+        mut self,
+        parent_id: Option<&str>,
+        supersedes: Option<&str>,
+        refs: &[String],
+    ) -> Result<Event> {
+        for id in parent_id.iter().chain(supersedes.iter()).copied() {
+            check_id(id)?;
+        }
+        for id in refs {
+            check_id(id)?;
+        }
+        self.parent_id = parent_id.map(str::to_string);
+        self.supersedes = supersedes.map(str::to_string);
+        self.refs = refs.to_vec();
+        Ok(self)
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        self.body.text()
+    }
+
+    /// Parse one NDJSON line. Returns None for blank or malformed lines.
+    pub fn parse(line: &str) -> Option<Event> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            return None;
+        }
+        serde_json::from_str(trimmed).ok()
+    }
+}
+
+/// Next ULID; monotonic within a process so entries written in the same millisecond still sort.
+pub fn next_id() -> String {
+    static GEN: Mutex<ulid::Generator> = Mutex::new(ulid::Generator::new());
+    let mut gen = GEN.lock().unwrap_or_else(|e| e.into_inner());
+    gen.generate()
+        .unwrap_or_else(|_| ulid::Ulid::new())
+        .to_string()
+}
+
+/// Reject confidence values outside 0.0–1.0.
+pub fn check_confidence(c: f64) -> Result<()> {
+    if !(0.0..=1.0).contains(&c) {
+        anyhow::bail!("confidence must be between 0 and 1, got {}", c);
+    }
+    Ok(())
+}
+
+/// Parse a confidence: a number in 0.0–1.0, or `low` (0.3), `med`/`medium` (0.6), `high` (0.9).
+pub fn parse_confidence(s: &str) -> Result<f64> {
+    let c = match s {
+        "low" => 0.3,
+        "med" | "medium" => 0.6,
+        "high" => 0.9,
+        _ => s
+            .parse()
+            .map_err(|_| anyhow::anyhow!("confidence must be 0–1 or low/med/high, got '{}'", s))?,
+    };
+    check_confidence(c)?;
+    Ok(c)
+}
+
+/// Reject strings that are not ULIDs (entry IDs are always ULIDs).
+pub fn check_id(id: &str) -> Result<()> {
+    ulid::Ulid::from_string(id)
+        .map(|_| ())
+        .map_err(|e| anyhow::anyhow!("invalid entry id '{}': {}", id, e))
+}
+
+/// Append one event as a JSON line to the log whose active file is `path`, numbering it one past
+/// the log's last `seq`. The log's lock (see [`crate::segment::lock`]) is held from reading that
+/// number until the line is written, so concurrent writers never share a number and a seal never
+/// takes the file mid-append. Creates parent dirs if needed. Returns the seq.
+pub fn append_event(path: &Path, event: &Event) -> Result<u64> {
+    let _lock = crate::segment::lock(path)?;
+    // Opened under the lock, so this is the active file and not one just sealed.
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("open {}", path.display()))?;
+    let seq = last_seq(path)? + 1;
+    let mut event = event.clone();
+    event.seq = Some(seq);
+    let line = serde_json::to_string(&event).context("serialize event")?;
+    writeln!(f, "{}", line).with_context(|| format!("write {}", path.display()))?;
+    Ok(seq)
+}
+
+/// Highest `seq` in a log: the last entry's, or for logs that predate numbering, the count of
+/// entries so far.
+fn last_seq(active: &Path) -> Result<u64> {
+    let last = crate::segment::tail_lines(active, 1, |l| Event::parse(l).is_some())?;
+    match last.first().and_then(|l| Event::parse(l)) {
+        None => Ok(0),
+        Some(e) => match e.seq {
+            Some(seq) => Ok(seq),
+            None => Ok(crate::segment::read_lines(active)?
+                .iter()
+                .filter(|l| Event::parse(l).is_some())
+                .count() as u64),
+        },
+    }
+}
+
+/// JSON Schema (draft 2020-12) for [`Event`], for non-Rust consumers.
+pub fn json_schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(Event)).expect("schema serializes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_kind_roundtrip() {
+        let e = Event::new(
+            "2025-01-01T00:00:00Z".into(),
+            "agent",
+            Body::from_kind_text("decision", "use ndjson"),
+        );
+        let line = serde_json::to_string(&e).unwrap();
+        assert!(line.contains("\"schema_version\":1"));
+        assert!(line.contains("\"kind\":\"decision\""));
+        let back = Event::parse(&line).unwrap();
+        assert_eq!(back.kind(), "decision");
+        assert_eq!(back.text(), Some("use ndjson"));
+        assert!(matches!(back.body, Body::Known(Payload::Decision { .. })));
+    }
+
+    #[test]
+    fn unknown_kind_and_fields_preserved() {
+        let line = r#"{"schema_version":9,"ts":"t","actor":"agent","kind":"hunch","text":"x","mood":"ok"}"#;
+        let e = Event::parse(line).unwrap();
+        assert_eq!(e.schema_version, 9);
+        assert_eq!(e.kind(), "hunch");
+        assert_eq!(e.text(), Some("x"));
+        let out = serde_json::to_string(&e).unwrap();
+        assert!(out.contains("\"mood\":\"ok\""));
+    }
+
+    #[test]
+    fn known_kind_keeps_unknown_fields() {
+        let line = r#"{"ts":"t","actor":"agent","kind":"decision","text":"x","extra":1}"#;
+        let e = Event::parse(line).unwrap();
+        assert!(matches!(e.body, Body::Known(Payload::Decision { .. })));
+        assert_eq!(e.text(), Some("x"));
+        let out: serde_json::Value = serde_json::to_value(&e).unwrap();
+        assert_eq!(out["extra"], 1);
+        assert_eq!(Event::parse(&out.to_string()).unwrap().body, e.body);
+    }
+
+    #[test]
+    fn legacy_line_without_version_parses() {
+        let e = Event::parse(r#"{"ts":"2025-01-01","text":"foo bar"}"#);
+        assert!(e.is_none(), "no kind: not an event");
+        let e = Event::parse(r#"{"ts":"2025-01-01","actor":"human","kind":"note","text":"a"}"#)
+            .unwrap();
+        assert_eq!(e.schema_version, 0);
+        assert_eq!(
+            e.body,
+            Body::Known(Payload::Note {
+                text: "a".into(),
+                extra: Extra::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn new_events_get_sortable_ids() {
+        let a = Event::new("t".into(), "agent", Body::from_kind_text("note", "a"));
+        let b = Event::new("t".into(), "agent", Body::from_kind_text("note", "b"));
+        let (a, b) = (a.id.unwrap(), b.id.unwrap());
+        assert_eq!(a.len(), 26);
+        assert!(a < b);
+    }
+
+    #[test]
+    fn with_links_rejects_bad_ids() {
+        let e = Event::new("t".into(), "agent", Body::from_kind_text("note", "x"));
+        let parent = e.id.clone().unwrap();
+        let reply = Event::new("t".into(), "human", Body::from_kind_text("note", "y"))
+            .with_links(Some(&parent), None, &[])
+            .unwrap();
+        assert_eq!(reply.parent_id.as_deref(), Some(parent.as_str()));
+        let line = serde_json::to_string(&reply).unwrap();
+        assert!(line.contains("\"parent_id\""));
+        assert!(!line.contains("\"refs\""));
+
+        let bad = Event::new("t".into(), "human", Body::from_kind_text("note", "z")).with_links(
+            None,
+            Some("not-an-id"),
+            &[],
+        );
+        assert!(bad.is_err());
+    }
+
+    #[test]
+    fn schema_lists_known_kinds() {
+        let s = json_schema().to_string();
+        for kind in ["note_chunk", "decision", "raw_appended", "patch_proposed"] {
+            assert!(s.contains(kind), "schema missing {}", kind);
+        }
+        assert!(s.contains("schema_version"));
+    }
+
+    #[test]
+    fn append_numbers_entries_across_legacy_lines_and_segments() {
+        let dir = std::env::temp_dir().join("hyena_event_seq");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("scratch.ndjson");
+        std::fs::write(&log, "{\"ts\":\"t\",\"kind\":\"note\",\"text\":\"old\"}\n").unwrap();
+        let e = Event::new("t".into(), "a", Body::from_kind_text("note", "x"));
+
+        assert_eq!(append_event(&log, &e).unwrap(), 2);
+        crate::segment::maybe_rotate(
+            &log,
+            &crate::segment::Rotation {
+                max_bytes: Some(1),
+                max_age_hours: None,
+            },
+            chrono::Utc::now(),
+        )
+        .unwrap();
+        assert_eq!(append_event(&log, &e).unwrap(), 3);
+        assert_eq!(append_event(&log, &e).unwrap(), 4);
+        let last = crate::segment::tail_lines(&log, 1, |_| true).unwrap();
+        assert_eq!(Event::parse(&last[0]).unwrap().seq, Some(4));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn appends_racing_seals_never_touch_a_sealed_segment() {
+        let dir = std::env::temp_dir().join("hyena_event_seal_race");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("scratch.ndjson");
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let log = log.clone();
+                std::thread::spawn(move || {
+                    let e = Event::new("t".into(), "a", Body::from_kind_text("note", "x"));
+                    for _ in 0..25 {
+                        append_event(&log, &e).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for _ in 0..10 {
+            crate::segment::maybe_rotate(
+                &log,
+                &crate::segment::Rotation {
+                    max_bytes: Some(1),
+                    max_age_hours: None,
+                },
+                chrono::Utc::now(),
+            )
+            .unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+        for w in writers {
+            w.join().unwrap();
+        }
+        assert_eq!(crate::segment::verify(&log).unwrap(), Vec::<String>::new());
+        let mut seqs: Vec<u64> = crate::segment::read_lines(&log)
+            .unwrap()
+            .iter()
+            .map(|l| Event::parse(l).unwrap().seq.unwrap())
+            .collect();
+        seqs.sort_unstable();
+        assert_eq!(seqs, (1..=100).collect::<Vec<_>>());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,379 @@
+//! SQLite export: a read-only, re-exportable projection of the NDJSON logs and raw inputs.
+//!
+//! Tables: `events` (scratch + derived, one row per log line), `links` (parent / supersedes /
+//! ref / promoted_from provenance), `raw_files`, `raw_chunks`, FTS5 indexes `events_fts` and
+//! `raw_chunks_fts`, and `export_state` (per-log line offsets for incremental re-export).
+//! The NDJSON files stay the source of truth; the database can be deleted and rebuilt any time.
+
+use crate::derived::ClaimFilter;
+use crate::event::Event;
+use crate::raw::RawInputs;
+use crate::{derived, digest, formats, raw, scratch, segment};
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::path::{Path, PathBuf};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    log TEXT NOT NULL,
+    line INTEGER NOT NULL,
+    id TEXT,
+    schema_version INTEGER,
+    ts TEXT,
+    actor TEXT,
+    kind TEXT,
+    text TEXT,
+    scope TEXT,
+    source TEXT,
+    confidence REAL,
+    json TEXT NOT NULL,
+    PRIMARY KEY (log, line)
+);
+CREATE INDEX IF NOT EXISTS events_id ON events(id);
+CREATE INDEX IF NOT EXISTS events_kind ON events(kind);
+CREATE TABLE IF NOT EXISTS links (
+    from_id TEXT NOT NULL,
+    to_id TEXT NOT NULL,
+    rel TEXT NOT NULL,
+    PRIMARY KEY (from_id, to_id, rel)
+);
+CREATE TABLE IF NOT EXISTS raw_files (
+    path TEXT PRIMARY KEY,
+    sha256 TEXT NOT NULL,
+    bytes INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS raw_chunks (
+    path TEXT NOT NULL REFERENCES raw_files(path),
+    idx INTEGER NOT NULL,
+    heading_path TEXT NOT NULL,
+    start_line INTEGER NOT NULL,
+    end_line INTEGER NOT NULL,
+    sha256 TEXT NOT NULL,
+    text TEXT NOT NULL,
+    PRIMARY KEY (path, idx)
+);
+CREATE VIRTUAL TABLE IF NOT EXISTS events_fts USING fts5(log UNINDEXED, line UNINDEXED, text);
+CREATE VIRTUAL TABLE IF NOT EXISTS raw_chunks_fts USING fts5(path UNINDEXED, idx UNINDEXED, heading_path, text);
+CREATE TABLE IF NOT EXISTS export_state (
+    log TEXT PRIMARY KEY,
+    lines INTEGER NOT NULL,
+    last_line_sha256 TEXT NOT NULL
+);
+";
+
+/// What one export run added or replaced.
+#[derive(Debug, Default, PartialEq)]
+pub struct ExportStats {
+    pub events: usize,
+    pub raw_files: usize,
+    pub raw_removed: usize,
+}
+
+/// Export derived log, scratch and raw inputs selected by `raw_inputs` into `db`, leaving out
+/// log lines `visible` does not match. Re-running only adds log lines past the last exported
+/// offset and re-chunks raw files whose hash changed.
+pub fn export_sqlite(
+    root: &Path,
+    db: &Path,
+    raw_inputs: &RawInputs,
+    visible: &ClaimFilter,
+) -> Result<ExportStats> {
+    let mut conn = Connection::open(db).with_context(|| format!("open {}", db.display()))?;
+    conn.execute_batch(SCHEMA).context("create export schema")?;
+    let tx = conn.transaction()?;
+    let mut stats = ExportStats::default();
+    for (log, active) in [
+        ("derived", derived::derived_path(root)),
+        ("scratch", scratch::scratch_path(root)),
+    ] {
+        stats.events += export_log(&tx, log, &active, visible)?;
+    }
+    let (updated, removed) = export_raw(&tx, root, raw_inputs)?;
+    stats.raw_files = updated;
+    stats.raw_removed = removed;
+    tx.commit()?;
+    Ok(stats)
+}
+
+fn clear_log(tx: &Transaction, log: &str) -> Result<()> {
+    tx.execute(
+        "DELETE FROM links WHERE from_id IN (SELECT id FROM events WHERE log = ?1)",
+        [log],
+    )?;
+    tx.execute("DELETE FROM events WHERE log = ?1", [log])?;
+    tx.execute("DELETE FROM events_fts WHERE log = ?1", [log])?;
+    Ok(())
+}
+
+/// Hash stored for the last exported line. Exports that hid subtrees fold them in, so an export
+/// for a reader with a different view starts over instead of keeping rows it may not see.
+fn line_mark(line: &str, visible: &ClaimFilter) -> String {
+    if visible.hidden.is_empty() {
+        return digest::sha256_hex(line.as_bytes());
+    }
+    digest::sha256_hex(format!("{}\n{}", visible.hidden.join("\n"), line).as_bytes())
+}
+
+/// Append lines past the stored offset that `visible` matches. If the stored last line no longer
+/// matches (the log was rewritten, which append-only forbids, or the reader's view changed), the
+/// log is re-exported from scratch.
+fn export_log(tx: &Transaction, log: &str, active: &Path, visible: &ClaimFilter) -> Result<usize> {
+    let lines = segment::read_lines(active)?;
+    let state: Option<(usize, String)> = tx
+        .query_row(
+            "SELECT lines, last_line_sha256 FROM export_state WHERE log = ?1",
+            [log],
+            |r| Ok((r.get::<_, i64>(0)? as usize, r.get(1)?)),
+        )
+        .optional()?;
+    let start = match state {
+        Some((n, sha))
+            if n <= lines.len() && (n == 0 || line_mark(&lines[n - 1], visible) == sha) =>
+        {
+            n
+        }
+        Some(_) => {
+            clear_log(tx, log)?;
+            0
+        }
+        None => 0,
+    };
+
+    let mut insert = tx.prepare(
+        "INSERT INTO events (log, line, id, schema_version, ts, actor, kind, text, scope, source, confidence, json)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+    )?;
+    let mut fts = tx.prepare("INSERT INTO events_fts (log, line, text) VALUES (?1, ?2, ?3)")?;
+    let mut link =
+        tx.prepare("INSERT OR IGNORE INTO links (from_id, to_id, rel) VALUES (?1, ?2, ?3)")?;
+    let mut exported = 0;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let n = (i + 1) as i64;
+        let e = Event::parse(line);
+        if e.as_ref().is_some_and(|e| !visible.matches(e)) {
+            continue;
+        }
+        exported += 1;
+        let text = e.as_ref().and_then(|e| e.text()).map(str::to_string);
+        insert.execute(params![
+            log,
+            n,
+            e.as_ref().and_then(|e| e.id.clone()),
+            e.as_ref().map(|e| e.schema_version),
+            e.as_ref().map(|e| e.ts.clone()),
+            e.as_ref().map(|e| e.actor.clone()),
+            e.as_ref().map(|e| e.kind().to_string()),
+            text,
+            e.as_ref().and_then(|e| e.scope.clone()),
+            e.as_ref().and_then(|e| e.source.clone()),
+            e.as_ref().and_then(|e| e.confidence),
+            line,
+        ])?;
+        fts.execute(params![log, n, text.as_deref().unwrap_or(line)])?;
+        let Some(e) = e else { continue };
+        let Some(id) = e.id.as_deref() else { continue };
+        let rels = e
+            .parent_id
+            .iter()
+            .map(|t| (t, "parent"))
+            .chain(e.supersedes.iter().map(|t| (t, "supersedes")))
+            .chain(e.refs.iter().map(|t| (t, "ref")))
+            .chain(e.promoted_from.iter().map(|t| (t, "promoted_from")));
+        for (to, rel) in rels {
+            link.execute(params![id, to, rel])?;
+        }
+    }
+    if let Some(last) = lines.last() {
+        tx.execute(
+            "INSERT INTO export_state (log, lines, last_line_sha256) VALUES (?1, ?2, ?3)
+             ON CONFLICT(log) DO UPDATE SET lines = ?2, last_line_sha256 = ?3",
+            params![log, lines.len() as i64, line_mark(last, visible)],
+        )?;
+    }
+    Ok(exported)
+}
+
+/// Re-chunk raw files whose hash changed; drop rows for files that no longer exist.
+/// Returns (files updated, files removed).
+fn export_raw(tx: &Transaction, root: &Path, inputs: &RawInputs) -> Result<(usize, usize)> {
+    let paths: Vec<PathBuf> = raw::discover_raw_files(root, None, inputs)?;
+    let mut seen = Vec::new();
+    let mut updated = 0;
+    for abs in &paths {
+        let Some(rel) = raw::relative_for_glob(abs, root) else {
+            continue;
+        };
+        let content =
+            std::fs::read_to_string(abs).with_context(|| format!("read {}", abs.display()))?;
+        let sha = digest::sha256_hex(content.as_bytes());
+        seen.push(rel.clone());
+        let stored: Option<String> = tx
+            .query_row(
+                "SELECT sha256 FROM raw_files WHERE path = ?1",
+                [&rel],
+                |r| r.get(0),
+            )
+            .optional()?;
+        if stored.as_deref() == Some(sha.as_str()) {
+            continue;
+        }
+        delete_raw(tx, &rel)?;
+        tx.execute(
+            "INSERT INTO raw_files (path, sha256, bytes) VALUES (?1, ?2, ?3)",
+            params![rel, sha, content.len() as i64],
+        )?;
+        let parsed = formats::parse(&rel, &content, inputs.format_of(&rel));
+        for (idx, c) in parsed.chunks.iter().enumerate() {
+            let heading = c.heading_path.join(" > ");
+            tx.execute(
+                "INSERT INTO raw_chunks (path, idx, heading_path, start_line, end_line, sha256, text)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![rel, idx as i64, heading, c.start_line as i64, c.end_line as i64, c.sha256(), c.text],
+            )?;
+            tx.execute(
+                "INSERT INTO raw_chunks_fts (path, idx, heading_path, text) VALUES (?1, ?2, ?3, ?4)",
+                params![rel, idx as i64, heading, c.text],
+            )?;
+        }
+        updated += 1;
+    }
+    let stale: Vec<String> = {
+        let mut stmt = tx.prepare("SELECT path FROM raw_files")?;
+        let all = stmt.query_map([], |r| r.get::<_, String>(0))?;
+        all.filter_map(|p| p.ok())
+            .filter(|p| !seen.contains(p))
+            .collect()
+    };
+    for rel in &stale {
+        delete_raw(tx, rel)?;
+    }
+    Ok((updated, stale.len()))
+}
+
+fn delete_raw(tx: &Transaction, rel: &str) -> Result<()> {
+    tx.execute("DELETE FROM raw_chunks WHERE path = ?1", [rel])?;
+    tx.execute("DELETE FROM raw_chunks_fts WHERE path = ?1", [rel])?;
+    tx.execute("DELETE FROM raw_files WHERE path = ?1", [rel])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Body;
+    use std::fs;
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |r| r.get(0)).unwrap()
+    }
+
+    #[test]
+    fn export_then_incremental_reexport() {
+        let root = std::env::temp_dir().join("hyena_export_sqlite");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("NOTES.md"),
+            "# Plan\nship the parser\n## Risks\nlexer bugs\n",
+        )
+        .unwrap();
+        let q = Event::new(
+            "t".into(),
+            "planner",
+            Body::from_kind_text("question", "which lexer?"),
+        );
+        let qid = scratch::append_scratch(&root, &q, &crate::secrets::Scanner::default())
+            .unwrap()
+            .id
+            .unwrap();
+        let a = Event::new(
+            "t".into(),
+            "coder",
+            Body::from_kind_text("decision", "logos lexer"),
+        )
+        .with_links(Some(&qid), None, &[])
+        .unwrap();
+        derived::append_derived(&root, &a, &crate::secrets::Scanner::default()).unwrap();
+
+        let db = root.join("out.db");
+        let patterns = RawInputs {
+            patterns: vec!["**/NOTES.md".to_string()],
+            ..Default::default()
+        };
+        let stats = export_sqlite(&root, &db, &patterns, &ClaimFilter::default()).unwrap();
+        assert_eq!(
+            stats,
+            ExportStats {
+                events: 2,
+                raw_files: 1,
+                raw_removed: 0
+            }
+        );
+
+        let conn = Connection::open(&db).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM raw_chunks"), 2);
+        assert_eq!(
+            count(&conn, "SELECT COUNT(*) FROM links WHERE rel = 'parent'"),
+            1
+        );
+        let hit: String = conn
+            .query_row(
+                "SELECT heading_path FROM raw_chunks_fts WHERE raw_chunks_fts MATCH 'lexer'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(hit, "Plan > Risks");
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM events_fts WHERE events_fts MATCH 'logos'"
+            ),
+            1
+        );
+        drop(conn);
+
+        // Nothing new: no rows added, raw unchanged.
+        let again = export_sqlite(&root, &db, &patterns, &ClaimFilter::default()).unwrap();
+        assert_eq!(again, ExportStats::default());
+
+        derived::append_derived(
+            &root,
+            &Event::new("t".into(), "coder", Body::from_kind_text("note", "done")),
+            &crate::secrets::Scanner::default(),
+        )
+        .unwrap();
+        fs::remove_file(root.join("NOTES.md")).unwrap();
+        let inc = export_sqlite(&root, &db, &patterns, &ClaimFilter::default()).unwrap();
+        assert_eq!(
+            inc,
+            ExportStats {
+                events: 1,
+                raw_files: 0,
+                raw_removed: 1
+            }
+        );
+        let conn = Connection::open(&db).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM events"), 3);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM raw_chunks"), 0);
+        drop(conn);
+
+        // A reader with hidden subtrees gets both logs re-exported without their entries.
+        let mut secret = Event::new("t".into(), "coder", Body::from_kind_text("note", "sealed"));
+        secret.source = Some("legal/NOTES.md".into());
+        derived::append_derived(&root, &secret, &crate::secrets::Scanner::default()).unwrap();
+        let visible = ClaimFilter {
+            hidden: vec!["legal".into()],
+            ..Default::default()
+        };
+        let filtered = export_sqlite(&root, &db, &patterns, &visible).unwrap();
+        assert_eq!(filtered.events, 3);
+        let conn = Connection::open(&db).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM events"), 3);
+        assert_eq!(
+            count(&conn, "SELECT COUNT(*) FROM events WHERE text = 'sealed'"),
+            0
+        );
+        fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,260 @@
+//! Raw input formats other than plain Markdown: Obsidian vaults, Logseq graphs and org-mode files.
+//!
+//! A policy declares them per glob (`raw_inputs.patterns: [{ glob, format }]`). Each parser splits
+//! a file into the same heading-path chunks as Markdown and reads what the file says about itself
+//! into metadata, which ingest records on the file's `note_chunk` events:
+//! - `obsidian`: Markdown with YAML frontmatter (`---` … `---`); daily notes named `YYYY-MM-DD`
+//!   get a `date`.
+//! - `logseq`: outline pages; leading `key:: value` lines are page properties, each top-level
+//!   block is a chunk and `- # Title` blocks set the heading path; journal pages named
+//!   `YYYY_MM_DD` get a `date`.
+//! - `org`: `*` headings (TODO keywords, priorities and tags left out of the path), leading
+//!   `#+KEY: value` lines as metadata, `#+BEGIN_…`/`#+END_…` blocks not split.
+//!
+//! Metadata lines are not part of any chunk; line numbers stay those of the file. Files are only
+//! read.
+
+use crate::chunk::{self, Chunk};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How a raw input is parsed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    #[default]
+    Markdown,
+    Obsidian,
+    Logseq,
+    Org,
+}
+
+/// Metadata a file declares about itself.
+pub type Meta = BTreeMap<String, serde_json::Value>;
+
+/// A raw input split into chunks, plus its metadata.
+#[derive(Debug, Default)]
+pub struct Parsed {
+    pub chunks: Vec<Chunk>,
+    pub meta: Meta,
+}
+
+/// Parse `content` of the root-relative file `rel` as `format`.
+pub fn parse(rel: &str, content: &str, format: Format) -> Parsed {
+    let lines: Vec<&str> = content.lines().collect();
+    match format {
+        Format::Markdown => Parsed {
+            chunks: chunk::chunk_markdown(content),
+            meta: Meta::new(),
+        },
+        Format::Obsidian => obsidian(rel, &lines),
+        Format::Logseq => logseq(rel, &lines),
+        Format::Org => org(&lines),
+    }
+}
+
+/// File name without directory and extension.
+fn stem(rel: &str) -> &str {
+    let name = rel.rsplit('/').next().unwrap_or(rel);
+    name.rsplit_once('.').map_or(name, |(s, _)| s)
+}
+
+/// `date` for daily/journal pages: the file stem as `YYYY-MM-DD`, with `sep` between the parts.
+fn note_date(rel: &str, sep: char, meta: &mut Meta) {
+    let parts: Vec<&str> = stem(rel).split(sep).collect();
+    let digits = |s: &str, n: usize| s.len() == n && s.bytes().all(|b| b.is_ascii_digit());
+    if let [y, m, d] = parts[..] {
+        if digits(y, 4) && digits(m, 2) && digits(d, 2) {
+            meta.entry("date".into())
+                .or_insert_with(|| format!("{}-{}-{}", y, m, d).into());
+        }
+    }
+}
+
+fn obsidian(rel: &str, lines: &[&str]) -> Parsed {
+    let mut meta = Meta::new();
+    let mut body = 0;
+    if lines.first().is_some_and(|l| l.trim_end() == "---") {
+        let close = lines[1..]
+            .iter()
+            .position(|l| matches!(l.trim_end(), "---" | "..."));
+        if let Some(close) = close {
+            let yaml = lines[1..=close].join("\n");
+            // Frontmatter that is not a YAML map is left in the text.
+            if let Ok(map) = serde_yaml::from_str::<Meta>(&yaml) {
+                meta = map;
+                body = close + 2;
+            }
+        }
+    }
+    note_date(rel, '-', &mut meta);
+    Parsed {
+        chunks: chunk::chunk_lines(&lines[body..], body + 1, chunk::heading, chunk::is_fence),
+        meta,
+    }
+}
+
+/// `key:: value` property line.
+fn logseq_property(line: &str) -> Option<(String, String)> {
+    let (k, v) = line.trim().split_once(":: ")?;
+    let ok = !k.is_empty()
+        && k.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+    ok.then(|| (k.to_lowercase(), v.trim().to_string()))
+}
+
+fn logseq(rel: &str, lines: &[&str]) -> Parsed {
+    let mut meta = Meta::new();
+    let mut body = 0;
+    while let Some((k, v)) = lines.get(body).and_then(|l| logseq_property(l)) {
+        meta.insert(k, v.into());
+        body += 1;
+    }
+    note_date(rel, '_', &mut meta);
+
+    // Each top-level block (`- …` at column 0, with everything indented under it) is a chunk.
+    let mut chunks = Vec::new();
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    let mut flush = |stack: &[(usize, String)], block: &[&str], start: usize| {
+        if block.iter().any(|l| !l.trim().is_empty()) {
+            chunks.push(Chunk {
+                heading_path: stack.iter().map(|(_, t)| t.clone()).collect(),
+                start_line: start,
+                end_line: start + block.len() - 1,
+                text: block.join("\n"),
+            });
+        }
+    };
+    let mut start = body;
+    for i in body..lines.len() {
+        let line = lines[i];
+        if !(line == "-" || line.starts_with("- ")) {
+            continue;
+        }
+        flush(&stack, &lines[start..i], start + 1);
+        start = i;
+        if let Some((level, title)) = chunk::heading(line[1..].trim_start()) {
+            while stack.last().is_some_and(|(l, _)| *l >= level) {
+                stack.pop();
+            }
+            stack.push((level, title));
+        }
+    }
+    flush(&stack, &lines[start..], start + 1);
+    Parsed { chunks, meta }
+}
+
+/// Org heading level and title, without TODO keyword, priority cookie or tags.
+fn org_heading(line: &str) -> Option<(usize, String)> {
+    let level = line.bytes().take_while(|b| *b == b'*').count();
+    let rest = line[level..].strip_prefix(' ')?;
+    if level == 0 {
+        return None;
+    }
+    let mut words: Vec<&str> = rest.split_whitespace().collect();
+    if words.first().is_some_and(|w| matches!(*w, "TODO" | "DONE")) {
+        words.remove(0);
+    }
+    if words
+        .first()
+        .is_some_and(|w| w.starts_with("[#") && w.ends_with(']'))
+    {
+        words.remove(0);
+    }
+    if words
+        .last()
+        .is_some_and(|w| w.len() > 1 && w.starts_with(':') && w.ends_with(':'))
+    {
+        words.pop();
+    }
+    Some((level, words.join(" ")))
+}
+
+/// `#+BEGIN_…` / `#+END_…` lines (any case).
+fn org_block(line: &str) -> bool {
+    let t = line.trim_start().to_ascii_uppercase();
+    t.starts_with("#+BEGIN_") || t.starts_with("#+END_")
+}
+
+fn org(lines: &[&str]) -> Parsed {
+    let mut meta = Meta::new();
+    let mut body = 0;
+    while let Some(line) = lines.get(body) {
+        let Some((k, v)) = line.strip_prefix("#+").and_then(|l| l.split_once(':')) else {
+            break;
+        };
+        if k.is_empty() || org_block(line) {
+            break;
+        }
+        meta.insert(k.to_lowercase(), v.trim().into());
+        body += 1;
+    }
+    Parsed {
+        chunks: chunk::chunk_lines(&lines[body..], body + 1, org_heading, org_block),
+        meta,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(p: &Parsed) -> Vec<(String, usize, usize)> {
+        p.chunks
+            .iter()
+            .map(|c| (c.heading_path.join(">"), c.start_line, c.end_line))
+            .collect()
+    }
+
+    #[test]
+    fn obsidian_frontmatter_becomes_meta_and_is_not_chunked() {
+        let md =
+            "---\ntags: [plan, q3]\nstatus: draft\n---\n# Plan\nsee [[Roadmap]]\n## Risks\nnone\n";
+        let p = parse("vault/daily/2025-03-14.md", md, Format::Obsidian);
+        assert_eq!(p.meta["tags"], serde_json::json!(["plan", "q3"]));
+        assert_eq!(p.meta["status"], "draft");
+        assert_eq!(p.meta["date"], "2025-03-14");
+        assert_eq!(
+            paths(&p),
+            vec![("Plan".into(), 5, 6), ("Plan>Risks".into(), 7, 8)]
+        );
+        assert!(!p.chunks[0].text.contains("status"));
+
+        // Plain Markdown keeps frontmatter as text.
+        let plain = parse("NOTES.md", md, Format::Markdown);
+        assert!(plain.meta.is_empty());
+        assert!(plain.chunks[0].text.starts_with("---"));
+    }
+
+    #[test]
+    fn logseq_blocks_and_org_headings() {
+        let page = "title:: Roadmap\ntags:: plan\n\n- # Q3\n- ship sync\n  - with tests\n- ## Risks\n\t- none\n- # Q4\n- rest\n";
+        let p = parse("journals/2025_07_01.md", page, Format::Logseq);
+        assert_eq!(p.meta["title"], "Roadmap");
+        assert_eq!(p.meta["date"], "2025-07-01");
+        assert_eq!(
+            paths(&p),
+            vec![
+                ("Q3".into(), 4, 4),
+                ("Q3".into(), 5, 6),
+                ("Q3>Risks".into(), 7, 8),
+                ("Q4".into(), 9, 9),
+                ("Q4".into(), 10, 10),
+            ]
+        );
+        assert_eq!(p.chunks[1].text, "- ship sync\n  - with tests");
+
+        let org_file = "#+TITLE: Plans\n#+FILETAGS: :work:\nintro\n* TODO [#A] Ship sync :urgent:\nbody\n#+BEGIN_SRC sh\n* not a heading\n#+END_SRC\n** Risks\nnone\n";
+        let p = parse("plans.org", org_file, Format::Org);
+        assert_eq!(p.meta["title"], "Plans");
+        assert_eq!(p.meta["filetags"], ":work:");
+        assert_eq!(
+            paths(&p),
+            vec![
+                (String::new(), 3, 3),
+                ("Ship sync".into(), 4, 8),
+                ("Ship sync>Risks".into(), 9, 10),
+            ]
+        );
+    }
+}
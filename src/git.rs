@@ -0,0 +1,225 @@
+//! Git provenance: record which revision a derived event came from, and read it back for trace.
+//!
+//! Runs the local `git` binary (plumbing commands only). Everything is optional: outside a work
+//! tree, without commits or without git installed, events are simply written without `git`.
+
+use crate::event::GitProvenance;
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// HEAD state of the work tree containing root, probed once per command.
+#[derive(Debug, Clone)]
+pub struct Repo {
+    root: PathBuf,
+    pub head: String,
+    pub dirty: bool,
+    pub author: Option<String>,
+}
+
+/// Run `git -C root args…`; trimmed stdout on success, None if git fails or is missing.
+pub fn git(root: &Path, args: &[&str]) -> Option<String> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).trim_end().to_string())
+}
+
+/// HEAD commit, dirty flag and author, or None if root is not in a git work tree with commits.
+pub fn probe(root: &Path) -> Option<Repo> {
+    let head = git(root, &["rev-parse", "--verify", "--quiet", "HEAD"])?;
+    let status = git(root, &["status", "--porcelain", "--untracked-files=no"])?;
+    let author = git(root, &["log", "-1", "--format=%an <%ae>", &head]);
+    Some(Repo {
+        root: root.to_path_buf(),
+        head,
+        dirty: !status.is_empty(),
+        author,
+    })
+}
+
+/// Object id git gives `content` as a blob (`git hash-object`), without writing it to the store.
+pub fn hash_object(root: &Path, content: &[u8]) -> Option<String> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["hash-object", "--stdin"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    child.stdin.take()?.write_all(content).ok()?;
+    let out = child.wait_with_output().ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// True if the object store holds `object`.
+pub fn has_object(root: &Path, object: &str) -> bool {
+    git(root, &["cat-file", "-e", object]).is_some()
+}
+
+impl Repo {
+    /// Blob SHA of `source` (root-relative) at HEAD, if tracked.
+    pub fn head_blob(&self, source: &str) -> Option<String> {
+        git(
+            &self.root,
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("HEAD:./{}", source),
+            ],
+        )
+    }
+
+    /// Provenance for an event, optionally about `source` (root-relative) as read (`content`):
+    /// the content's own blob id, and whether it differs from `source` at HEAD. Without a source,
+    /// `dirty` says whether any tracked file had uncommitted changes.
+    pub fn provenance(&self, source: Option<(&str, &[u8])>) -> GitProvenance {
+        let (dirty, blob) = match source {
+            Some((rel, content)) => {
+                let blob = hash_object(&self.root, content);
+                (blob.is_none() || blob != self.head_blob(rel), blob)
+            }
+            None => (self.dirty, None),
+        };
+        GitProvenance {
+            commit: self.head.clone(),
+            dirty,
+            author: self.author.clone(),
+            blob,
+        }
+    }
+}
+
+/// Staged changes under root as (status letter, root-relative path), renames split into D + A.
+pub fn staged_changes(root: &Path) -> Result<Vec<(char, String)>> {
+    let out = git(
+        root,
+        &[
+            "diff",
+            "--cached",
+            "--name-status",
+            "--no-renames",
+            "--relative",
+        ],
+    )
+    .context("git diff --cached (is root in a git work tree?)")?;
+    Ok(out
+        .lines()
+        .filter_map(|l| {
+            let (status, path) = l.split_once('\t')?;
+            Some((status.chars().next()?, path.to_string()))
+        })
+        .collect())
+}
+
+/// Raw bytes of an object spec such as `HEAD:./NOTES.md` or `:./NOTES.md` (the index).
+pub fn object_bytes(root: &Path, spec: &str) -> Option<Vec<u8>> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["cat-file", "-p", spec])
+        .output()
+        .ok()?;
+    out.status.success().then_some(out.stdout)
+}
+
+/// Hooks directory of the repository containing root (honours `core.hooksPath`).
+pub fn hooks_dir(root: &Path) -> Result<PathBuf> {
+    let dir = git(root, &["rev-parse", "--git-path", "hooks"])
+        .context("git rev-parse (is root in a git work tree?)")?;
+    let dir = PathBuf::from(dir);
+    Ok(if dir.is_absolute() {
+        dir
+    } else {
+        root.join(dir)
+    })
+}
+
+/// Text recorded by `prov`: the blob itself, or `source` at the commit when no blob was recorded.
+pub fn source_text(root: &Path, prov: &GitProvenance, source: &str) -> Result<String> {
+    let object = match &prov.blob {
+        Some(blob) => blob.clone(),
+        None => format!("{}:./{}", prov.commit, source),
+    };
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["cat-file", "-p", &object])
+        .output()
+        .context("run git")?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "git cat-file {}: {}",
+            object,
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn run(root: &Path, args: &[&str]) {
+        let ok = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(["-c", "user.name=Tester", "-c", "user.email=t@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status
+            .success();
+        assert!(ok, "git {:?}", args);
+    }
+
+    #[test]
+    fn probe_records_head_blob_and_dirty() {
+        let root = std::env::temp_dir().join("hyena_git_probe");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        run(&root, &["init", "-q"]);
+        assert!(probe(&root).is_none(), "no commits yet");
+        fs::write(root.join("NOTES.md"), "v1\n").unwrap();
+        run(&root, &["add", "NOTES.md"]);
+        run(&root, &["commit", "-q", "-m", "first"]);
+
+        let repo = probe(&root).unwrap();
+        assert_eq!(repo.head.len(), 40);
+        assert!(!repo.dirty);
+        assert_eq!(repo.author.as_deref(), Some("Tester <t@example.com>"));
+        let prov = repo.provenance(Some(("NOTES.md", b"v1\n")));
+        assert_eq!(prov.blob, repo.head_blob("NOTES.md"));
+        assert!(!prov.dirty);
+
+        fs::write(root.join("NOTES.md"), "v2\n").unwrap();
+        fs::write(root.join("other.md"), "x\n").unwrap();
+        assert!(probe(&root).unwrap().dirty);
+        // The recorded blob still yields the committed text.
+        assert_eq!(source_text(&root, &prov, "NOTES.md").unwrap(), "v1\n");
+
+        // Content that was never committed gets its own id, which the store does not hold; only
+        // the file read is marked dirty.
+        let edited = repo.provenance(Some(("NOTES.md", b"v2\n")));
+        assert!(edited.dirty);
+        assert_ne!(edited.blob, prov.blob);
+        assert!(!has_object(&root, edited.blob.as_deref().unwrap()));
+        let repo = probe(&root).unwrap();
+        fs::write(root.join("NOTES.md"), "v1\n").unwrap();
+        assert!(!repo.provenance(Some(("NOTES.md", b"v1\n"))).dirty);
+        fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,276 @@
+//! Git pre-commit guard: raw inputs change only with a human attestation, append-only logs only grow.
+//!
+//! `hyena hook install` writes a pre-commit hook that runs `hyena check-commit`, which inspects
+//! the staged changes (index vs HEAD).
+
+use crate::policy::Policy;
+use crate::{digest, git, raw, segment};
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Environment variable a human sets (to their name) to attest raw-input edits in this commit.
+pub const ATTEST_ENV: &str = "HYENA_HUMAN_ATTEST";
+
+/// Marks hooks written by `hook install`, so re-installing may overwrite them.
+const HOOK_MARKER: &str = "# hyena pre-commit hook";
+
+/// Single-quote for /bin/sh.
+fn sh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Install `<hooks>/pre-commit` running `exe --root root --policy policy check-commit`. An existing
+/// hook not written by hyena is kept unless `force`. Returns the hook path.
+pub fn install(root: &Path, exe: &Path, policy: &Path, force: bool) -> Result<std::path::PathBuf> {
+    let dir = git::hooks_dir(root)?;
+    let hook = dir.join("pre-commit");
+    if let Ok(existing) = std::fs::read_to_string(&hook) {
+        if !existing.contains(HOOK_MARKER) && !force {
+            anyhow::bail!(
+                "{} exists and was not installed by hyena (use --force to replace it)",
+                hook.display()
+            );
+        }
+    }
+    let script = format!(
+        "#!/bin/sh\n{} (installed by `hyena hook install`)\nexec {} --root {} --policy {} check-commit\n",
+        HOOK_MARKER,
+        sh_quote(&exe.to_string_lossy()),
+        sh_quote(&root.to_string_lossy()),
+        sh_quote(&policy.to_string_lossy()),
+    );
+    std::fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
+    std::fs::write(&hook, script).with_context(|| format!("write {}", hook.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("chmod {}", hook.display()))?;
+    }
+    Ok(hook)
+}
+
+/// How the human vouched for raw edits: `HYENA_HUMAN_ATTEST=<name>`, set explicitly for the
+/// commit. Repository config (such as `commit.gpgsign`) is not proof; anyone can set it.
+pub fn attestation() -> Option<String> {
+    std::env::var(ATTEST_ENV)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(|name| format!("{}={}", ATTEST_ENV, name))
+}
+
+/// Problems with the staged changes under root; empty means the commit may proceed.
+pub fn check_commit(
+    root: &Path,
+    policy: &Policy,
+    attestation: Option<&str>,
+) -> Result<Vec<String>> {
+    let inputs = policy.raw_inputs();
+    let raw_set = raw::build_globset(&inputs.patterns)?;
+    let raw_exclude = raw::build_globset(&inputs.exclude)?;
+    let active = policy.append_only_active();
+    let active_set = raw::build_globset(&active)?;
+    let segments = raw::build_globset(
+        &active
+            .iter()
+            .map(|a| segment::sealed_globs(a).swap_remove(0))
+            .collect::<Vec<_>>(),
+    )?;
+    let append_only = raw::build_globset(&policy.append_only())?;
+
+    let staged = git::staged_changes(root)?;
+    let mut problems = Vec::new();
+    let mut raw_changed = Vec::new();
+    for (status, path) in &staged {
+        if raw_set.is_match(path) && !raw_exclude.is_match(path) {
+            raw_changed.push(path.clone());
+        }
+        if !append_only.is_match(path) || *status == 'A' {
+            continue;
+        }
+        if segments.is_match(path) {
+            problems.push(format!("{}: sealed log segment changed or deleted", path));
+            continue;
+        }
+        let old = git::object_bytes(root, &format!("HEAD:./{}", path)).unwrap_or_default();
+        let mut now = Vec::new();
+        if active_set.is_match(path) {
+            // Rotation moves the active file's content into new sealed segments.
+            match sealed_in_commit(root, path, &staged) {
+                Ok(bytes) => now = bytes,
+                Err(problem) => {
+                    problems.push(problem);
+                    continue;
+                }
+            }
+        }
+        if *status != 'D' {
+            now.extend(git::object_bytes(root, &format!(":./{}", path)).unwrap_or_default());
+        }
+        if !now.starts_with(&old) {
+            problems.push(match status {
+                'D' => format!("{}: append-only log deleted", path),
+                _ => format!(
+                    "{}: append-only log has existing lines changed or removed",
+                    path
+                ),
+            });
+        }
+    }
+    if !raw_changed.is_empty() && attestation.is_none() {
+        problems.push(format!(
+            "raw inputs changed without human attestation: {} (set {}=<name>)",
+            raw_changed.join(", "),
+            ATTEST_ENV
+        ));
+    }
+    Ok(problems)
+}
+
+/// Staged content of the segments of `active` sealed in this commit, oldest first. Each must be
+/// listed in the staged segment manifest with its hash; otherwise the problem to report.
+fn sealed_in_commit(
+    root: &Path,
+    active: &str,
+    staged: &[(char, String)],
+) -> std::result::Result<Vec<u8>, String> {
+    let active_path = Path::new(active);
+    let mut added: Vec<(u64, &str)> = staged
+        .iter()
+        .filter(|(status, p)| *status == 'A' && Path::new(p).parent() == active_path.parent())
+        .filter_map(|(_, p)| {
+            let name = Path::new(p).file_name()?.to_string_lossy();
+            Some((segment::segment_number(active_path, &name)?, p.as_str()))
+        })
+        .collect();
+    if added.is_empty() {
+        return Ok(Vec::new());
+    }
+    added.sort();
+    let manifest = segment::manifest_path(active_path)
+        .to_string_lossy()
+        .into_owned();
+    let listed: Vec<segment::SealedSegment> = git::object_bytes(root, &format!(":./{}", manifest))
+        .map(|b| {
+            String::from_utf8_lossy(&b)
+                .lines()
+                .filter_map(|l| serde_json::from_str(l).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut out = Vec::new();
+    for (_, seg) in added {
+        let bytes = git::object_bytes(root, &format!(":./{}", seg)).unwrap_or_default();
+        let name = Path::new(seg).file_name().map(|n| n.to_string_lossy());
+        let recorded = listed.iter().any(|l| {
+            Some(l.file.as_str()) == name.as_deref() && l.sha256 == digest::sha256_hex(&bytes)
+        });
+        if !recorded {
+            return Err(format!(
+                "{}: sealed segment {} is not recorded in {}",
+                active, seg, manifest
+            ));
+        }
+        out.extend(bytes);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+
+    fn run(root: &Path, args: &[&str]) {
+        let ok = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(["-c", "user.name=T", "-c", "user.email=t@x"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status
+            .success();
+        assert!(ok, "git {:?}", args);
+    }
+
+    #[test]
+    fn check_commit_flags_raw_edits_and_log_rewrites() {
+        let root = std::env::temp_dir().join("hyena_hook_check");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(".notes")).unwrap();
+        fs::write(root.join("NOTES.md"), "human notes\n").unwrap();
+        fs::write(root.join(".notes/notes.ndjson"), "{\"a\":1}\n").unwrap();
+        run(&root, &["init", "-q"]);
+        run(&root, &["add", "."]);
+        run(&root, &["commit", "-q", "-m", "init"]);
+        let policy: Policy = serde_yaml::from_str("policy:\n  name: hyena\n").unwrap();
+
+        // Appending to the log is fine; nothing raw is staged.
+        fs::write(root.join(".notes/notes.ndjson"), "{\"a\":1}\n{\"b\":2}\n").unwrap();
+        run(&root, &["add", "."]);
+        assert!(check_commit(&root, &policy, None).unwrap().is_empty());
+
+        // Rewriting a line and editing NOTES.md are both flagged.
+        fs::write(root.join(".notes/notes.ndjson"), "{\"a\":9}\n").unwrap();
+        fs::write(root.join("NOTES.md"), "agent was here\n").unwrap();
+        run(&root, &["add", "."]);
+        let problems = check_commit(&root, &policy, None).unwrap();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].contains("existing lines changed"));
+        assert!(problems[1].contains("NOTES.md"));
+
+        // With an attestation only the log rewrite remains.
+        let problems = check_commit(&root, &policy, Some("HYENA_HUMAN_ATTEST=ann")).unwrap();
+        assert_eq!(problems.len(), 1);
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn check_commit_allows_rotation_but_not_segment_edits() {
+        let root = std::env::temp_dir().join("hyena_hook_rotation");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(".notes")).unwrap();
+        let log = root.join(".notes/notes.ndjson");
+        fs::write(&log, "{\"a\":1}\n").unwrap();
+        run(&root, &["init", "-q"]);
+        run(&root, &["add", "."]);
+        run(&root, &["commit", "-q", "-m", "init"]);
+        let policy: Policy = serde_yaml::from_str("policy:\n  name: hyena\n").unwrap();
+
+        // Sealing moves the active content to a recorded segment; appending after it is fine.
+        segment::maybe_rotate(
+            &log,
+            &segment::Rotation {
+                max_bytes: Some(1),
+                max_age_hours: None,
+            },
+            chrono::Utc::now(),
+        )
+        .unwrap();
+        fs::write(&log, "{\"b\":2}\n").unwrap();
+        run(&root, &["add", "-A"]);
+        assert!(check_commit(&root, &policy, None).unwrap().is_empty());
+        run(&root, &["commit", "-q", "-m", "rotate"]);
+
+        // Editing the sealed segment is flagged, even as an append.
+        let seg = root.join(".notes/notes.000001.ndjson");
+        fs::write(&seg, "{\"a\":1}\n{\"x\":0}\n").unwrap();
+        run(&root, &["add", "-A"]);
+        let problems = check_commit(&root, &policy, None).unwrap();
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].contains("sealed log segment"));
+        run(&root, &["reset", "-q"]);
+        run(&root, &["checkout", "--", "."]);
+
+        // Truncating the active file behind an unrecorded "segment" is flagged.
+        fs::write(root.join(".notes/notes.000002.ndjson"), "{\"b\":2}\n").unwrap();
+        fs::write(&log, "").unwrap();
+        run(&root, &["add", "-A"]);
+        let problems = check_commit(&root, &policy, None).unwrap();
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].contains("not recorded"));
+        fs::remove_dir_all(&root).ok();
+    }
+}
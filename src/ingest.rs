@@ -0,0 +1,453 @@
+//! Ingest: chunk raw inputs and append new chunks to the derived log as `note_chunk` events.
+//!
+//! Incremental: `.hyena/ingest.json` records each raw file's hash and chunk hashes, so unchanged
+//! files are skipped and only chunks not seen before in that file are appended. Checkbox items
+//! are tracked there too (see [`crate::tasks`]); a `task` event is appended when one is new,
+//! changed or removed. So are each file's outgoing links (see [`crate::links`]).
+//!
+//! Files are parsed in the format the policy declares for them (see [`crate::formats`]); their
+//! metadata goes on each chunk's event, and when it or the declared format changes every chunk is
+//! appended again. Tasks and links are read from the same parsed chunks, so their heading paths
+//! and line numbers follow the format too.
+
+use crate::chunk::Chunk;
+use crate::event::{Body, ChunkRef, Event, Extra, GitProvenance, Payload};
+use crate::formats::{self, Format, Meta};
+use crate::links::{self, LinkItem};
+use crate::raw::{self, RawInputs};
+use crate::secrets::Scanner;
+use crate::tasks::{self, TaskItem};
+use crate::{derived, digest, git, segment};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+const MANIFEST_REL: &str = ".hyena/ingest.json";
+
+/// What ingest last saw of each raw file (keyed by root-relative path).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub files: BTreeMap<String, FileState>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileState {
+    pub sha256: String,
+    pub ingested_at: String,
+    #[serde(default)]
+    pub chunks: Vec<ChunkState>,
+    /// Checkbox items in the file; None if it was last ingested before tasks were extracted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tasks: Option<Vec<TaskItem>>,
+    /// Outgoing links; None if last ingested before links were extracted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<Vec<LinkItem>>,
+    /// Format the file was parsed as; a change re-parses it even if its content did not.
+    #[serde(default, skip_serializing_if = "is_markdown")]
+    pub format: Format,
+    /// Frontmatter, page properties or file keywords, as recorded on the chunks.
+    #[serde(default, skip_serializing_if = "Meta::is_empty")]
+    pub meta: Meta,
+}
+
+pub type ChunkState = ChunkRef;
+
+fn is_markdown(f: &Format) -> bool {
+    *f == Format::Markdown
+}
+
+/// Outcome of one ingest pass.
+#[derive(Debug, Default, PartialEq)]
+pub struct IngestStats {
+    pub files_changed: Vec<String>,
+    pub files_removed: Vec<String>,
+    pub chunks_appended: usize,
+    /// Checkbox items that were new or changed.
+    pub tasks_changed: usize,
+}
+
+pub fn manifest_path(root: &Path) -> PathBuf {
+    root.join(MANIFEST_REL)
+}
+
+pub fn load_manifest(root: &Path) -> Result<Manifest> {
+    let path = manifest_path(root);
+    if !path.is_file() {
+        return Ok(Manifest::default());
+    }
+    let s = std::fs::read_to_string(&path).with_context(|| format!("read {}", path.display()))?;
+    serde_json::from_str(&s).with_context(|| format!("parse {}", path.display()))
+}
+
+/// Write via temp file + rename so a crash never leaves a half-written manifest.
+fn save_manifest(root: &Path, m: &Manifest) -> Result<()> {
+    let path = manifest_path(root);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("create {}", parent.display()))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(m)? + "\n")
+        .with_context(|| format!("write {}", tmp.display()))?;
+    std::fs::rename(&tmp, &path).with_context(|| format!("write {}", path.display()))?;
+    Ok(())
+}
+
+/// Directory of a root-relative path, "." for files at the root.
+fn scope_of(rel: &str) -> String {
+    match rel.rsplit_once('/') {
+        Some((dir, _)) => dir.to_string(),
+        None => ".".to_string(),
+    }
+}
+
+fn chunk_event(
+    rel: &str,
+    c: &Chunk,
+    meta: &Meta,
+    git: Option<&GitProvenance>,
+    actor: &str,
+    ts: &str,
+) -> Event {
+    let mut e = Event::new(
+        ts.to_string(),
+        actor,
+        Body::Known(Payload::NoteChunk {
+            text: c.text.clone(),
+            heading_path: c.heading_path.clone(),
+            start_line: Some(c.start_line),
+            end_line: Some(c.end_line),
+            extra: Extra::new(),
+        }),
+    );
+    e.source = Some(rel.to_string());
+    e.scope = Some(scope_of(rel));
+    e.meta = meta.clone();
+    e.git = git.cloned();
+    e
+}
+
+fn task_event(
+    t: &TaskItem,
+    removed: bool,
+    git: Option<&GitProvenance>,
+    actor: &str,
+    ts: &str,
+) -> Event {
+    let mut e = Event::new(
+        ts.to_string(),
+        actor,
+        Body::Known(Payload::Task {
+            text: t.text.clone(),
+            done: t.done,
+            task_id: Some(t.id.clone()),
+            owner: t.owner.clone(),
+            due: t.due.clone(),
+            removed,
+            extra: Extra::new(),
+        }),
+    );
+    e.source = Some(t.source.clone());
+    e.scope = Some(scope_of(&t.source));
+    e.git = git.cloned();
+    e
+}
+
+/// Ingest raw files selected by `inputs`. Inside a git work tree, chunks record HEAD and the
+/// file's blob (see [`crate::git`]). With `only`, just those paths (absolute or root-relative)
+/// are considered; an `only` path that no longer exists is dropped from the manifest. A pass that
+/// changed anything ends with an `ingest_run` event listing every chunk of the changed files and
+/// the removed ones, so the manifest at any instant can be rebuilt from the log. Holds the
+/// manifest's lock from load to save, so concurrent runs (a watcher and a manual ingest) do not
+/// both append the same chunks.
+pub fn ingest(
+    root: &Path,
+    inputs: &RawInputs,
+    only: Option<&[PathBuf]>,
+    actor: &str,
+    ts: &str,
+    scanner: &Scanner,
+) -> Result<IngestStats> {
+    let _lock = segment::lock(&manifest_path(root))?;
+    let mut manifest = load_manifest(root)?;
+    let repo = git::probe(root);
+    let mut stats = IngestStats::default();
+    let discovered: Vec<String> = raw::discover_raw_files(root, None, inputs)?
+        .iter()
+        .filter_map(|p| raw::relative_for_glob(p, root))
+        .collect();
+
+    let wanted: Option<HashSet<String>> = only.map(|paths| {
+        paths
+            .iter()
+            .filter_map(|p| {
+                let abs = if p.is_absolute() {
+                    p.clone()
+                } else {
+                    root.join(p)
+                };
+                raw::relative_for_glob(&abs, root)
+            })
+            .collect()
+    });
+    let in_scope = |rel: &String| wanted.as_ref().is_none_or(|w| w.contains(rel));
+
+    for rel in discovered.iter().filter(|r| in_scope(r)) {
+        let abs = root.join(rel);
+        let content =
+            std::fs::read_to_string(&abs).with_context(|| format!("read {}", abs.display()))?;
+        let sha = digest::sha256_hex(content.as_bytes());
+        let format = inputs.format_of(rel);
+        let previous = manifest.files.get(rel);
+        if previous.is_some_and(|f| {
+            f.sha256 == sha && f.format == format && f.tasks.is_some() && f.links.is_some()
+        }) {
+            continue;
+        }
+        let parsed = formats::parse(rel, &content, format);
+        let seen: HashSet<&str> = previous
+            .filter(|f| f.format == format && f.meta == parsed.meta)
+            .map(|f| f.chunks.iter().map(|c| c.sha256.as_str()).collect())
+            .unwrap_or_default();
+        let chunks = &parsed.chunks;
+        let prov = repo
+            .as_ref()
+            .map(|r| r.provenance(Some((rel, content.as_bytes()))));
+        let mut states = Vec::with_capacity(chunks.len());
+        let mut appended = 0;
+        for c in chunks {
+            let chunk_sha = c.sha256();
+            if !seen.contains(chunk_sha.as_str()) {
+                let e = chunk_event(rel, c, &parsed.meta, prov.as_ref(), actor, ts);
+                derived::append_derived(root, &e, scanner)?;
+                appended += 1;
+            }
+            states.push(ChunkState {
+                sha256: chunk_sha,
+                heading_path: c.heading_path.clone(),
+                start_line: c.start_line,
+                end_line: c.end_line,
+            });
+        }
+        let known: HashMap<&str, &TaskItem> = previous
+            .and_then(|f| f.tasks.as_ref())
+            .map(|ts| ts.iter().map(|t| (t.id.as_str(), t)).collect())
+            .unwrap_or_default();
+        let items = tasks::extract(rel, chunks);
+        for t in &items {
+            if !known.get(t.id.as_str()).is_some_and(|k| k.same_state(t)) {
+                let e = task_event(t, false, prov.as_ref(), actor, ts);
+                derived::append_derived(root, &e, scanner)?;
+                stats.tasks_changed += 1;
+            }
+        }
+        let current: HashSet<&str> = items.iter().map(|t| t.id.as_str()).collect();
+        let dropped = previous
+            .and_then(|f| f.tasks.as_ref())
+            .into_iter()
+            .flatten()
+            .filter(|t| !current.contains(t.id.as_str()));
+        for t in dropped {
+            let e = task_event(t, true, prov.as_ref(), actor, ts);
+            derived::append_derived(root, &e, scanner)?;
+            stats.tasks_changed += 1;
+        }
+        stats.chunks_appended += appended;
+        stats.files_changed.push(rel.clone());
+        manifest.files.insert(
+            rel.clone(),
+            FileState {
+                sha256: sha,
+                ingested_at: ts.to_string(),
+                chunks: states,
+                tasks: Some(items),
+                links: Some(links::extract(rel, chunks)),
+                format,
+                meta: parsed.meta,
+            },
+        );
+    }
+
+    let present: HashSet<&String> = discovered.iter().collect();
+    let gone: Vec<String> = manifest
+        .files
+        .keys()
+        .filter(|rel| in_scope(rel) && !present.contains(rel))
+        .cloned()
+        .collect();
+    for rel in gone {
+        let dropped = manifest.files.remove(&rel).and_then(|f| f.tasks);
+        for t in dropped.iter().flatten() {
+            derived::append_derived(root, &task_event(t, true, None, actor, ts), scanner)?;
+            stats.tasks_changed += 1;
+        }
+        stats.files_removed.push(rel);
+    }
+
+    if !stats.files_changed.is_empty() || !stats.files_removed.is_empty() {
+        let file_chunks = stats
+            .files_changed
+            .iter()
+            .map(|rel| (rel.clone(), manifest.files[rel].chunks.clone()))
+            .collect();
+        let e = Event::new(
+            ts.to_string(),
+            actor,
+            Body::Known(Payload::IngestRun {
+                files: stats
+                    .files_changed
+                    .iter()
+                    .chain(&stats.files_removed)
+                    .cloned()
+                    .collect(),
+                chunks: stats.chunks_appended,
+                file_chunks,
+                removed: stats.files_removed.clone(),
+                extra: Extra::new(),
+            }),
+        );
+        derived::append_derived(root, &e, scanner)?;
+        save_manifest(root, &manifest)?;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn derived_lines(root: &Path) -> Vec<String> {
+        crate::segment::read_lines(&derived::derived_path(root)).unwrap()
+    }
+
+    #[test]
+    fn ingest_appends_only_new_chunks() {
+        let root = std::env::temp_dir().join("hyena_ingest_incremental");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("NOTES.md"), "# A\none\n# B\ntwo\n").unwrap();
+        fs::write(root.join("sub/NOTES.md"), "sub notes\n").unwrap();
+        let patterns = RawInputs {
+            patterns: vec!["**/NOTES.md".to_string()],
+            ..Default::default()
+        };
+
+        let stats = ingest(&root, &patterns, None, "human", "t1", &Scanner::default()).unwrap();
+        assert_eq!(stats.chunks_appended, 3);
+        assert_eq!(stats.files_changed, vec!["NOTES.md", "sub/NOTES.md"]);
+        let lines = derived_lines(&root);
+        assert!(lines[0].contains("\"kind\":\"note_chunk\""));
+        assert!(lines[0].contains("\"source\":\"NOTES.md\""));
+        assert!(lines[2].contains("\"scope\":\"sub\""));
+
+        // Unchanged: nothing appended, manifest untouched.
+        assert_eq!(
+            ingest(&root, &patterns, None, "human", "t2", &Scanner::default()).unwrap(),
+            IngestStats::default()
+        );
+
+        // Edit one section: only that chunk is new.
+        fs::write(root.join("NOTES.md"), "# A\none\n# B\ntwo, revised\n").unwrap();
+        let stats = ingest(&root, &patterns, None, "human", "t3", &Scanner::default()).unwrap();
+        assert_eq!(stats.chunks_appended, 1);
+        let lines = derived_lines(&root);
+        assert_eq!(lines.len(), 6);
+        assert!(lines[5].contains("\"kind\":\"ingest_run\""));
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn concurrent_ingests_append_each_chunk_once() {
+        let root = std::env::temp_dir().join("hyena_ingest_concurrent");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let body: String = (0..20).map(|i| format!("# S{}\nline {}\n", i, i)).collect();
+        fs::write(root.join("NOTES.md"), body).unwrap();
+        let patterns = RawInputs {
+            patterns: vec!["**/NOTES.md".to_string()],
+            ..Default::default()
+        };
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| ingest(&root, &patterns, None, "human", "t", &Scanner::default()));
+            }
+        });
+        let chunks = derived_lines(&root)
+            .iter()
+            .filter(|l| l.contains("\"kind\":\"note_chunk\""))
+            .count();
+        assert_eq!(chunks, 20);
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn changing_the_declared_format_reparses_an_unchanged_file() {
+        let root = std::env::temp_dir().join("hyena_ingest_format");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("plan.org"), "* TODO Ship\n- [ ] go\n").unwrap();
+        let mut inputs = RawInputs {
+            patterns: vec!["*.org".to_string()],
+            ..Default::default()
+        };
+        ingest(&root, &inputs, None, "human", "t1", &Scanner::default()).unwrap();
+
+        inputs.formats = vec![("*.org".to_string(), Format::Org)];
+        let stats = ingest(&root, &inputs, None, "human", "t2", &Scanner::default()).unwrap();
+        assert_eq!(stats.files_changed, vec!["plan.org"]);
+        assert_eq!(stats.chunks_appended, 1);
+        let m = load_manifest(&root).unwrap();
+        let file = &m.files["plan.org"];
+        assert_eq!(file.format, Format::Org);
+        assert_eq!(file.chunks[0].heading_path, vec!["Ship"]);
+        assert_eq!(file.tasks.as_ref().unwrap()[0].heading_path, vec!["Ship"]);
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn ingest_only_listed_paths_and_drops_deleted() {
+        let root = std::env::temp_dir().join("hyena_ingest_only");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        fs::write(root.join("a/NOTES.md"), "a\n").unwrap();
+        fs::write(root.join("b/NOTES.md"), "b\n").unwrap();
+        let patterns = RawInputs {
+            patterns: vec!["**/NOTES.md".to_string()],
+            ..Default::default()
+        };
+
+        let only = vec![root.join("a/NOTES.md")];
+        let stats = ingest(
+            &root,
+            &patterns,
+            Some(&only),
+            "human",
+            "t",
+            &Scanner::default(),
+        )
+        .unwrap();
+        assert_eq!(stats.files_changed, vec!["a/NOTES.md"]);
+        assert!(!load_manifest(&root)
+            .unwrap()
+            .files
+            .contains_key("b/NOTES.md"));
+
+        fs::remove_file(root.join("a/NOTES.md")).unwrap();
+        let only = vec![PathBuf::from("a/NOTES.md")];
+        let stats = ingest(
+            &root,
+            &patterns,
+            Some(&only),
+            "human",
+            "t",
+            &Scanner::default(),
+        )
+        .unwrap();
+        assert_eq!(stats.files_removed, vec!["a/NOTES.md"]);
+        assert!(load_manifest(&root).unwrap().files.is_empty());
+        fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,315 @@
+//! Links: Markdown `[text](path)` and wiki-style `[[Note]]` references in raw inputs.
+//!
+//! Ingest extracts each file's links (outside fenced code and inline code) and keeps them in
+//! `.hyena/ingest.json`. Markdown paths are stored root-relative, resolved against the linking
+//! file's directory (`/path` against root). Wiki names are stored as written and resolved when
+//! asked: next to the file, then from root, with or without `.md`, then as the file name of a
+//! unique raw input. URLs and in-page `#anchors` are not links here. Since targets are resolved
+//! and checked at query time, a link turns unbroken as soon as its target appears.
+
+use crate::chunk::{self, Chunk};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// One outgoing link of a raw input.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkItem {
+    /// Root-relative target (starts with `../` if it points outside root); for a wiki link, the
+    /// name as written (see [`resolve`]).
+    pub target: String,
+    /// 1-based line of the link in its source.
+    pub line: usize,
+    /// Written as `[[wiki]]` rather than `[text](path)`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub wiki: bool,
+}
+
+/// A link together with the file it is in.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Edge<'a> {
+    pub source: &'a str,
+    pub target: String,
+    pub line: usize,
+    pub broken: bool,
+}
+
+fn markdown_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"\[[^\]]*\]\(\s*<?([^)\s>]+)>?(?:\s+"[^"]*")?\s*\)"#)
+            .expect("markdown link pattern compiles")
+    })
+}
+
+fn wiki_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\[\[([^\]|#]+)(?:#[^\]|]*)?(?:\|[^\]]*)?\]\]")
+            .expect("wiki link pattern compiles")
+    })
+}
+
+/// `line` with inline code spans blanked out (same length, so nothing inside them matches).
+fn without_code_spans(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut in_code = false;
+    for c in line.chars() {
+        if c == '`' {
+            in_code = !in_code;
+            out.push(' ');
+        } else {
+            out.push(if in_code { ' ' } else { c });
+        }
+    }
+    out
+}
+
+/// Collapse `.` and `..` in a `/`-separated path; leading `..` that climb above root are kept.
+fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." if parts.last().is_some_and(|p| *p != "..") => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Directory of a root-relative file, "" at root.
+fn dir_of(rel: &str) -> &str {
+    rel.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+fn join(dir: &str, path: &str) -> String {
+    if dir.is_empty() {
+        normalize(path)
+    } else {
+        normalize(&format!("{}/{}", dir, path))
+    }
+}
+
+/// Root-relative target of a Markdown link destination, or None for URLs and anchors.
+fn resolve_markdown(source: &str, dest: &str) -> Option<String> {
+    let has_scheme = dest.split_once(':').is_some_and(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+    if has_scheme || dest.starts_with('#') || dest.starts_with("//") {
+        return None;
+    }
+    let path = dest.split(['#', '?']).next().unwrap_or_default();
+    if path.is_empty() {
+        return None;
+    }
+    Some(match path.strip_prefix('/') {
+        Some(from_root) => normalize(from_root),
+        None => join(dir_of(source), path),
+    })
+}
+
+/// Root-relative target of a wiki name; `files` are the root-relative raw inputs.
+pub fn resolve_wiki(root: &Path, source: &str, name: &str, files: &[String]) -> String {
+    let name = name.trim();
+    let with_md = format!("{}.md", name);
+    let candidates = [
+        join(dir_of(source), name),
+        join(dir_of(source), &with_md),
+        normalize(name),
+        normalize(&with_md),
+    ];
+    if let Some(found) = candidates.iter().find(|c| root.join(c).is_file()) {
+        return found.clone();
+    }
+    let by_name: Vec<&String> = files
+        .iter()
+        .filter(|f| {
+            let file = f.rsplit('/').next().unwrap_or(f);
+            file == name || file == with_md
+        })
+        .collect();
+    match by_name.as_slice() {
+        [only] => (*only).clone(),
+        _ => candidates[1].clone(),
+    }
+}
+
+/// Root-relative target of `link` from `source`: wiki names are resolved now against the
+/// filesystem and `files` (root-relative raw inputs).
+pub fn resolve(root: &Path, source: &str, link: &LinkItem, files: &[String]) -> String {
+    if link.wiki {
+        resolve_wiki(root, source, &link.target, files)
+    } else {
+        link.target.clone()
+    }
+}
+
+/// Links in the chunks of a raw input as its format parses it (see [`crate::formats::parse`]),
+/// in order; wiki names are kept unresolved.
+pub fn extract(source: &str, chunks: &[Chunk]) -> Vec<LinkItem> {
+    let mut out = Vec::new();
+    for c in chunks {
+        let mut in_fence = false;
+        for (i, line) in c.text.lines().enumerate() {
+            if chunk::is_fence(line) {
+                in_fence = !in_fence;
+                continue;
+            }
+            if in_fence || !line.contains('[') {
+                continue;
+            }
+            out.extend(line_links(source, line, c.start_line + i));
+        }
+    }
+    out
+}
+
+/// Links on one line (`line_no` is 1-based), in order.
+fn line_links(source: &str, line: &str, line_no: usize) -> Vec<LinkItem> {
+    let line_text = without_code_spans(line);
+    let mut found: Vec<(usize, LinkItem)> = Vec::new();
+    for caps in wiki_re().captures_iter(&line_text) {
+        let at = caps.get(0).map_or(0, |m| m.start());
+        found.push((
+            at,
+            LinkItem {
+                target: caps[1].trim().to_string(),
+                line: line_no,
+                wiki: true,
+            },
+        ));
+    }
+    for caps in markdown_re().captures_iter(&line_text) {
+        let at = caps.get(0).map_or(0, |m| m.start());
+        if let Some(target) = resolve_markdown(source, &caps[1]) {
+            found.push((
+                at,
+                LinkItem {
+                    target,
+                    line: line_no,
+                    wiki: false,
+                },
+            ));
+        }
+    }
+    found.sort_by_key(|(at, _)| *at);
+    found.into_iter().map(|(_, l)| l).collect()
+}
+
+/// True if `target` leaves root or does not exist.
+pub fn is_broken(root: &Path, target: &str) -> bool {
+    target.is_empty() || target == ".." || target.starts_with("../") || !root.join(target).exists()
+}
+
+/// Graphviz digraph; broken links are dashed red.
+pub fn render_dot(edges: &[Edge]) -> String {
+    let mut out = String::from("digraph hyena {\n  rankdir=LR;\n");
+    for e in edges {
+        out.push_str(&format!(
+            "  {:?} -> {:?}{};\n",
+            e.source,
+            e.target,
+            if e.broken {
+                " [style=dashed, color=red]"
+            } else {
+                ""
+            }
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// `{"nodes": [...], "edges": [{source, target, line, broken}]}`.
+pub fn render_json(edges: &[Edge]) -> serde_json::Value {
+    let mut nodes: Vec<&str> = edges
+        .iter()
+        .flat_map(|e| [e.source, e.target.as_str()])
+        .collect();
+    nodes.sort_unstable();
+    nodes.dedup();
+    serde_json::json!({ "nodes": nodes, "edges": edges })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn extracts_and_resolves_markdown_and_wiki_links() {
+        let root = std::env::temp_dir().join("hyena_links_extract");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("docs/design")).unwrap();
+        fs::write(root.join("docs/design/Parser.md"), "").unwrap();
+        fs::write(root.join("docs/NOTES.md"), "").unwrap();
+        let files = vec![
+            "docs/NOTES.md".to_string(),
+            "docs/design/Parser.md".to_string(),
+        ];
+        let md = "See [spec](../src/lib.rs#L10 \"code\") and [[Parser|the parser]].\n\
+                  ```\n[skip](me.md)\n```\n\
+                  `[not](a-link.md)` [web](https://example.com) [top](#intro) [root](/README.md)\n\
+                  [[Missing Note]] [up](../../../etc/passwd)\n";
+        let links = extract("docs/NOTES.md", &chunk::chunk_markdown(md));
+        let got: Vec<(String, usize, bool)> = links
+            .iter()
+            .map(|l| (resolve(&root, "docs/NOTES.md", l, &files), l.line, l.wiki))
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                ("src/lib.rs".to_string(), 1, false),
+                ("docs/design/Parser.md".to_string(), 1, true),
+                ("README.md".to_string(), 5, false),
+                ("docs/Missing Note.md".to_string(), 6, true),
+                ("../../etc/passwd".to_string(), 6, false),
+            ]
+        );
+        assert_eq!(links[3].target, "Missing Note");
+        assert!(!is_broken(&root, "docs/design/Parser.md"));
+        assert!(is_broken(&root, "docs/Missing Note.md"));
+        assert!(is_broken(&root, "../../etc/passwd"));
+
+        // The target appears after ingest: the stored name now resolves to it.
+        fs::create_dir_all(root.join("notes")).unwrap();
+        fs::write(root.join("notes/Missing Note.md"), "").unwrap();
+        let files = [files, vec!["notes/Missing Note.md".to_string()]].concat();
+        assert_eq!(
+            resolve(&root, "docs/NOTES.md", &links[3], &files),
+            "notes/Missing Note.md"
+        );
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn renders_dot_and_json() {
+        let edges = vec![
+            Edge {
+                source: "NOTES.md",
+                target: "src/lib.rs".to_string(),
+                line: 1,
+                broken: false,
+            },
+            Edge {
+                source: "NOTES.md",
+                target: "gone.md".to_string(),
+                line: 2,
+                broken: true,
+            },
+        ];
+        let dot = render_dot(&edges);
+        assert!(dot.contains("\"NOTES.md\" -> \"src/lib.rs\";"));
+        assert!(dot.contains("\"NOTES.md\" -> \"gone.md\" [style=dashed, color=red];"));
+        let json = render_json(&edges);
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 3);
+        assert_eq!(json["edges"][1]["broken"], true);
+    }
+}
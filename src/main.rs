@@ -1,15 +1,48 @@
 //! Hyena CLI: policy-enforcing, file-first agent substrate.
 //! Contract: repos/docs/internal/agent/HYENA_CLI_SPEC.md
 
+mod audit;
+mod changes;
+mod chunk;
+mod clock;
 mod context;
+mod derived;
+mod digest;
+mod event;
+mod export;
+mod formats;
+mod git;
+mod hook;
+mod ingest;
+mod links;
+mod overlay;
+mod paths;
 mod policy;
 mod raw;
+mod run;
 mod scratch;
 mod search;
+mod secrets;
+mod segment;
+mod serve;
+mod tasks;
+mod thread;
+mod tokens;
+mod view;
+mod watch;
+mod workspace;
 
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::io::Write;
 use std::path::PathBuf;
+use std::time::Duration;
+
+/// How often `read scratch --follow` checks for appended lines.
+const FOLLOW_POLL: Duration = Duration::from_millis(250);
+/// How often `run` checks the child and its log usage.
+const RUN_POLL: Duration = Duration::from_millis(100);
 
 #[derive(Parser)]
 #[command(
@@ -23,9 +56,18 @@ struct Cli {
     #[arg(long)]
     policy: Option<std::path::PathBuf>,
 
-    #[arg(long, default_value = "human", value_parser = ["human", "agent"])]
+    /// Who is acting: `human`, `agent` or any actor named in the policy
+    #[arg(long, env = "HYENA_ACTOR", default_value = "human", value_parser = parse_actor)]
     actor: String,
 
+    /// Pin the clock for entry timestamps and @runtime/@today (RFC 3339 or YYYY-MM-DD)
+    #[arg(long, env = "HYENA_NOW")]
+    now: Option<String>,
+
+    /// Workspace file listing several roots: `search`, `read derived` and `tasks` run in each
+    #[arg(long, env = "HYENA_WORKSPACE")]
+    workspace: Option<std::path::PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -44,17 +86,137 @@ enum Commands {
     },
     /// Walk NOTES.md, chunk, append events to .notes/notes.ndjson
     Ingest,
+    /// Re-ingest raw inputs whenever they change (Ctrl-C to stop)
+    Watch {
+        /// Quiet period after the last change before ingesting
+        #[arg(long, default_value_t = 500)]
+        debounce_ms: u64,
+    },
     /// Grep/scan .notes/notes.ndjson (and optionally scratch)
     Search {
         query: String,
         #[arg(long)]
         include_scratch: bool,
+        #[command(flatten)]
+        claims: ClaimArgs,
     },
     /// Human-only: append bullet to nearest NOTES.md
     Human {
         #[command(subcommand)]
         sub: HumanSub,
     },
+    /// Export logs and raw inputs to another format (read-only projection)
+    Export {
+        #[command(subcommand)]
+        to: ExportKind,
+    },
+    /// Check sealed log segments against their manifests (hashes, missing or stray segments)
+    Verify,
+    /// Copy a scratch entry into the derived log with `promoted_from` provenance
+    Promote {
+        scratch_id: String,
+        /// Edited text to record instead of the scratch entry's text
+        #[arg(long)]
+        summary: Option<String>,
+        /// Confidence in the promoted entry: 0.0–1.0 or low/med/high
+        #[arg(long, value_parser = parse_confidence)]
+        confidence: Option<f64>,
+    },
+    /// Git hooks that enforce the raw/derived doctrine at commit time
+    Hook {
+        #[command(subcommand)]
+        sub: HookSub,
+    },
+    /// Check staged changes: raw inputs need a human attestation, append-only logs may only grow
+    CheckCommit,
+    /// Inspect the policy in force
+    Policy {
+        #[command(subcommand)]
+        sub: PolicySub,
+    },
+    /// Run an agent command under the policy's `limits`, logging start and finish to scratch
+    Run {
+        /// Actor the command runs as (exported as HYENA_ACTOR)
+        #[arg(long = "as", default_value = "agent", value_parser = parse_actor)]
+        run_as: String,
+        /// Wall-clock limit in seconds (can only tighten the policy's max_wall_seconds)
+        #[arg(long)]
+        max_wall_seconds: Option<u64>,
+        /// Command and arguments, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Hash every file under root (before an agent session) and print the snapshot id
+    Snapshot,
+    /// Materialize the derived log's live entries under .hyena/views/ for `read derived --latest`
+    SnapshotDerived,
+    /// Diff the tree against a snapshot and log policy violations as derived audit events
+    Audit {
+        /// Snapshot to compare against, as `snapshot` printed it: `ID@SHA256` (or `FILE@SHA256`)
+        #[arg(long)]
+        since: String,
+    },
+    /// List checkbox tasks (`- [ ] …`) that ingest found in raw inputs
+    Tasks {
+        /// Only open tasks
+        #[arg(long, conflicts_with = "done")]
+        open: bool,
+        /// Only done tasks
+        #[arg(long)]
+        done: bool,
+        /// Only tasks assigned with `@OWNER`
+        #[arg(long)]
+        owner: Option<String>,
+        /// Only tasks due on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        due_by: Option<String>,
+        /// Only tasks in raw inputs under this directory
+        #[arg(long)]
+        scope: Option<std::path::PathBuf>,
+    },
+    /// Chunks of raw inputs added, removed or changed since the last ingest (or since --since)
+    Changes {
+        /// Compare with what was ingested by this time (RFC 3339, YYYY-MM-DD, or an event id)
+        #[arg(long)]
+        since: Option<String>,
+        #[arg(long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+    /// Outgoing links of a raw input (of all raw inputs if no path), as ingest found them
+    Links {
+        path: Option<std::path::PathBuf>,
+        /// Only links whose target is missing or outside root
+        #[arg(long)]
+        broken: bool,
+    },
+    /// Raw inputs that link to a file
+    Backlinks { path: std::path::PathBuf },
+    /// Link graph of all raw inputs
+    Graph {
+        #[arg(long, default_value = "dot", value_parser = ["dot", "json"])]
+        format: String,
+    },
+    /// Show the source text a derived event was written from, at its recorded git revision
+    Trace { event_id: String },
+    /// Serve the read commands as a local HTTP/JSON API (bearer tokens from `hyena token issue`)
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        http: String,
+        /// Allow listening on a non-loopback address
+        #[arg(long)]
+        allow_remote: bool,
+    },
+    /// API tokens for `hyena serve`
+    Token {
+        #[command(subcommand)]
+        sub: TokenSub,
+    },
+    /// Event schema for scratch and derived logs
+    Schema {
+        #[command(subcommand)]
+        sub: SchemaSub,
+    },
 }
 
 #[derive(Subcommand)]
@@ -72,12 +234,36 @@ enum ReadKind {
     Derived {
         #[arg(long)]
         scope_contains: Option<String>,
+        #[command(flatten)]
+        claims: ClaimArgs,
+        /// Only live entries (superseded, withdrawn and stale task entries left out)
+        #[arg(long)]
+        latest: bool,
         #[arg(long)]
         max: Option<usize>,
     },
     Scratch {
+        /// Oldest N matching lines
         #[arg(long)]
         max: Option<usize>,
+        /// Newest N matching lines (reads backwards from the end)
+        #[arg(long, conflicts_with = "max")]
+        tail: Option<usize>,
+        /// Only entries written by this actor
+        #[arg(long = "actor", id = "filter_actor")]
+        actor: Option<String>,
+        /// Only entries of this kind
+        #[arg(long)]
+        kind: Option<String>,
+        /// Only entries at or after this time (RFC 3339 or YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+        /// Keep running and print new matching lines as they are appended
+        #[arg(long)]
+        follow: bool,
+        /// Render the conversation tree containing this entry id
+        #[arg(long, conflicts_with_all = ["max", "tail", "filter_actor", "kind", "since", "follow"])]
+        thread: Option<String>,
     },
 }
 
@@ -87,15 +273,119 @@ enum WriteKind {
         text: String,
         #[arg(long)]
         kind: Option<String>,
+        #[command(flatten)]
+        links: LinkArgs,
     },
-    Derived {
-        text: String,
-        #[arg(long)]
-        kind: Option<String>,
+    Derived(DerivedArgs),
+}
+
+#[derive(clap::Args)]
+struct DerivedArgs {
+    text: String,
+    #[arg(long)]
+    kind: Option<String>,
+    #[arg(long)]
+    scope: Option<std::path::PathBuf>,
+    #[arg(long)]
+    source: Option<std::path::PathBuf>,
+    /// Confidence in the entry: 0.0–1.0 or low/med/high
+    #[arg(long, value_parser = parse_confidence)]
+    confidence: Option<f64>,
+    /// hypothesis, verified or refuted
+    #[arg(long, value_parser = parse_status)]
+    status: Option<event::Status>,
+    /// Entry ID or source span (PATH:LINE or PATH:START-END) backing the entry (repeatable)
+    #[arg(long)]
+    evidence: Vec<String>,
+    #[command(flatten)]
+    links: LinkArgs,
+}
+
+/// Filter and order derived entries by status and confidence.
+#[derive(clap::Args)]
+struct ClaimArgs {
+    /// Only entries with this status (hypothesis, verified, refuted)
+    #[arg(long, value_parser = parse_status)]
+    status: Option<event::Status>,
+    /// Only entries with at least this confidence (0.0–1.0 or low/med/high)
+    #[arg(long, value_parser = parse_confidence)]
+    min_confidence: Option<f64>,
+    /// Order by confidence (highest first) or status (verified first)
+    #[arg(long, value_parser = ["confidence", "status"])]
+    sort: Option<String>,
+}
+
+impl ClaimArgs {
+    fn filter(&self) -> derived::ClaimFilter {
+        derived::ClaimFilter {
+            status: self.status,
+            min_confidence: self.min_confidence,
+            sort: self.sort.as_deref().map(|s| match s {
+                "status" => derived::SortBy::Status,
+                _ => derived::SortBy::Confidence,
+            }),
+            hidden: Vec::new(),
+        }
+    }
+}
+
+/// Links from a new entry to existing ones (by entry id). Prior lines are never edited.
+#[derive(clap::Args)]
+struct LinkArgs {
+    /// Entry this one replies to
+    #[arg(long = "parent")]
+    parent_id: Option<String>,
+    /// Entry this one corrects or replaces
+    #[arg(long)]
+    supersedes: Option<String>,
+    /// Entry this one cites (repeatable)
+    #[arg(long = "ref")]
+    refs: Vec<String>,
+}
+
+#[derive(Subcommand)]
+enum ExportKind {
+    /// Materialize events, provenance links and raw chunks into SQLite tables with FTS5 indexes;
+    /// re-running only adds what changed
+    Sqlite { file: std::path::PathBuf },
+}
+
+#[derive(Subcommand)]
+enum SchemaSub {
+    /// Print JSON Schema for log events (or write it to --out)
+    Export {
         #[arg(long)]
-        scope: Option<std::path::PathBuf>,
+        out: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum PolicySub {
+    /// Print the effective policy for a path (root policy merged with subtree overlays)
+    Explain { path: Option<std::path::PathBuf> },
+}
+
+#[derive(Subcommand)]
+enum TokenSub {
+    /// Issue a token that acts as ACTOR; it is printed once and only its hash is kept
+    Issue {
+        #[arg(value_parser = parse_actor)]
+        actor: String,
+    },
+    /// Revoke every token of ACTOR
+    Revoke {
+        #[arg(value_parser = parse_actor)]
+        actor: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum HookSub {
+    /// Install a git pre-commit hook that runs `hyena check-commit`
+    Install {
+        /// Replace an existing pre-commit hook not written by hyena
         #[arg(long)]
-        source: Option<std::path::PathBuf>,
+        force: bool,
     },
 }
 
@@ -108,40 +398,208 @@ enum HumanSub {
     },
 }
 
+/// Actor names are recorded in every log entry: keep them short identifiers.
+fn parse_actor(s: &str) -> std::result::Result<String, String> {
+    let ok = !s.is_empty()
+        && s.len() <= 64
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if ok {
+        Ok(s.to_string())
+    } else {
+        Err("actor must be 1-64 characters of A-Z, a-z, 0-9, '-', '_' or '.'".to_string())
+    }
+}
+
+/// Confidence as a number in 0.0–1.0 or one of low/med/high.
+fn parse_confidence(s: &str) -> std::result::Result<f64, String> {
+    event::parse_confidence(s).map_err(|e| e.to_string())
+}
+
+/// Claim status: hypothesis, verified or refuted.
+fn parse_status(s: &str) -> std::result::Result<event::Status, String> {
+    s.parse().map_err(|e: anyhow::Error| e.to_string())
+}
+
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Some(ws) = workspace_for(&cli, &matches)? {
+        return cmd_workspace(ws, &cli.actor, &cli.command);
+    }
     let policy_path = cli
         .policy
         .unwrap_or_else(|| cli.root.join(".agent/POLICY.yaml"));
+    if let Some(policy) = policy::load_if_exists(&policy_path)? {
+        policy.check_actor(&cli.actor)?;
+    }
+    let clock = clock::Clock::from_arg(cli.now.as_deref())?;
 
     match &cli.command {
         Commands::Read { what } => match what {
-            ReadKind::Context { path, max_lines } => {
-                cmd_read_context(&cli.root, &policy_path, path.as_ref(), *max_lines)?
+            ReadKind::Context { path, max_lines } => cmd_read_context(
+                &cli.root,
+                &policy_path,
+                &cli.actor,
+                path.as_ref(),
+                *max_lines,
+            )?,
+            ReadKind::Raw { scope } => {
+                cmd_read_raw(&cli.root, &policy_path, &cli.actor, scope.as_ref())?
             }
-            ReadKind::Raw { scope } => cmd_read_raw(&cli.root, &policy_path, scope.as_ref())?,
-            ReadKind::Derived { .. } => println!("read derived (stub)"),
-            ReadKind::Scratch { max } => cmd_read_scratch(&cli.root, *max)?,
+            ReadKind::Derived {
+                scope_contains,
+                claims,
+                latest,
+                max,
+            } => cmd_read_derived(
+                &cli.root,
+                scope_contains.as_deref(),
+                &visible_claims(&cli.root, &policy_path, &cli.actor, claims)?,
+                *latest,
+                *max,
+            )?,
+            ReadKind::Scratch {
+                max,
+                tail,
+                actor,
+                kind,
+                since,
+                follow,
+                thread,
+            } => match thread {
+                Some(id) => cmd_read_thread(&cli.root, id)?,
+                None => {
+                    let filter = scratch::ScratchFilter {
+                        actor: actor.clone(),
+                        kind: kind.clone(),
+                        since: since.as_deref().map(scratch::parse_since).transpose()?,
+                    };
+                    cmd_read_scratch(&cli.root, *max, *tail, &filter, *follow)?
+                }
+            },
         },
         Commands::Write { what } => match what {
-            WriteKind::Scratch { text, kind } => {
-                cmd_write_scratch(&cli.root, &cli.actor, text, kind.as_deref())?
+            WriteKind::Scratch { text, kind, links } => cmd_write_scratch(
+                &cli.root,
+                &policy_path,
+                &cli.actor,
+                clock,
+                text,
+                kind.as_deref(),
+                links,
+            )?,
+            WriteKind::Derived(args) => {
+                cmd_write_derived(&cli.root, &policy_path, &cli.actor, clock, args)?
             }
-            WriteKind::Derived { .. } => println!("write derived (stub)"),
         },
-        Commands::Ingest => println!("ingest (stub)"),
+        Commands::Ingest => cmd_ingest(&cli.root, &policy_path, &cli.actor, clock)?,
+        Commands::Watch { debounce_ms } => {
+            cmd_watch(&cli.root, &policy_path, &cli.actor, clock, *debounce_ms)?
+        }
         Commands::Search {
             query,
             include_scratch,
-        } => cmd_search(&cli.root, query, *include_scratch)?,
+            claims,
+        } => cmd_search(
+            &cli.root,
+            query,
+            *include_scratch,
+            &visible_claims(&cli.root, &policy_path, &cli.actor, claims)?,
+        )?,
         Commands::Human { sub } => match sub {
-            HumanSub::AppendRaw { .. } => {
-                if cli.actor != "human" {
-                    anyhow::bail!("human append-raw requires --actor human");
+            HumanSub::AppendRaw { path, .. } => {
+                let policy = policy::load_if_exists(&policy_path)?;
+                let may_write = match &policy {
+                    Some(p) => p.perms(&cli.actor).can_write_raw_inputs,
+                    None => cli.actor == "human",
+                };
+                if !may_write {
+                    anyhow::bail!(
+                        "human append-raw requires an actor with can_write_raw_inputs (e.g. --actor human)"
+                    );
+                }
+                if let Some(path) = path {
+                    let symlinks = policy.map(|p| p.symlinks()).unwrap_or_default();
+                    resolve_path_arg(&cli.root, path, symlinks)?;
                 }
                 println!("human append-raw (stub)");
             }
         },
+        Commands::Export { to } => match to {
+            ExportKind::Sqlite { file } => {
+                cmd_export_sqlite(&cli.root, &policy_path, &cli.actor, file)?
+            }
+        },
+        Commands::Verify => cmd_verify(&cli.root)?,
+        Commands::Promote {
+            scratch_id,
+            summary,
+            confidence,
+        } => cmd_promote(
+            &cli.root,
+            &policy_path,
+            &cli.actor,
+            clock,
+            scratch_id,
+            summary.as_deref(),
+            *confidence,
+        )?,
+        Commands::Hook { sub } => match sub {
+            HookSub::Install { force } => cmd_hook_install(&cli.root, &policy_path, *force)?,
+        },
+        Commands::Policy { sub } => match sub {
+            PolicySub::Explain { path } => {
+                cmd_policy_explain(&cli.root, &policy_path, path.as_ref())?
+            }
+        },
+        Commands::CheckCommit => cmd_check_commit(&cli.root, &policy_path)?,
+        Commands::Run {
+            run_as,
+            max_wall_seconds,
+            command,
+        } => cmd_run(
+            &cli.root,
+            &policy_path,
+            &cli.actor,
+            clock,
+            run_as,
+            *max_wall_seconds,
+            command,
+        )?,
+        Commands::Snapshot => cmd_snapshot(&cli.root, clock)?,
+        Commands::SnapshotDerived => cmd_snapshot_derived(&cli.root, clock)?,
+        Commands::Audit { since } => cmd_audit(&cli.root, &policy_path, &cli.actor, clock, since)?,
+        Commands::Tasks {
+            open,
+            done,
+            owner,
+            due_by,
+            scope,
+        } => {
+            let filter = task_filter(*open, *done, owner, due_by.as_deref())?;
+            cmd_tasks(&cli.root, &policy_path, &cli.actor, filter, scope.as_ref())?
+        }
+        Commands::Changes { since, format } => cmd_changes(
+            &cli.root,
+            &policy_path,
+            &cli.actor,
+            since.as_deref(),
+            format,
+        )?,
+        Commands::Links { path, broken } => {
+            cmd_links(&cli.root, &policy_path, &cli.actor, path.as_ref(), *broken)?
+        }
+        Commands::Backlinks { path } => cmd_backlinks(&cli.root, &policy_path, &cli.actor, path)?,
+        Commands::Graph { format } => cmd_graph(&cli.root, &policy_path, &cli.actor, format)?,
+        Commands::Trace { event_id } => cmd_trace(&cli.root, &policy_path, &cli.actor, event_id)?,
+        Commands::Serve { http, allow_remote } => {
+            serve::serve(&cli.root, &policy_path, http, *allow_remote)?
+        }
+        Commands::Token { sub } => cmd_token(&cli.root, &policy_path, &cli.actor, clock, sub)?,
+        Commands::Schema { sub } => match sub {
+            SchemaSub::Export { out } => cmd_schema_export(out.as_ref())?,
+        },
     }
     Ok(())
 }
@@ -149,13 +607,15 @@ fn main() -> Result<()> {
 fn cmd_read_context(
     root: &std::path::Path,
     policy_path: &std::path::Path,
+    actor: &str,
     path: Option<&PathBuf>,
     max_lines: Option<usize>,
 ) -> Result<()> {
-    let _policy = policy::load(policy_path)?;
-    let (_dir, notes_path) = context::nearest_notes_dir(root, path.cloned())
-        .ok_or_else(|| anyhow::anyhow!("no NOTES.md found from path (walk up to root)"))?;
-    let excerpt = context::read_notes_excerpt(&notes_path, max_lines)?;
+    let policy = policy::load(policy_path)?;
+    let start = path
+        .map(|p| resolve_path_arg(root, p, policy.symlinks()))
+        .transpose()?;
+    let (notes_path, excerpt) = context::read_context(root, policy_path, actor, start, max_lines)?;
     println!("{}", notes_path.display());
     println!("---");
     print!("{}", excerpt);
@@ -167,47 +627,1140 @@ fn cmd_read_context(
     Ok(())
 }
 
+/// Canonical absolute form of a path argument; errors if it leaves root.
+fn resolve_path_arg(
+    root: &std::path::Path,
+    arg: &std::path::Path,
+    symlinks: paths::SymlinkPolicy,
+) -> Result<PathBuf> {
+    paths::resolve_under_root(&paths::canonical_root(root)?, arg, symlinks)
+}
+
+/// Root-relative form of a path argument (as stored in events); errors if it leaves root.
+fn relative_path_arg(
+    root: &std::path::Path,
+    arg: &std::path::Path,
+    symlinks: paths::SymlinkPolicy,
+) -> Result<String> {
+    paths::relative_under_root(&paths::canonical_root(root)?, arg, symlinks)
+}
+
 fn cmd_read_raw(
     root: &std::path::Path,
     policy_path: &std::path::Path,
+    actor: &str,
     scope: Option<&PathBuf>,
 ) -> Result<()> {
     let policy = policy::load(policy_path)?;
-    let patterns: Vec<String> = policy
-        .filesystem
-        .as_ref()
-        .and_then(|fs| fs.raw_inputs.as_ref())
-        .and_then(|ri| ri.patterns.as_ref())
-        .cloned()
-        .unwrap_or_else(|| {
-            raw::DEFAULT_RAW_PATTERNS
-                .iter()
-                .map(|s| (*s).to_string())
-                .collect()
-        });
-    let paths = raw::discover_raw_files(root, scope, &patterns)?;
+    let scope = scope
+        .map(|s| relative_path_arg(root, s, policy.symlinks()).map(PathBuf::from))
+        .transpose()?;
+    if !policy.may_read_raw(actor) {
+        anyhow::bail!("policy: actor '{}' may not read raw inputs", actor);
+    }
+    let inputs = overlay::raw_inputs_for(root, policy_path, &policy, actor)?;
+    let paths = raw::discover_raw_files(root, scope.as_ref(), &inputs)?;
     let out = raw::read_raw_content(&paths)?;
     print!("{}", out);
     Ok(())
 }
 
-fn cmd_read_scratch(root: &std::path::Path, max: Option<usize>) -> Result<()> {
-    let out = scratch::read_scratch(root, max)?;
+fn cmd_read_scratch(
+    root: &std::path::Path,
+    max: Option<usize>,
+    tail: Option<usize>,
+    filter: &scratch::ScratchFilter,
+    follow: bool,
+) -> Result<()> {
+    let out = match tail {
+        Some(n) => scratch::tail_scratch(root, n, filter)?,
+        None => scratch::read_scratch(root, max, filter)?,
+    };
     print!("{}", out);
+    if follow {
+        let mut stdout = std::io::stdout();
+        stdout.flush()?;
+        scratch::follow_scratch(root, filter, FOLLOW_POLL, |line| {
+            writeln!(stdout, "{}", line).is_ok() && stdout.flush().is_ok()
+        })?;
+    }
+    Ok(())
+}
+
+fn cmd_read_thread(root: &std::path::Path, id: &str) -> Result<()> {
+    print!("{}", scratch::read_thread(root, id)?);
+    Ok(())
+}
+
+/// Build a new event of `kind` (default note) with reply/supersede/ref links, stamped by `clock`
+/// and with `@runtime`/`@today` in `text` resolved.
+fn new_event(
+    actor: &str,
+    clock: clock::Clock,
+    kind: Option<&str>,
+    text: &str,
+    links: &LinkArgs,
+) -> Result<event::Event> {
+    event::Event::new(
+        clock.ts(),
+        actor,
+        event::Body::from_kind_text(kind.unwrap_or("note"), &clock.resolve_tokens(text)),
+    )
+    .with_links(
+        links.parent_id.as_deref(),
+        links.supersedes.as_deref(),
+        &links.refs,
+    )
+}
+
+/// Seal the active log file first if policy rotation says it is due.
+fn rotate_if_due(
+    active: &std::path::Path,
+    rotation: Option<&segment::Rotation>,
+    clock: clock::Clock,
+) -> Result<()> {
+    if let Some(rotation) = rotation {
+        segment::maybe_rotate(active, rotation, clock.now())?;
+    }
     Ok(())
 }
 
 fn cmd_write_scratch(
     root: &std::path::Path,
+    policy_path: &std::path::Path,
     actor: &str,
+    clock: clock::Clock,
     text: &str,
     kind: Option<&str>,
+    links: &LinkArgs,
+) -> Result<()> {
+    let policy = policy::load_if_exists(policy_path)?;
+    let e = new_event(actor, clock, kind, text, links)?;
+    let scanner = match &policy {
+        Some(p) => p.secret_scanner()?,
+        None => secrets::Scanner::default(),
+    };
+    rotate_if_due(
+        &scratch::scratch_path(root),
+        policy.as_ref().and_then(|p| p.scratch_rotation()),
+        clock,
+    )?;
+    let written = scratch::append_scratch(root, &e, &scanner)?;
+    report_redactions(&written);
+    println!("{}", written.id.as_deref().unwrap_or_default());
+    Ok(())
+}
+
+fn cmd_read_derived(
+    root: &std::path::Path,
+    scope_contains: Option<&str>,
+    claims: &derived::ClaimFilter,
+    latest: bool,
+    max: Option<usize>,
+) -> Result<()> {
+    let out = derived::read_derived(root, scope_contains, claims, latest, max)?;
+    print!("{}", out);
+    Ok(())
+}
+
+fn cmd_write_derived(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    clock: clock::Clock,
+    args: &DerivedArgs,
 ) -> Result<()> {
-    scratch::append_scratch(root, actor, kind.unwrap_or("note"), text)
+    let policy = policy::load(policy_path)?;
+    require_derived_append(&policy, actor)?;
+    let mut e = new_event(actor, clock, args.kind.as_deref(), &args.text, &args.links)?;
+    let symlinks = policy.symlinks();
+    e.scope = args
+        .scope
+        .as_ref()
+        .map(|p| relative_path_arg(root, p, symlinks))
+        .transpose()?;
+    e.source = args
+        .source
+        .as_ref()
+        .map(|p| relative_path_arg(root, p, symlinks))
+        .transpose()?;
+    if let Some(source) = &e.source {
+        require_visible_source(root, policy_path, &policy, actor, source)?;
+    }
+    e.confidence = args.confidence;
+    e.status = args.status;
+    e.evidence = args
+        .evidence
+        .iter()
+        .map(|ev| evidence_arg(root, ev, symlinks))
+        .collect::<Result<_>>()?;
+    if e.status == Some(event::Status::Verified) && e.evidence.is_empty() {
+        eprintln!("warning: entry marked verified cites no --evidence");
+    }
+    e.git = git::probe(root).map(|r| {
+        let content = e
+            .source
+            .as_ref()
+            .and_then(|s| std::fs::read(root.join(s)).ok());
+        r.provenance(e.source.as_deref().zip(content.as_deref()))
+    });
+    let scanner = policy.secret_scanner()?;
+    rotate_if_due(
+        &derived::derived_path(root),
+        policy.derived_rotation(),
+        clock,
+    )?;
+    let written = derived::append_derived(root, &e, &scanner)?;
+    report_redactions(&written);
+    println!("{}", written.id.as_deref().unwrap_or_default());
+    Ok(())
 }
 
-fn cmd_search(root: &std::path::Path, query: &str, include_scratch: bool) -> Result<()> {
-    let lines = search::search(root, query, include_scratch)?;
+/// `--evidence` as stored: an entry ID as is, or a source span `PATH:LINE[-LINE]` with its path
+/// made root-relative.
+fn evidence_arg(
+    root: &std::path::Path,
+    arg: &str,
+    symlinks: paths::SymlinkPolicy,
+) -> Result<String> {
+    if event::check_id(arg).is_ok() {
+        return Ok(arg.to_string());
+    }
+    let span = arg.rsplit_once(':').filter(|(path, lines)| {
+        let (start, end) = lines.split_once('-').unwrap_or((lines, lines));
+        match (start.parse::<usize>(), end.parse::<usize>()) {
+            (Ok(start), Ok(end)) => !path.is_empty() && start >= 1 && end >= start,
+            _ => false,
+        }
+    });
+    let Some((path, lines)) = span else {
+        anyhow::bail!(
+            "--evidence must be an entry id or a source span PATH:LINE[-LINE], got '{}'",
+            arg
+        );
+    };
+    let rel = relative_path_arg(root, std::path::Path::new(path), symlinks)?;
+    Ok(format!("{}:{}", rel, lines))
+}
+
+/// Redact secrets in `e` (or reject the write, per policy) and say so on stderr.
+/// Tell the writer what the append path redacted from `written`, if anything.
+fn report_redactions(written: &event::Event) {
+    if !written.redactions.is_empty() {
+        let rules: Vec<&str> = written.redactions.iter().map(|r| r.rule.as_str()).collect();
+        eprintln!(
+            "redacted {} secret(s): {}",
+            written.redactions.len(),
+            rules.join(", ")
+        );
+    }
+}
+
+fn require_derived_append(policy: &policy::Policy, actor: &str) -> Result<()> {
+    if !policy.may_append_derived(actor) {
+        anyhow::bail!("policy: actor '{}' may not append to derived logs", actor);
+    }
+    Ok(())
+}
+
+fn cmd_promote(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    clock: clock::Clock,
+    scratch_id: &str,
+    summary: Option<&str>,
+    confidence: Option<f64>,
+) -> Result<()> {
+    let policy = policy::load(policy_path)?;
+    require_derived_append(&policy, actor)?;
+    rotate_if_due(
+        &derived::derived_path(root),
+        policy.derived_rotation(),
+        clock,
+    )?;
+    let summary = summary.map(|s| clock.resolve_tokens(s));
+    let e = derived::promote(
+        root,
+        scratch_id,
+        actor,
+        clock.ts(),
+        summary.as_deref(),
+        confidence,
+        &policy.secret_scanner()?,
+    )?;
+    println!("{}", e.id.as_deref().unwrap_or_default());
+    Ok(())
+}
+
+fn cmd_policy_explain(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    path: Option<&PathBuf>,
+) -> Result<()> {
+    let policy = policy::load(policy_path)?;
+    let start = path
+        .map(|p| resolve_path_arg(root, p, policy.symlinks()))
+        .transpose()?;
+    let eff = overlay::effective(root, policy_path, start)?;
+    println!(
+        "# effective policy for {}",
+        path.map_or(".".to_string(), |p| p.display().to_string())
+    );
+    for source in &eff.sources {
+        println!("# from: {}", source.display());
+    }
+    for note in &eff.ignored {
+        println!("# ignored: {}", note);
+    }
+    print!("{}", serde_yaml::to_string(&eff.value)?);
+    Ok(())
+}
+
+fn cmd_hook_install(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    force: bool,
+) -> Result<()> {
+    let root = paths::canonical_root(root)?;
+    let policy_path = std::path::absolute(policy_path)
+        .with_context(|| format!("resolve {}", policy_path.display()))?;
+    let exe = std::env::current_exe().context("locate hyena executable")?;
+    let hook = hook::install(&root, &exe, &policy_path, force)?;
+    println!("installed {}", hook.display());
+    Ok(())
+}
+
+fn cmd_check_commit(root: &std::path::Path, policy_path: &std::path::Path) -> Result<()> {
+    let policy = policy::load(policy_path)?;
+    let attestation = hook::attestation();
+    let problems = hook::check_commit(root, &policy, attestation.as_deref())?;
+    if problems.is_empty() {
+        println!("check-commit: ok");
+        return Ok(());
+    }
+    for p in &problems {
+        eprintln!("check-commit: {}", p);
+    }
+    anyhow::bail!(
+        "check-commit: {} problem(s); commit blocked",
+        problems.len()
+    )
+}
+
+fn cmd_run(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    clock: clock::Clock,
+    run_as: &str,
+    max_wall_seconds: Option<u64>,
+    command: &[String],
+) -> Result<()> {
+    let policy = policy::load_if_exists(policy_path)?;
+    if let Some(p) = &policy {
+        p.check_actor(run_as)?;
+    }
+    let mut limits = policy
+        .as_ref()
+        .and_then(|p| p.limits.clone())
+        .unwrap_or_default();
+    if let Some(s) = max_wall_seconds {
+        limits.max_wall_seconds = Some(limits.max_wall_seconds.map_or(s, |p| p.min(s)));
+    }
+    let scratch_log = scratch::scratch_path(root);
+    let scratch_rotation = policy.as_ref().and_then(|p| p.scratch_rotation());
+
+    let start = event::Event::new(
+        clock.ts(),
+        run_as,
+        event::Body::Known(event::Payload::RunStarted {
+            command: command.to_vec(),
+            run_as: run_as.to_string(),
+            started_by: Some(actor.to_string()),
+            extra: event::Extra::new(),
+        }),
+    );
+    let scanner = match &policy {
+        Some(p) => p.secret_scanner()?,
+        None => secrets::Scanner::default(),
+    };
+    rotate_if_due(&scratch_log, scratch_rotation, clock)?;
+    let start = scratch::append_scratch(root, &start, &scanner)?;
+    report_redactions(&start);
+    let run_id = start.id.unwrap_or_default();
+
+    let pinned = clock.pinned().map(|t| t.to_rfc3339());
+    let mut env = vec![("HYENA_ACTOR", run_as), ("HYENA_RUN_ID", run_id.as_str())];
+    if let Some(now) = &pinned {
+        env.push(("HYENA_NOW", now));
+    }
+    // The command runs in its own process group, out of reach of the terminal's Ctrl-C: pass it on.
+    let interrupt = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    {
+        let interrupt = interrupt.clone();
+        ctrlc::set_handler(move || interrupt.store(true, std::sync::atomic::Ordering::SeqCst))
+            .context("install SIGINT handler")?;
+    }
+    let report = run::supervise(
+        &scratch_log,
+        &derived::derived_path(root),
+        command,
+        &env,
+        &limits,
+        RUN_POLL,
+        &interrupt,
+    )?;
+    let (exit_code, killed_for) = match &report.outcome {
+        run::Outcome::Exited(status) => (status.code(), None),
+        run::Outcome::Killed(reason) => (None, Some(reason.clone())),
+    };
+    let mut finish = event::Event::new(
+        clock.ts(),
+        run_as,
+        event::Body::Known(event::Payload::RunFinished {
+            exit_code,
+            killed_for: killed_for.clone(),
+            duration_ms: report.duration.as_millis() as u64,
+            scratch_bytes: report.scratch_bytes,
+            derived_entries: report.derived_entries,
+            extra: event::Extra::new(),
+        }),
+    );
+    finish.parent_id = Some(run_id.clone());
+    rotate_if_due(&scratch_log, scratch_rotation, clock)?;
+    scratch::append_scratch(root, &finish, &scanner)?;
+
+    eprintln!(
+        "run {}: {} in {:.1}s, {} scratch bytes, {} derived entries",
+        run_id,
+        match (&killed_for, exit_code) {
+            (Some(reason), _) => format!("killed ({})", reason),
+            (None, Some(code)) => format!("exit {}", code),
+            (None, None) => "terminated by signal".to_string(),
+        },
+        report.duration.as_secs_f64(),
+        report.scratch_bytes,
+        report.derived_entries
+    );
+    if let Some(reason) = killed_for {
+        anyhow::bail!("run killed: exceeded {}", reason);
+    }
+    match exit_code {
+        Some(0) => Ok(()),
+        Some(code) => std::process::exit(code),
+        None => std::process::exit(1),
+    }
+}
+
+fn cmd_snapshot(root: &std::path::Path, clock: clock::Clock) -> Result<()> {
+    let snapshot = audit::take(root, event::next_id(), clock.ts())?;
+    let (path, sha256) = audit::save(root, &snapshot)?;
+    eprintln!(
+        "snapshot of {} files -> {}",
+        snapshot.files.len(),
+        path.display()
+    );
+    // The hash pins the baseline: audit refuses it if the file is rewritten meanwhile.
+    println!("{}@{}", snapshot.id, sha256);
+    Ok(())
+}
+
+fn cmd_audit(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    clock: clock::Clock,
+    since: &str,
+) -> Result<()> {
+    let policy = policy::load(policy_path)?;
+    require_derived_append(&policy, actor)?;
+    let before = audit::load(root, since)?;
+    let ts = clock.ts();
+    let after = audit::take(root, event::next_id(), ts.clone())?;
+    let findings = audit::audit(root, &policy, &before, &after)?;
+    if findings.is_empty() {
+        println!("audit: ok ({} files checked)", after.files.len());
+        return Ok(());
+    }
+    rotate_if_due(
+        &derived::derived_path(root),
+        policy.derived_rotation(),
+        clock,
+    )?;
+    let scanner = policy.secret_scanner()?;
+    for f in &findings {
+        let e = event::Event::new(
+            ts.clone(),
+            actor,
+            event::Body::Known(event::Payload::AuditFinding {
+                finding: f.finding.to_string(),
+                path: f.path.clone(),
+                snapshot: before.id.clone(),
+                text: f.detail.clone(),
+                extra: event::Extra::new(),
+            }),
+        );
+        derived::append_derived(root, &e, &scanner)?;
+        println!("{} {}: {}", f.finding, f.path, f.detail);
+    }
+    anyhow::bail!(
+        "audit: {} violation(s) since snapshot {}",
+        findings.len(),
+        before.id
+    )
+}
+
+fn cmd_snapshot_derived(root: &std::path::Path, clock: clock::Clock) -> Result<()> {
+    let meta = view::build(root, clock.ts())?;
+    println!(
+        "{}: {} live of {} entries, {} threads",
+        view::view_path(root).display(),
+        meta.entries,
+        meta.log_lines,
+        meta.threads.len()
+    );
+    Ok(())
+}
+
+/// Issue or revoke a `serve` token. Tokens grant access as another actor, so only an actor that
+/// may write raw inputs (a human) manages them, and each change is logged to derived.
+fn cmd_token(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    clock: clock::Clock,
+    sub: &TokenSub,
+) -> Result<()> {
+    let policy = policy::load_if_exists(policy_path)?;
+    let may_manage = match &policy {
+        Some(p) => p.check_actor(actor).is_ok() && p.perms(actor).can_write_raw_inputs,
+        None => actor == "human",
+    };
+    if !may_manage {
+        anyhow::bail!(
+            "policy: actor '{}' may not manage tokens (requires can_write_raw_inputs, e.g. --actor human)",
+            actor
+        );
+    }
+    let log_as = |payload: event::Payload| -> Result<()> {
+        if let Some(p) = &policy {
+            require_derived_append(p, actor)?;
+            rotate_if_due(&derived::derived_path(root), p.derived_rotation(), clock)?;
+        }
+        let e = event::Event::new(clock.ts(), actor, event::Body::Known(payload));
+        let scanner = match &policy {
+            Some(p) => p.secret_scanner()?,
+            None => secrets::Scanner::default(),
+        };
+        report_redactions(&derived::append_derived(root, &e, &scanner)?);
+        Ok(())
+    };
+    match sub {
+        TokenSub::Issue { actor: for_actor } => {
+            if let Some(p) = &policy {
+                p.check_actor(for_actor)?;
+            }
+            let token = tokens::issue(root, for_actor, clock.ts())?;
+            log_as(event::Payload::TokenIssued {
+                for_actor: for_actor.clone(),
+                sha256: digest::sha256_hex(token.as_bytes()),
+                extra: event::Extra::new(),
+            })?;
+            println!("{}", token);
+            eprintln!(
+                "token for {} stored (hashed) in {}; it is not shown again",
+                for_actor,
+                tokens::TOKENS_REL
+            );
+        }
+        TokenSub::Revoke { actor: for_actor } => {
+            let revoked = tokens::revoke(root, for_actor)?;
+            log_as(event::Payload::TokenRevoked {
+                for_actor: for_actor.clone(),
+                revoked,
+                extra: event::Extra::new(),
+            })?;
+            eprintln!("revoked {} token(s) of {}", revoked, for_actor);
+        }
+    }
+    Ok(())
+}
+
+fn task_filter(
+    open: bool,
+    done: bool,
+    owner: &Option<String>,
+    due_by: Option<&str>,
+) -> Result<tasks::TaskFilter> {
+    Ok(tasks::TaskFilter {
+        done: (open || done).then_some(done),
+        owner: owner.clone(),
+        due_by: due_by
+            .map(|d| clock::parse_instant(d, "--due-by").map(|t| t.date_naive()))
+            .transpose()?,
+        scope: None,
+    })
+}
+
+fn cmd_tasks(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    mut filter: tasks::TaskFilter,
+    scope: Option<&PathBuf>,
+) -> Result<()> {
+    let policy = policy::load(policy_path)?;
+    filter.scope = scope
+        .map(|s| relative_path_arg(root, s, policy.symlinks()))
+        .transpose()?;
+    for line in task_lines(root, policy_path, &policy, actor, &filter)? {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Rendered tasks from raw inputs the actor may read.
+fn task_lines(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    policy: &policy::Policy,
+    actor: &str,
+    filter: &tasks::TaskFilter,
+) -> Result<Vec<String>> {
+    let visible = visible_raw(root, policy_path, policy, actor)?;
+    let manifest = ingest::load_manifest(root)?;
+    let mut out = Vec::new();
+    for (rel, file) in &manifest.files {
+        if !visible.contains(rel) {
+            continue;
+        }
+        for t in file.tasks.iter().flatten().filter(|t| filter.matches(t)) {
+            out.push(tasks::render(t));
+        }
+    }
+    Ok(out)
+}
+
+/// Workspace the command fans out over. `--workspace` must name a fan-out command and excludes
+/// `--root`/`--policy`; `$HYENA_WORKSPACE` applies only to fan-out commands run without them.
+fn workspace_for<'a>(cli: &'a Cli, matches: &ArgMatches) -> Result<Option<&'a std::path::Path>> {
+    let Some(ws) = cli.workspace.as_deref() else {
+        return Ok(None);
+    };
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let fans_out = matches!(
+        cli.command,
+        Commands::Search { .. }
+            | Commands::Tasks { .. }
+            | Commands::Read {
+                what: ReadKind::Derived { .. }
+            }
+    );
+    let single_root = given("root") || given("policy");
+    if given("workspace") {
+        if single_root {
+            anyhow::bail!("--workspace cannot be combined with --root or --policy");
+        }
+        if !fans_out {
+            anyhow::bail!("--workspace works with search, read derived and tasks");
+        }
+        return Ok(Some(ws));
+    }
+    Ok((fans_out && !single_root).then_some(ws))
+}
+
+/// `search`, `read derived` or `tasks` in every root of a workspace, each under its own policy.
+fn cmd_workspace(path: &std::path::Path, actor: &str, command: &Commands) -> Result<()> {
+    let members = workspace::load(path)?;
+    let out = match command {
+        Commands::Search {
+            query,
+            include_scratch,
+            claims,
+        } => workspace::fan_out(&members, actor, |m, policy| {
+            let claims = member_claims(m, policy, actor, claims)?;
+            Ok(claims.apply(search::search(&m.root, query, *include_scratch)?))
+        })?,
+        Commands::Read {
+            what:
+                ReadKind::Derived {
+                    scope_contains,
+                    claims,
+                    latest,
+                    max,
+                },
+        } => workspace::fan_out(&members, actor, |m, policy| {
+            let out = derived::read_derived(
+                &m.root,
+                scope_contains.as_deref(),
+                &member_claims(m, policy, actor, claims)?,
+                *latest,
+                *max,
+            )?;
+            Ok(out.lines().map(str::to_string).collect())
+        })?,
+        Commands::Tasks {
+            open,
+            done,
+            owner,
+            due_by,
+            scope,
+        } => {
+            if scope.is_some() {
+                anyhow::bail!(
+                    "--scope names a directory in one root; it cannot be used with --workspace"
+                );
+            }
+            let filter = task_filter(*open, *done, owner, due_by.as_deref())?;
+            workspace::fan_out(&members, actor, |m, policy| {
+                task_lines(&m.root, &m.policy_path(), policy, actor, &filter)
+            })?
+        }
+        _ => anyhow::bail!("--workspace works with search, read derived and tasks"),
+    };
+    for line in &out.lines {
+        println!("{}", line);
+    }
+    for (alias, reason) in &out.skipped {
+        eprintln!("workspace: skipped {}: {}", alias, reason);
+    }
+    Ok(())
+}
+
+/// Fail unless `source` is a raw input the actor may read now.
+fn require_visible_source(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    policy: &policy::Policy,
+    actor: &str,
+    source: &str,
+) -> Result<()> {
+    if !visible_raw(root, policy_path, policy, actor)?.contains(source) {
+        anyhow::bail!(
+            "policy: {} is not a raw input actor '{}' may read",
+            source,
+            actor
+        );
+    }
+    Ok(())
+}
+
+/// Root-relative raw inputs the actor may read now (overlays can hide subtrees).
+fn visible_raw(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    policy: &policy::Policy,
+    actor: &str,
+) -> Result<std::collections::HashSet<String>> {
+    if !policy.may_read_raw(actor) {
+        anyhow::bail!("policy: actor '{}' may not read raw inputs", actor);
+    }
+    let inputs = overlay::raw_inputs_for(root, policy_path, policy, actor)?;
+    Ok(raw::discover_raw_files(root, None, &inputs)?
+        .iter()
+        .filter_map(|p| raw::relative_for_glob(p, root))
+        .collect())
+}
+
+/// Links recorded by ingest from raw inputs the actor may read, in path order. Wiki names are
+/// resolved against the files as they are now, among those the actor may read.
+fn link_edges<'a>(
+    root: &std::path::Path,
+    manifest: &'a ingest::Manifest,
+    visible: &std::collections::HashSet<String>,
+) -> Vec<links::Edge<'a>> {
+    let mut files: Vec<String> = visible.iter().cloned().collect();
+    files.sort();
+    let files = &files;
+    manifest
+        .files
+        .iter()
+        .filter(|(rel, _)| visible.contains(*rel))
+        .flat_map(|(rel, file)| {
+            file.links.iter().flatten().map(move |l| {
+                let target = links::resolve(root, rel, l, files);
+                links::Edge {
+                    source: rel,
+                    broken: links::is_broken(root, &target),
+                    target,
+                    line: l.line,
+                }
+            })
+        })
+        .collect()
+}
+
+/// Instant for `changes --since`: a timestamp or date, or the `ts` of a derived or scratch event.
+fn since_instant(root: &std::path::Path, since: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    if event::check_id(since).is_err() {
+        return clock::parse_instant(since, "--since");
+    }
+    let e = match derived::find_derived(root, since)? {
+        Some(e) => e,
+        None => scratch::scratch_events(root)?
+            .into_iter()
+            .find(|e| e.id.as_deref() == Some(since))
+            .ok_or_else(|| anyhow::anyhow!("no derived or scratch event with id {}", since))?,
+    };
+    clock::parse_instant(&e.ts, "event ts")
+}
+
+fn cmd_changes(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    since: Option<&str>,
+    format: &str,
+) -> Result<()> {
+    let policy = policy::load(policy_path)?;
+    let visible = visible_raw(root, policy_path, &policy, actor)?;
+    let events: Vec<event::Event> = segment::read_lines(&derived::derived_path(root))?
+        .iter()
+        .filter_map(|l| event::Event::parse(l))
+        .collect();
+    let mut baseline = match since {
+        Some(s) => changes::from_log(&events, since_instant(root, s)?),
+        None => changes::from_manifest(&ingest::load_manifest(root)?),
+    };
+    // Files that still exist but are hidden from the actor stay out of the diff.
+    baseline.retain(|rel, _| visible.contains(rel) || !root.join(rel).exists());
+    let inputs = overlay::raw_inputs_for(root, policy_path, &policy, actor)?;
+    let mut current = Vec::new();
+    let mut rels: Vec<&String> = visible.iter().collect();
+    rels.sort();
+    for rel in rels {
+        let abs = root.join(rel);
+        let content =
+            std::fs::read_to_string(&abs).with_context(|| format!("read {}", abs.display()))?;
+        let parsed = formats::parse(rel, &content, inputs.format_of(rel));
+        current.push((rel.clone(), parsed.chunks));
+    }
+    let found = changes::diff(&baseline, &current, &changes::known_texts(&events));
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&found)?),
+        _ => {
+            for c in &found {
+                println!("{}", changes::render(c));
+            }
+        }
+    }
+    let count = |kind: &str| found.iter().filter(|c| c.change == kind).count();
+    eprintln!(
+        "{} added, {} changed, {} removed chunk(s)",
+        count("added"),
+        count("changed"),
+        count("removed")
+    );
+    Ok(())
+}
+
+fn cmd_links(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    path: Option<&PathBuf>,
+    broken_only: bool,
+) -> Result<()> {
+    let policy = policy::load(policy_path)?;
+    let path = path
+        .map(|p| relative_path_arg(root, p, policy.symlinks()))
+        .transpose()?;
+    let visible = visible_raw(root, policy_path, &policy, actor)?;
+    let manifest = ingest::load_manifest(root)?;
+    let mut broken = 0;
+    for e in link_edges(root, &manifest, &visible) {
+        if path.as_deref().is_some_and(|p| p != e.source) || (broken_only && !e.broken) {
+            continue;
+        }
+        if e.broken {
+            broken += 1;
+        }
+        println!(
+            "{}:{} -> {}{}",
+            e.source,
+            e.line,
+            e.target,
+            if e.broken { "  (broken)" } else { "" }
+        );
+    }
+    if broken > 0 {
+        eprintln!("{} broken link(s)", broken);
+    }
+    Ok(())
+}
+
+fn cmd_backlinks(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    path: &std::path::Path,
+) -> Result<()> {
+    let policy = policy::load(policy_path)?;
+    let target = relative_path_arg(root, path, policy.symlinks())?;
+    let visible = visible_raw(root, policy_path, &policy, actor)?;
+    let manifest = ingest::load_manifest(root)?;
+    for e in link_edges(root, &manifest, &visible) {
+        if e.target == target {
+            println!("{}:{}", e.source, e.line);
+        }
+    }
+    Ok(())
+}
+
+fn cmd_graph(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    format: &str,
+) -> Result<()> {
+    let policy = policy::load(policy_path)?;
+    let visible = visible_raw(root, policy_path, &policy, actor)?;
+    let manifest = ingest::load_manifest(root)?;
+    let edges = link_edges(root, &manifest, &visible);
+    match format {
+        "json" => println!(
+            "{}",
+            serde_json::to_string_pretty(&links::render_json(&edges))?
+        ),
+        _ => print!("{}", links::render_dot(&edges)),
+    }
+    Ok(())
+}
+
+fn cmd_trace(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    id: &str,
+) -> Result<()> {
+    let policy = policy::load(policy_path)?;
+    let e = derived::find_derived(root, id)?
+        .ok_or_else(|| anyhow::anyhow!("no derived event with id {}", id))?;
+    let source = e
+        .source
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("event {} has no source", id))?;
+    require_visible_source(root, policy_path, &policy, actor, source)?;
+    let prov = e
+        .git
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("event {} has no git provenance", id))?;
+    if let Some(blob) = prov.blob.as_deref().filter(|b| !git::has_object(root, b)) {
+        anyhow::bail!(
+            "{} as read for event {} (blob {}) is not in the git object store: it was never \
+             committed or staged, so its text cannot be traced",
+            source,
+            id,
+            blob
+        );
+    }
+    let text = git::source_text(root, prov, source)?;
+    let range = match &e.body {
+        event::Body::Known(event::Payload::NoteChunk {
+            start_line: Some(start),
+            end_line: Some(end),
+            ..
+        }) => Some((*start, *end)),
+        _ => None,
+    };
+    let mut header = format!("{} {} {}", id, e.kind(), source);
+    if let Some((start, end)) = range {
+        header.push_str(&format!(":{}-{}", start, end));
+    }
+    header.push_str(&format!(" @ {}", prov.commit));
+    if prov.dirty {
+        header.push_str(" (source had uncommitted changes)");
+    }
+    println!("{}", header);
+    println!("---");
+    match range {
+        Some((start, end)) => {
+            for line in text
+                .lines()
+                .skip(start.saturating_sub(1))
+                .take((end + 1).saturating_sub(start))
+            {
+                println!("{}", line);
+            }
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+fn cmd_ingest(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    clock: clock::Clock,
+) -> Result<()> {
+    let policy = policy::load(policy_path)?;
+    require_derived_append(&policy, actor)?;
+    rotate_if_due(
+        &derived::derived_path(root),
+        policy.derived_rotation(),
+        clock,
+    )?;
+    let ts = clock.ts();
+    // Every raw input, whoever runs ingest: the manifest is shared, and readers are filtered at
+    // query time.
+    let inputs = overlay::raw_inputs_all(root, policy_path, &policy)?;
+    let stats = ingest::ingest(root, &inputs, None, actor, &ts, &policy.secret_scanner()?)?;
+    println!(
+        "ingested {} changed files, {} new chunks, {} tasks changed, {} removed",
+        stats.files_changed.len(),
+        stats.chunks_appended,
+        stats.tasks_changed,
+        stats.files_removed.len()
+    );
+    Ok(())
+}
+
+fn cmd_watch(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    clock: clock::Clock,
+    debounce_ms: u64,
+) -> Result<()> {
+    let policy = policy::load(policy_path)?;
+    require_derived_append(&policy, actor)?;
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("resolve {}", root.display()))?;
+    let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    {
+        let stop = stop.clone();
+        ctrlc::set_handler(move || stop.store(true, std::sync::atomic::Ordering::SeqCst))
+            .context("install SIGINT handler")?;
+    }
+    eprintln!("watching {} (Ctrl-C to stop)", root.display());
+    watch::watch(
+        &root,
+        &overlay::raw_inputs_all(&root, policy_path, &policy)?,
+        actor,
+        clock,
+        &policy.secret_scanner()?,
+        Duration::from_millis(debounce_ms),
+        &stop,
+        |stats| {
+            let files: Vec<&str> = stats
+                .files_changed
+                .iter()
+                .chain(&stats.files_removed)
+                .map(String::as_str)
+                .collect();
+            println!(
+                "ingest: {} new chunks from {}",
+                stats.chunks_appended,
+                files.join(", ")
+            );
+        },
+    )?;
+    eprintln!("watch stopped");
+    Ok(())
+}
+
+fn cmd_export_sqlite(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    file: &std::path::Path,
+) -> Result<()> {
+    let policy = policy::load(policy_path)?;
+    let inputs = overlay::raw_inputs_for(root, policy_path, &policy, actor)?;
+    let visible = derived::ClaimFilter {
+        hidden: overlay::hidden_subtrees(root, policy_path, &policy, actor)?,
+        ..Default::default()
+    };
+    let stats = export::export_sqlite(root, file, &inputs, &visible)?;
+    println!(
+        "exported {} new events, {} raw files updated, {} removed -> {}",
+        stats.events,
+        stats.raw_files,
+        stats.raw_removed,
+        file.display()
+    );
+    Ok(())
+}
+
+fn cmd_verify(root: &std::path::Path) -> Result<()> {
+    let mut failed = false;
+    for log in [derived::derived_path(root), scratch::scratch_path(root)] {
+        let problems = segment::verify(&log)?;
+        let sealed = segment::read_manifest(&log)?.len();
+        if problems.is_empty() {
+            println!("ok {} ({} sealed segments)", log.display(), sealed);
+        }
+        for p in &problems {
+            println!("FAIL {}", p);
+        }
+        failed |= !problems.is_empty();
+    }
+    // Claims marked verified should say what verified them; a warning, not a failure.
+    for line in segment::read_lines(&derived::derived_path(root))? {
+        let Some(e) = event::Event::parse(&line) else {
+            continue;
+        };
+        if e.status == Some(event::Status::Verified) && e.evidence.is_empty() {
+            println!(
+                "WARN {}: verified entry {} cites no evidence",
+                derived::derived_path(root).display(),
+                e.id.as_deref().unwrap_or("(no id)")
+            );
+        }
+    }
+    if failed {
+        anyhow::bail!("verify failed");
+    }
+    Ok(())
+}
+
+fn cmd_schema_export(out: Option<&PathBuf>) -> Result<()> {
+    let schema = serde_json::to_string_pretty(&event::json_schema())?;
+    match out {
+        Some(path) => std::fs::write(path, schema + "\n")
+            .with_context(|| format!("write {}", path.display()))?,
+        None => println!("{}", schema),
+    }
+    Ok(())
+}
+
+/// `claims` plus the subtrees whose raw inputs `actor` may not read: entries ingested from there
+/// are not shown.
+fn visible_claims(
+    root: &std::path::Path,
+    policy_path: &std::path::Path,
+    actor: &str,
+    claims: &ClaimArgs,
+) -> Result<derived::ClaimFilter> {
+    let mut filter = claims.filter();
+    if let Some(policy) = policy::load_if_exists(policy_path)? {
+        filter.hidden = overlay::hidden_subtrees(root, policy_path, &policy, actor)?;
+    }
+    Ok(filter)
+}
+
+/// [`visible_claims`] in one workspace root.
+fn member_claims(
+    m: &workspace::Member,
+    policy: &policy::Policy,
+    actor: &str,
+    claims: &ClaimArgs,
+) -> Result<derived::ClaimFilter> {
+    let mut filter = claims.filter();
+    filter.hidden = overlay::hidden_subtrees(&m.root, &m.policy_path(), policy, actor)?;
+    Ok(filter)
+}
+
+fn cmd_search(
+    root: &std::path::Path,
+    query: &str,
+    include_scratch: bool,
+    claims: &derived::ClaimFilter,
+) -> Result<()> {
+    let lines = claims.apply(search::search(root, query, include_scratch)?);
     for line in &lines {
         println!("{}", line);
     }
@@ -0,0 +1,405 @@
+//! Policy overlays: nested `.agent/POLICY.yaml` files refine the root policy for their subtree.
+//!
+//! The effective policy for a path merges the root policy with each overlay from root down to the
+//! path's directory (found with [`crate::context::dirs_up_to_root`]). Overlays may only restrict:
+//! - a flag may be turned off; turning one on needs the parent to have it on already,
+//! - `exclude` and `enforced_on` lists grow; other lists may only shrink to a subset,
+//! - `symlinks` may become `refuse`; other scalars (rotation, …) are left as the parent has them.
+//!
+//! The root policy can set `overlays: { allow_extend: true }` to let overlays grant more, too.
+//! `policy:` (name/version) and `overlays:` in an overlay are not merged. Globs in every policy file
+//! are relative to root. Anything an overlay asks for but may not have is reported by
+//! `policy explain`.
+
+use crate::context;
+use crate::policy::{self, Policy};
+use crate::raw::{self, RawInputs};
+use anyhow::{Context, Result};
+use serde_yaml::Value;
+use std::path::{Path, PathBuf};
+
+/// Overlay file, relative to the directory it governs.
+pub const OVERLAY_REL: &str = ".agent/POLICY.yaml";
+
+/// Lists where extra entries narrow what is allowed.
+const RESTRICTING_LISTS: &[&str] = &["exclude", "enforced_on"];
+
+/// Merged policy for one path and how it came about.
+pub struct Effective {
+    pub policy: Policy,
+    pub value: Value,
+    /// Root policy first, then overlays from the top down.
+    pub sources: Vec<PathBuf>,
+    /// Overlay settings that were not applied, as "file: key.path = value".
+    pub ignored: Vec<String>,
+}
+
+/// Effective policy at `path` (root if None): the root policy at `policy_path` refined by every
+/// overlay between root and `path`.
+pub fn effective(root: &Path, policy_path: &Path, path: Option<PathBuf>) -> Result<Effective> {
+    let mut value = policy::load_value(policy_path)?;
+    let extend = policy::from_value(value.clone())?.overlays_may_extend();
+    let mut sources = vec![policy_path.to_path_buf()];
+    let mut ignored = Vec::new();
+    // Nearest-first up to root; root's own file is `policy_path`, so skip it.
+    let dirs = context::dirs_up_to_root(root, path);
+    for dir in dirs.iter().rev().skip(1) {
+        let file = dir.join(OVERLAY_REL);
+        if !file.is_file() {
+            continue;
+        }
+        let child = policy::load_value(&file)?;
+        policy::parse_value(child.clone())
+            .with_context(|| format!("overlay {}", file.display()))?;
+        let Value::Mapping(mut child) = child else {
+            continue;
+        };
+        child.remove("policy");
+        child.remove("overlays");
+        let mut notes = Vec::new();
+        merge(
+            &mut value,
+            Value::Mapping(child),
+            "",
+            "",
+            extend,
+            &mut notes,
+        );
+        ignored.extend(
+            notes
+                .into_iter()
+                .map(|n| format!("{}: {}", file.display(), n)),
+        );
+        sources.push(file);
+    }
+    Ok(Effective {
+        policy: policy::from_value(value.clone())?,
+        value,
+        sources,
+        ignored,
+    })
+}
+
+/// Merge `child` into `parent` under the restrict-only rules (or freely, with `extend`).
+fn merge(
+    parent: &mut Value,
+    child: Value,
+    key: &str,
+    path: &str,
+    extend: bool,
+    ignored: &mut Vec<String>,
+) {
+    let refuse = |child: &Value, ignored: &mut Vec<String>| {
+        let shown = serde_yaml::to_string(child).unwrap_or_default();
+        ignored.push(format!(
+            "{} = {} (overlays may only restrict)",
+            path,
+            shown.trim()
+        ));
+    };
+    match (parent, child) {
+        (p @ (Value::Mapping(_) | Value::Null), Value::Mapping(c)) => {
+            if p.is_null() {
+                *p = Value::Mapping(Default::default());
+            }
+            let Value::Mapping(pm) = p else {
+                unreachable!()
+            };
+            for (k, v) in c {
+                let Some(sub) = k.as_str().map(str::to_string) else {
+                    continue;
+                };
+                let sub_path = if path.is_empty() {
+                    sub.clone()
+                } else {
+                    format!("{}.{}", path, sub)
+                };
+                let slot = pm.entry(k).or_insert(Value::Null);
+                merge(slot, v, &sub, &sub_path, extend, ignored);
+                if slot.is_null() {
+                    pm.remove(sub.as_str());
+                }
+            }
+        }
+        (p @ (Value::Bool(_) | Value::Null), Value::Bool(c)) => {
+            if !c || extend || p.as_bool() == Some(true) {
+                *p = Value::Bool(c);
+            } else {
+                refuse(&Value::Bool(c), ignored);
+            }
+        }
+        (p @ (Value::Sequence(_) | Value::Null), Value::Sequence(c)) => {
+            let current = p.as_sequence().cloned().unwrap_or_default();
+            if RESTRICTING_LISTS.contains(&key) || extend {
+                let mut merged = current;
+                for item in c {
+                    if !merged.contains(&item) {
+                        merged.push(item);
+                    }
+                }
+                *p = Value::Sequence(merged);
+            } else if !p.is_null() && c.iter().all(|item| current.contains(item)) {
+                *p = Value::Sequence(c);
+            } else {
+                refuse(&Value::Sequence(c), ignored);
+            }
+        }
+        (p, Value::String(c)) if key == "symlinks" => {
+            if c == "refuse" || extend {
+                *p = Value::String(c);
+            } else if p.as_str() != Some(c.as_str()) {
+                refuse(&Value::String(c), ignored);
+            }
+        }
+        (p, c) => {
+            if extend {
+                *p = c;
+            } else if *p != c {
+                refuse(&c, ignored);
+            }
+        }
+    }
+}
+
+/// Raw inputs as `actor` may see them: the root policy's inputs with each overlay's effective
+/// inputs for its own subtree, minus every subtree whose effective policy denies `actor` raw reads.
+pub fn raw_inputs_for(
+    root: &Path,
+    policy_path: &Path,
+    base: &Policy,
+    actor: &str,
+) -> Result<RawInputs> {
+    let (mut inputs, hidden) = scan_overlays(root, policy_path, base, Some(actor))?;
+    let levels = std::iter::once(&mut inputs.exclude)
+        .chain(inputs.subtrees.iter_mut().map(|(_, sub)| &mut sub.exclude));
+    for exclude in levels {
+        for rel in &hidden {
+            if !exclude.contains(rel) {
+                exclude.push(rel.clone());
+            }
+        }
+    }
+    Ok(inputs)
+}
+
+/// Every raw input, whoever may read it: what ingest chunks (readers are filtered at query time
+/// by [`hidden_subtrees`]).
+pub fn raw_inputs_all(root: &Path, policy_path: &Path, base: &Policy) -> Result<RawInputs> {
+    Ok(scan_overlays(root, policy_path, base, None)?.0)
+}
+
+/// Root-relative directories whose effective policy denies `actor` raw reads.
+pub fn hidden_subtrees(
+    root: &Path,
+    policy_path: &Path,
+    base: &Policy,
+    actor: &str,
+) -> Result<Vec<String>> {
+    Ok(scan_overlays(root, policy_path, base, Some(actor))?.1)
+}
+
+/// The root policy's raw inputs with every overlay's effective inputs as a subtree, and (if
+/// `actor` is given) the overlay directories that deny it raw reads.
+fn scan_overlays(
+    root: &Path,
+    policy_path: &Path,
+    base: &Policy,
+    actor: Option<&str>,
+) -> Result<(RawInputs, Vec<String>)> {
+    let mut inputs = base.raw_inputs();
+    let mut hidden = Vec::new();
+    let mut subtrees = Vec::new();
+    let overlay_files = raw::discover_raw_files(
+        root,
+        None,
+        &RawInputs {
+            patterns: vec![format!("**/{}", OVERLAY_REL)],
+            exclude: inputs.exclude.clone(),
+            ..Default::default()
+        },
+    )?;
+    for file in overlay_files {
+        let Some(dir) = file.parent().and_then(Path::parent) else {
+            continue;
+        };
+        let Some(rel) = raw::relative_for_glob(dir, root).filter(|r| !r.is_empty()) else {
+            continue;
+        };
+        let eff = effective(root, policy_path, Some(dir.to_path_buf()))?;
+        subtrees.push((rel.clone(), eff.policy.raw_inputs()));
+        if actor.is_some_and(|a| !eff.policy.may_read_raw(a)) {
+            hidden.push(rel);
+        }
+    }
+    inputs.subtrees = subtrees;
+    Ok((inputs, hidden))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write(path: &Path, s: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, s).unwrap();
+    }
+
+    const ROOT_POLICY: &str = r#"
+policy:
+  name: hyena
+actors:
+  agent:
+    can_write_raw_inputs: false
+filesystem:
+  symlinks: resolve
+  raw_inputs:
+    patterns: ["**/NOTES.md", "**/*.notes.md"]
+"#;
+
+    #[test]
+    fn overlay_restricts_but_cannot_extend() {
+        let root = std::env::temp_dir().join("hyena_overlay_restrict");
+        let _ = fs::remove_dir_all(&root);
+        write(&root.join(".agent/POLICY.yaml"), ROOT_POLICY);
+        write(
+            &root.join("legal/.agent/POLICY.yaml"),
+            r#"
+policy:
+  name: hyena
+actors:
+  agent:
+    can_read_raw_inputs: false
+    can_write_raw_inputs: true
+filesystem:
+  symlinks: refuse
+  raw_inputs:
+    patterns: ["**/NOTES.md"]
+    exclude: ["legal/drafts"]
+"#,
+        );
+        fs::create_dir_all(root.join("legal/sub")).unwrap();
+        let policy_path = root.join(".agent/POLICY.yaml");
+
+        let eff = effective(&root, &policy_path, Some(PathBuf::from("legal/sub"))).unwrap();
+        assert_eq!(eff.sources.len(), 2);
+        let agent = eff.policy.perms("agent");
+        assert!(!agent.can_read_raw_inputs);
+        assert!(!agent.can_write_raw_inputs);
+        assert_eq!(eff.policy.symlinks(), crate::paths::SymlinkPolicy::Refuse);
+        let inputs = eff.policy.raw_inputs();
+        assert_eq!(inputs.patterns, vec!["**/NOTES.md"]);
+        assert_eq!(inputs.exclude, vec!["legal/drafts"]);
+        assert_eq!(eff.ignored.len(), 1, "{:?}", eff.ignored);
+        assert!(eff.ignored[0].contains("actors.agent.can_write_raw_inputs"));
+
+        // Outside the subtree the root policy applies unchanged.
+        let top = effective(&root, &policy_path, None).unwrap();
+        assert_eq!(top.sources.len(), 1);
+        assert!(top.policy.may_read_raw("agent"));
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn allow_extend_lets_overlay_grant() {
+        let root = std::env::temp_dir().join("hyena_overlay_extend");
+        let _ = fs::remove_dir_all(&root);
+        write(
+            &root.join(".agent/POLICY.yaml"),
+            &format!("{}overlays:\n  allow_extend: true\n", ROOT_POLICY),
+        );
+        write(
+            &root.join("lab/.agent/POLICY.yaml"),
+            "policy:\n  name: hyena\nactors:\n  agent:\n    can_write_raw_inputs: true\n",
+        );
+        let eff = effective(
+            &root,
+            &root.join(".agent/POLICY.yaml"),
+            Some(PathBuf::from("lab")),
+        )
+        .unwrap();
+        assert!(eff.policy.perms("agent").can_write_raw_inputs);
+        assert!(eff.ignored.is_empty());
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn raw_inputs_for_hides_denied_subtrees() {
+        let root = std::env::temp_dir().join("hyena_overlay_raw");
+        let _ = fs::remove_dir_all(&root);
+        write(&root.join(".agent/POLICY.yaml"), ROOT_POLICY);
+        write(
+            &root.join("legal/.agent/POLICY.yaml"),
+            "policy:\n  name: hyena\nactors:\n  agent:\n    can_read_raw_inputs: false\n",
+        );
+        write(&root.join("NOTES.md"), "top");
+        write(&root.join("legal/NOTES.md"), "secret");
+        let policy_path = root.join(".agent/POLICY.yaml");
+        let base = policy::load(&policy_path).unwrap();
+
+        let count = |actor: &str| {
+            let inputs = raw_inputs_for(&root, &policy_path, &base, actor).unwrap();
+            raw::discover_raw_files(&root, None, &inputs).unwrap().len()
+        };
+        assert_eq!(count("human"), 2);
+        assert_eq!(count("agent"), 1);
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn overlay_inputs_apply_only_in_their_subtree() {
+        let root = std::env::temp_dir().join("hyena_overlay_subtree_inputs");
+        let _ = fs::remove_dir_all(&root);
+        write(
+            &root.join(".agent/POLICY.yaml"),
+            &format!("{}overlays:\n  allow_extend: true\n", ROOT_POLICY),
+        );
+        write(
+            &root.join("legal/.agent/POLICY.yaml"),
+            "policy:\n  name: hyena\nfilesystem:\n  raw_inputs:\n    patterns: [\"**/NOTES.md\"]\n    exclude: [\"**/drafts\"]\n",
+        );
+        write(
+            &root.join("lab/.agent/POLICY.yaml"),
+            "policy:\n  name: hyena\nfilesystem:\n  raw_inputs:\n    patterns: [\"**/*.txt\"]\n",
+        );
+        for rel in [
+            "a.notes.md",
+            "a.txt",
+            "drafts/NOTES.md",
+            "legal/NOTES.md",
+            "legal/b.notes.md",
+            "legal/drafts/NOTES.md",
+            "lab/c.txt",
+        ] {
+            write(&root.join(rel), "x");
+        }
+        let policy_path = root.join(".agent/POLICY.yaml");
+        let found = || -> Vec<String> {
+            let base = policy::load(&policy_path).unwrap();
+            let inputs = raw_inputs_all(&root, &policy_path, &base).unwrap();
+            raw::discover_raw_files(&root, None, &inputs)
+                .unwrap()
+                .iter()
+                .filter_map(|p| raw::relative_for_glob(p, &root))
+                .collect()
+        };
+        // legal/'s exclude and lab/'s extra pattern stay in their own subtrees.
+        assert_eq!(
+            found(),
+            vec![
+                "a.notes.md",
+                "drafts/NOTES.md",
+                "lab/c.txt",
+                "legal/NOTES.md",
+                "legal/b.notes.md"
+            ]
+        );
+        // Without allow_extend, legal/ narrows to NOTES.md and lab/ cannot add a pattern.
+        write(&policy_path, ROOT_POLICY);
+        assert_eq!(
+            found(),
+            vec!["a.notes.md", "drafts/NOTES.md", "legal/NOTES.md"]
+        );
+        fs::remove_dir_all(&root).ok();
+    }
+}
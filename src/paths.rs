@@ -0,0 +1,184 @@
+//! Path arguments: canonicalize user-supplied paths and keep them inside the repo root.
+//!
+//! `--path`, `--scope` and `--source` may be relative (to root) or absolute. Each is resolved
+//! against the canonical root; `..` or a symlink that leads outside root is an error, and with
+//! `symlinks: refuse` any symlink on the way is an error too.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Component, Path, PathBuf};
+
+/// How path arguments treat symlinks (policy `filesystem.symlinks`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymlinkPolicy {
+    /// Follow symlinks, as long as the target is still under root.
+    #[default]
+    Resolve,
+    /// Reject any path that passes through a symlink.
+    Refuse,
+}
+
+pub fn canonical_root(root: &Path) -> Result<PathBuf> {
+    root.canonicalize()
+        .with_context(|| format!("resolve root {}", root.display()))
+}
+
+/// Resolve `arg` to a canonical absolute path under `root` (already canonical). The path need not
+/// exist: its longest existing prefix is canonicalized and the rest appended, with no `..` allowed
+/// in the part that does not exist yet.
+pub fn resolve_under_root(root: &Path, arg: &Path, symlinks: SymlinkPolicy) -> Result<PathBuf> {
+    let joined = if arg.is_absolute() {
+        arg.to_path_buf()
+    } else {
+        root.join(arg)
+    };
+    if symlinks == SymlinkPolicy::Refuse {
+        refuse_symlinks(root, &joined, arg)?;
+    }
+
+    let mut existing = joined.as_path();
+    let mut missing: Vec<Component> = Vec::new();
+    let base = loop {
+        match existing.canonicalize() {
+            Ok(p) => break p,
+            Err(_) => {
+                let name = existing
+                    .components()
+                    .next_back()
+                    .with_context(|| format!("resolve {}", arg.display()))?;
+                missing.push(name);
+                existing = existing
+                    .parent()
+                    .with_context(|| format!("resolve {}", arg.display()))?;
+            }
+        }
+    };
+    let mut resolved = base;
+    for c in missing.iter().rev() {
+        match c {
+            Component::Normal(name) => resolved.push(name),
+            Component::CurDir => {}
+            _ => anyhow::bail!("path {} has '..' past its existing part", arg.display()),
+        }
+    }
+    if !resolved.starts_with(root) {
+        anyhow::bail!("path {} escapes root {}", arg.display(), root.display());
+    }
+    Ok(resolved)
+}
+
+/// Resolve `arg` under `root` and return it relative to root ("." for root itself), with forward
+/// slashes — the form stored in event `scope`/`source`.
+pub fn relative_under_root(root: &Path, arg: &Path, symlinks: SymlinkPolicy) -> Result<String> {
+    let resolved = resolve_under_root(root, arg, symlinks)?;
+    let rel = crate::raw::relative_for_glob(&resolved, root).unwrap_or_default();
+    Ok(if rel.is_empty() { ".".to_string() } else { rel })
+}
+
+/// Fail if any existing component of `joined` (walking lexically) is a symlink.
+fn refuse_symlinks(root: &Path, joined: &Path, arg: &Path) -> Result<()> {
+    let mut current = PathBuf::new();
+    for c in joined.components() {
+        current.push(c);
+        let Ok(meta) = std::fs::symlink_metadata(&current) else {
+            break;
+        };
+        // Symlinks above the root (e.g. /tmp -> /private/tmp) are the caller's business.
+        if meta.file_type().is_symlink() && !root.starts_with(&current) {
+            anyhow::bail!(
+                "path {} goes through symlink {} (policy: symlinks: refuse)",
+                arg.display(),
+                current.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Canonical temp root with `inside/` and a sibling `outside/` directory.
+    fn fixture(name: &str) -> (PathBuf, PathBuf) {
+        let base = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(base.join("root/inside")).unwrap();
+        fs::create_dir_all(base.join("outside")).unwrap();
+        let base = base.canonicalize().unwrap();
+        (base.join("root"), base)
+    }
+
+    #[test]
+    fn relative_and_missing_paths_resolve_under_root() {
+        let (root, base) = fixture("hyena_paths_ok");
+        let p = resolve_under_root(&root, Path::new("inside"), SymlinkPolicy::Resolve).unwrap();
+        assert_eq!(p, root.join("inside"));
+        let rel = relative_under_root(
+            &root,
+            Path::new("inside/new/file.md"),
+            SymlinkPolicy::Refuse,
+        )
+        .unwrap();
+        assert_eq!(rel, "inside/new/file.md");
+        assert_eq!(
+            relative_under_root(&root, Path::new("inside/.."), SymlinkPolicy::Resolve).unwrap(),
+            "."
+        );
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn dotdot_and_absolute_escapes_are_refused() {
+        let (root, base) = fixture("hyena_paths_escape");
+        for arg in [
+            PathBuf::from("../outside"),
+            PathBuf::from("inside/../../outside"),
+            PathBuf::from("../../../../etc"),
+            base.join("outside"),
+            PathBuf::from("/"),
+        ] {
+            let err = resolve_under_root(&root, &arg, SymlinkPolicy::Resolve).unwrap_err();
+            assert!(
+                err.to_string().contains("escapes root"),
+                "{}",
+                arg.display()
+            );
+        }
+        // `..` in a part that does not exist cannot be checked, so it is refused outright.
+        let err = resolve_under_root(
+            &root,
+            Path::new("nope/../../outside"),
+            SymlinkPolicy::Resolve,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("'..'"));
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_root_are_refused_in_both_modes() {
+        let (root, base) = fixture("hyena_paths_symlink_out");
+        std::os::unix::fs::symlink(base.join("outside"), root.join("link")).unwrap();
+        for mode in [SymlinkPolicy::Resolve, SymlinkPolicy::Refuse] {
+            assert!(resolve_under_root(&root, Path::new("link"), mode).is_err());
+            assert!(resolve_under_root(&root, Path::new("link/x.md"), mode).is_err());
+        }
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_inside_root_follows_policy() {
+        let (root, base) = fixture("hyena_paths_symlink_in");
+        std::os::unix::fs::symlink(root.join("inside"), root.join("alias")).unwrap();
+        let p = resolve_under_root(&root, Path::new("alias"), SymlinkPolicy::Resolve).unwrap();
+        assert_eq!(p, root.join("inside"));
+        let err = resolve_under_root(&root, Path::new("alias"), SymlinkPolicy::Refuse).unwrap_err();
+        assert!(err.to_string().contains("symlinks: refuse"));
+        fs::remove_dir_all(&base).ok();
+    }
+}
@@ -2,19 +2,75 @@
 
 #![allow(dead_code)] // fields used by serde deserialize; used as we add write/ingest
 
+use crate::formats::Format;
+use crate::paths::SymlinkPolicy;
+use crate::raw::RawInputs;
+use crate::secrets::{Scanner, SecretsPolicy};
+use crate::segment::{self, Rotation};
 use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::path::Path;
 
 const POLICY_NAME: &str = "hyena";
 
+/// Actors every policy accepts, listed or not.
+const BUILTIN_ACTORS: &[&str] = &["human", "agent"];
+
 #[derive(Debug, Deserialize)]
 pub struct Policy {
     pub policy: PolicyMeta,
     #[serde(default)]
     pub actors: Option<Actors>,
+    /// Named permission sets that actors (and other roles) inherit via `roles: [...]`.
+    #[serde(default)]
+    pub roles: Option<BTreeMap<String, ActorPerms>>,
     #[serde(default)]
     pub filesystem: Option<Filesystem>,
+    #[serde(default)]
+    pub invariants: Option<Invariants>,
+    /// Budgets enforced on `hyena run` (root policy only).
+    #[serde(default)]
+    pub limits: Option<RunLimits>,
+    /// Secret scanning on scratch and derived writes.
+    #[serde(default)]
+    pub secrets: Option<SecretsPolicy>,
+    /// How nested `.agent/POLICY.yaml` files may refine this one (root policy only).
+    #[serde(default)]
+    pub overlays: Option<OverlaySettings>,
+}
+
+/// Per-run budgets for supervised agent commands; unset means unlimited.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RunLimits {
+    #[serde(default)]
+    pub max_wall_seconds: Option<u64>,
+    /// Bytes the run may add to the scratch log (all segments).
+    #[serde(default)]
+    pub max_scratch_bytes: Option<u64>,
+    /// Entries the run may append to the derived log.
+    #[serde(default)]
+    pub max_derived_entries: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OverlaySettings {
+    /// Let subtree overlays grant more than their parent (otherwise they may only restrict).
+    #[serde(default)]
+    pub allow_extend: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Invariants {
+    #[serde(default)]
+    pub append_only: Option<AppendOnly>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AppendOnly {
+    /// Root-relative globs of files that may only grow (checked by `check-commit`).
+    #[serde(default)]
+    pub enforced_on: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -24,18 +80,26 @@ pub struct PolicyMeta {
     pub version: Option<String>,
 }
 
+/// Named actors (`human`, `agent`, `planner`, …) and their permissions.
 #[derive(Debug, Default, Deserialize)]
-pub struct Actors {
-    #[serde(default)]
-    pub human: Option<ActorPerms>,
-    #[serde(default)]
-    pub agent: Option<ActorPerms>,
-}
+#[serde(transparent)]
+pub struct Actors(pub BTreeMap<String, ActorPerms>);
 
+/// Permissions of an actor or a role. Unset flags fall through to `roles`, in listed order.
 #[derive(Debug, Default, Deserialize)]
 pub struct ActorPerms {
-    #[serde(rename = "can_write_raw_inputs", default)]
-    pub can_write_raw_inputs: bool,
+    /// Roles (keys of the top-level `roles:` map) to inherit unset flags from.
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Unset means denied (raw inputs are human-written).
+    #[serde(default)]
+    pub can_write_raw_inputs: Option<bool>,
+    /// Unset means allowed (derived logs are the agent's output channel).
+    #[serde(default)]
+    pub can_append_derived_logs: Option<bool>,
+    /// Unset means allowed; overlays set false to hide a subtree's raw notes from an actor.
+    #[serde(default)]
+    pub can_read_raw_inputs: Option<bool>,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -46,23 +110,354 @@ pub struct Filesystem {
     pub agent_scratch: Option<PathPerms>,
     #[serde(default)]
     pub derived_logs: Option<PathPerms>,
+    /// Directories (`roots`) where agents may create and edit files freely.
+    #[serde(default)]
+    pub derived_workspaces: Option<PathPerms>,
+    /// How path arguments treat symlinks: `resolve` (default) or `refuse`.
+    #[serde(default)]
+    pub symlinks: Option<SymlinkPolicy>,
 }
 
 #[derive(Debug, Default, Deserialize)]
 pub struct PathPerms {
     #[serde(default)]
-    pub patterns: Option<Vec<String>>,
+    pub patterns: Option<Vec<Pattern>>,
     #[serde(default)]
     pub roots: Option<Vec<String>>,
+    /// Globs to skip during discovery (raw_inputs), on top of .gitignore and .hyenaignore.
+    #[serde(default)]
+    pub exclude: Option<Vec<String>>,
     #[serde(default)]
     pub permissions: Option<serde_yaml::Value>,
+    /// Segment rotation for append-only logs (agent_scratch, derived_logs).
+    #[serde(default)]
+    pub rotation: Option<Rotation>,
+}
+
+/// A glob, or (for raw inputs) a glob with the format its files are parsed as.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Pattern {
+    Glob(String),
+    WithFormat { glob: String, format: Format },
+}
+
+impl Pattern {
+    pub fn glob(&self) -> &str {
+        match self {
+            Pattern::Glob(g) | Pattern::WithFormat { glob: g, .. } => g,
+        }
+    }
+}
+
+/// Globs of a `patterns` list.
+fn globs(patterns: &[Pattern]) -> Vec<String> {
+    patterns.iter().map(|p| p.glob().to_string()).collect()
+}
+
+impl Actors {
+    pub fn get(&self, actor: &str) -> Option<&ActorPerms> {
+        self.0.get(actor)
+    }
+}
+
+/// An actor's permissions after role inheritance and defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Perms {
+    pub can_write_raw_inputs: bool,
+    pub can_append_derived_logs: bool,
+    pub can_read_raw_inputs: bool,
+}
+
+impl PathPerms {
+    /// Boolean permission flag (e.g. `append`), if declared.
+    pub fn permission(&self, key: &str) -> Option<bool> {
+        self.permissions.as_ref()?.get(key)?.as_bool()
+    }
+}
+
+impl Policy {
+    /// Error if the policy lists actors and `actor` is neither one of them nor a built-in
+    /// (`human`, `agent`).
+    pub fn check_actor(&self, actor: &str) -> Result<()> {
+        if BUILTIN_ACTORS.contains(&actor) {
+            return Ok(());
+        }
+        match &self.actors {
+            Some(actors) if !actors.0.is_empty() && actors.get(actor).is_none() => {
+                let known: Vec<&str> = actors.0.keys().map(String::as_str).collect();
+                anyhow::bail!(
+                    "policy: unknown actor '{}' (known: {})",
+                    actor,
+                    known.join(", ")
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Error if an actor or role lists a role the top-level `roles:` map does not define (a typo
+    /// would otherwise fall through to the permissive defaults).
+    fn check_roles(&self) -> Result<()> {
+        let defined = |name: &str| self.roles.as_ref().is_some_and(|r| r.contains_key(name));
+        let actors = self
+            .actors
+            .iter()
+            .flat_map(|a| &a.0)
+            .map(|(n, p)| ("actor", n, p));
+        let roles = self.roles.iter().flatten().map(|(n, p)| ("role", n, p));
+        for (what, name, perms) in actors.chain(roles) {
+            if let Some(missing) = perms.roles.iter().find(|r| !defined(r)) {
+                anyhow::bail!(
+                    "policy: {} '{}' lists undefined role '{}'",
+                    what,
+                    name,
+                    missing
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// First setting of a flag for `actor`: its own, else its roles' (depth-first, in order).
+    fn actor_flag(&self, actor: &str, flag: fn(&ActorPerms) -> Option<bool>) -> Option<bool> {
+        let own = self.actors.as_ref()?.get(actor)?;
+        let roles = self.roles.as_ref();
+        let mut seen = Vec::new();
+        fn walk<'a>(
+            perms: &'a ActorPerms,
+            roles: Option<&'a BTreeMap<String, ActorPerms>>,
+            flag: fn(&ActorPerms) -> Option<bool>,
+            seen: &mut Vec<&'a str>,
+        ) -> Option<bool> {
+            if let Some(v) = flag(perms) {
+                return Some(v);
+            }
+            for name in &perms.roles {
+                if seen.contains(&name.as_str()) {
+                    continue;
+                }
+                seen.push(name);
+                if let Some(v) = roles
+                    .and_then(|r| r.get(name))
+                    .and_then(|role| walk(role, roles, flag, seen))
+                {
+                    return Some(v);
+                }
+            }
+            None
+        }
+        walk(own, roles, flag, &mut seen)
+    }
+
+    /// Resolved permissions for `actor` (role inheritance, then defaults).
+    pub fn perms(&self, actor: &str) -> Perms {
+        Perms {
+            can_write_raw_inputs: self
+                .actor_flag(actor, |p| p.can_write_raw_inputs)
+                .unwrap_or(false),
+            can_append_derived_logs: self
+                .actor_flag(actor, |p| p.can_append_derived_logs)
+                .unwrap_or(true),
+            can_read_raw_inputs: self
+                .actor_flag(actor, |p| p.can_read_raw_inputs)
+                .unwrap_or(true),
+        }
+    }
+
+    /// Raw input glob patterns (or the defaults if the policy declares none), excludes and
+    /// declared formats.
+    pub fn raw_inputs(&self) -> RawInputs {
+        let ri = self
+            .filesystem
+            .as_ref()
+            .and_then(|fs| fs.raw_inputs.as_ref());
+        let declared = ri.and_then(|ri| ri.patterns.as_deref()).unwrap_or_default();
+        let formats = declared
+            .iter()
+            .filter_map(|p| match p {
+                Pattern::WithFormat { glob, format } => Some((glob.clone(), *format)),
+                Pattern::Glob(_) => None,
+            })
+            .collect();
+        let patterns = ri
+            .and_then(|ri| ri.patterns.as_deref())
+            .map(globs)
+            .unwrap_or_else(|| {
+                crate::raw::DEFAULT_RAW_PATTERNS
+                    .iter()
+                    .map(|s| (*s).to_string())
+                    .collect()
+            });
+        let exclude = ri
+            .and_then(|ri| ri.exclude.as_ref())
+            .cloned()
+            .unwrap_or_default();
+        RawInputs {
+            patterns,
+            exclude,
+            formats,
+            subtrees: Vec::new(),
+        }
+    }
+
+    /// True if `actor` may read raw inputs: `raw_inputs.permissions.read` is not false and the
+    /// actor's `can_read_raw_inputs` is not false.
+    pub fn may_read_raw(&self, actor: &str) -> bool {
+        let inputs_allow = self
+            .filesystem
+            .as_ref()
+            .and_then(|fs| fs.raw_inputs.as_ref())
+            .and_then(|r| r.permission("read"))
+            .unwrap_or(true);
+        inputs_allow && self.perms(actor).can_read_raw_inputs
+    }
+
+    /// Overlays may grant more than their parent.
+    pub fn overlays_may_extend(&self) -> bool {
+        self.overlays.as_ref().is_some_and(|o| o.allow_extend)
+    }
+
+    /// Active log files that may only be appended to; defaults to the derived and scratch logs.
+    pub fn append_only_active(&self) -> Vec<String> {
+        self.invariants
+            .as_ref()
+            .and_then(|i| i.append_only.as_ref())
+            .map(|a| a.enforced_on.clone())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| {
+                [".notes/notes.ndjson", ".hyena/agent/scratch.ndjson"]
+                    .iter()
+                    .map(|s| (*s).to_string())
+                    .collect()
+            })
+    }
+
+    /// Files that may only be appended to: the active logs of [`Self::append_only_active`] plus
+    /// their sealed segments and segment manifests.
+    pub fn append_only(&self) -> Vec<String> {
+        let active = self.append_only_active();
+        let sealed: Vec<String> = active
+            .iter()
+            .flat_map(|a| segment::sealed_globs(a))
+            .collect();
+        active.into_iter().chain(sealed).collect()
+    }
+
+    /// Globs of agent scratch files; defaults to everything under `.hyena/agent/`.
+    pub fn scratch_patterns(&self) -> Vec<String> {
+        self.filesystem
+            .as_ref()
+            .and_then(|fs| fs.agent_scratch.as_ref())
+            .and_then(|s| s.patterns.as_deref().map(globs))
+            .unwrap_or_else(|| vec![".hyena/agent/**".to_string()])
+    }
+
+    /// Globs of derived log files; defaults to the active log, its segments and manifest.
+    pub fn derived_log_patterns(&self) -> Vec<String> {
+        self.filesystem
+            .as_ref()
+            .and_then(|fs| fs.derived_logs.as_ref())
+            .and_then(|d| d.patterns.as_deref().map(globs))
+            .unwrap_or_else(|| {
+                [".notes/notes.ndjson", ".notes/notes.*.ndjson"]
+                    .iter()
+                    .map(|s| (*s).to_string())
+                    .collect()
+            })
+    }
+
+    /// Root-relative workspace directories, without trailing slash; defaults to `.work`.
+    pub fn workspace_roots(&self) -> Vec<String> {
+        self.filesystem
+            .as_ref()
+            .and_then(|fs| fs.derived_workspaces.as_ref())
+            .and_then(|w| w.roots.clone())
+            .unwrap_or_else(|| vec![".work".to_string()])
+            .into_iter()
+            .map(|r| r.trim_end_matches('/').to_string())
+            .collect()
+    }
+
+    /// Secret scanner for agent writes: built-in detectors plus `secrets.patterns`.
+    pub fn secret_scanner(&self) -> Result<Scanner> {
+        Scanner::new(&self.secrets.clone().unwrap_or_default())
+    }
+
+    /// Symlink handling for path arguments.
+    pub fn symlinks(&self) -> SymlinkPolicy {
+        self.filesystem
+            .as_ref()
+            .and_then(|fs| fs.symlinks)
+            .unwrap_or_default()
+    }
+
+    /// Rotation settings for agent scratch.
+    pub fn scratch_rotation(&self) -> Option<&Rotation> {
+        self.filesystem
+            .as_ref()?
+            .agent_scratch
+            .as_ref()?
+            .rotation
+            .as_ref()
+    }
+
+    /// Rotation settings for derived logs.
+    pub fn derived_rotation(&self) -> Option<&Rotation> {
+        self.filesystem
+            .as_ref()?
+            .derived_logs
+            .as_ref()?
+            .rotation
+            .as_ref()
+    }
+
+    /// True if `actor` may append to derived logs: `derived_logs.permissions.append` is not
+    /// false and the actor's `can_append_derived_logs` is not false.
+    pub fn may_append_derived(&self, actor: &str) -> bool {
+        let log_allows = self
+            .filesystem
+            .as_ref()
+            .and_then(|fs| fs.derived_logs.as_ref())
+            .and_then(|d| d.permission("append"))
+            .unwrap_or(true);
+        log_allows && self.perms(actor).can_append_derived_logs
+    }
+}
+
+/// Load policy if the file exists (commands that work without a policy, e.g. write scratch).
+pub fn load_if_exists(path: &Path) -> Result<Option<Policy>> {
+    if path.exists() {
+        load(path).map(Some)
+    } else {
+        Ok(None)
+    }
 }
 
 /// Load policy from path and validate policy.name == "hyena".
 pub fn load(path: &Path) -> Result<Policy> {
+    from_value(load_value(path)?)
+}
+
+/// Parse a policy file as YAML without interpreting it (overlay merging works on this form).
+pub fn load_value(path: &Path) -> Result<serde_yaml::Value> {
     let s = std::fs::read_to_string(path)
         .with_context(|| format!("read policy: {}", path.display()))?;
-    let p: Policy = serde_yaml::from_str(&s).context("parse POLICY.yaml")?;
+    serde_yaml::from_str(&s).with_context(|| format!("parse {}", path.display()))
+}
+
+/// Interpret a policy YAML value, validate policy.name == "hyena" and that every role an actor
+/// or role lists is defined.
+pub fn from_value(v: serde_yaml::Value) -> Result<Policy> {
+    let p = parse_value(v)?;
+    p.check_roles()?;
+    Ok(p)
+}
+
+/// Like [`from_value`] but without the role check: an overlay on its own may name roles only the
+/// root policy defines (the merged result goes through [`from_value`]).
+pub fn parse_value(v: serde_yaml::Value) -> Result<Policy> {
+    let p: Policy = serde_yaml::from_value(v).context("parse POLICY.yaml")?;
     if p.policy.name != POLICY_NAME {
         anyhow::bail!(
             "POLICY.yaml policy.name must be 'hyena', got '{}'",
@@ -90,15 +485,92 @@ actors:
 "#;
         let p: Policy = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(p.policy.name, "hyena");
-        assert!(
-            p.actors
-                .as_ref()
-                .unwrap()
-                .human
-                .as_ref()
-                .unwrap()
-                .can_write_raw_inputs
+        assert!(p.perms("human").can_write_raw_inputs);
+        assert!(!p.perms("agent").can_write_raw_inputs);
+    }
+
+    #[test]
+    fn may_append_derived_checks_log_and_actor() {
+        let open: Policy = serde_yaml::from_str("policy:\n  name: hyena\n").unwrap();
+        assert!(open.may_append_derived("agent"));
+
+        let yaml = r#"
+policy:
+  name: hyena
+actors:
+  agent:
+    can_append_derived_logs: false
+filesystem:
+  derived_logs:
+    permissions:
+      append: true
+"#;
+        let p: Policy = serde_yaml::from_str(yaml).unwrap();
+        assert!(p.may_append_derived("human"));
+        assert!(!p.may_append_derived("agent"));
+
+        let closed = yaml.replace("append: true", "append: false").replace(
+            "can_append_derived_logs: false",
+            "can_write_raw_inputs: false",
         );
+        let p: Policy = serde_yaml::from_str(&closed).unwrap();
+        assert!(!p.may_append_derived("human"));
+    }
+
+    #[test]
+    fn named_actors_inherit_from_roles() {
+        let yaml = r#"
+policy:
+  name: hyena
+roles:
+  agent:
+    can_write_raw_inputs: false
+    can_append_derived_logs: true
+  readonly:
+    roles: [agent]
+    can_append_derived_logs: false
+  loop:
+    roles: [loop]
+actors:
+  human:
+    can_write_raw_inputs: true
+  coder:
+    roles: [agent]
+  reviewer:
+    roles: [readonly]
+    can_read_raw_inputs: false
+  odd:
+    roles: [loop]
+"#;
+        let p: Policy = serde_yaml::from_str(yaml).unwrap();
+        assert!(p.perms("coder").can_append_derived_logs);
+        assert!(!p.perms("coder").can_write_raw_inputs);
+        let reviewer = p.perms("reviewer");
+        assert!(!reviewer.can_append_derived_logs);
+        assert!(!reviewer.can_read_raw_inputs);
+        assert!(!p.may_append_derived("reviewer"));
+        // Cycles fall back to defaults.
+        assert!(p.perms("odd").can_append_derived_logs);
+        assert!(from_value(serde_yaml::from_str(yaml).unwrap()).is_ok());
+
+        // Undefined roles are rejected at load.
+        let typo = yaml.replace(
+            "  odd:\n    roles: [loop]",
+            "  odd:\n    roles: [loop, missing]",
+        );
+        let err = from_value(serde_yaml::from_str(&typo).unwrap()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("actor 'odd' lists undefined role 'missing'"));
+        let in_role = yaml.replace("  loop:\n    roles: [loop]", "  loop:\n    roles: [lop]");
+        let err = from_value(serde_yaml::from_str(&in_role).unwrap()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("role 'loop' lists undefined role 'lop'"));
+
+        assert!(p.check_actor("coder").is_ok());
+        let err = p.check_actor("intruder").unwrap_err().to_string();
+        assert!(err.contains("unknown actor 'intruder'"));
     }
 
     #[test]
@@ -1,14 +1,19 @@
 //! Raw inputs: discover files matching policy patterns and read their content.
 
+use crate::formats::Format;
 use anyhow::{Context, Result};
+use ignore::{WalkBuilder, WalkState};
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::sync::Mutex;
 
 /// Default patterns if policy has none (NOTES.md only).
 pub const DEFAULT_RAW_PATTERNS: &[&str] = &["**/NOTES.md"];
 
+/// Per-directory ignore file (gitignore syntax) for paths Hyena should skip but git should not.
+pub const IGNORE_FILE: &str = ".hyenaignore";
+
 /// Build a globset from pattern strings (e.g. "**/NOTES.md"). Uses forward slashes.
-fn build_globset(patterns: &[String]) -> Result<globset::GlobSet> {
+pub fn build_globset(patterns: &[String]) -> Result<globset::GlobSet> {
     let mut builder = globset::GlobSetBuilder::new();
     for p in patterns {
         builder.add(globset::Glob::new(p).with_context(|| format!("invalid pattern: {}", p))?);
@@ -17,7 +22,7 @@ fn build_globset(patterns: &[String]) -> Result<globset::GlobSet> {
 }
 
 /// Path relative to root, normalized to forward slashes for glob matching.
-fn relative_for_glob(path: &Path, root: &Path) -> Option<String> {
+pub fn relative_for_glob(path: &Path, root: &Path) -> Option<String> {
     path.strip_prefix(root).ok().map(|p| {
         p.components()
             .map(|c| c.as_os_str().to_string_lossy().into_owned())
@@ -26,24 +31,108 @@ fn relative_for_glob(path: &Path, root: &Path) -> Option<String> {
     })
 }
 
-/// Discover all files under `root` (optionally under `scope` dir) matching `patterns`.
-/// Returns paths relative to root.
+/// Which raw inputs to discover: include globs plus exclude globs (both root-relative), the
+/// format of files matching each declared glob (first match wins; Markdown otherwise), and the
+/// inputs overlays set for their own subtrees.
+#[derive(Debug, Clone, Default)]
+pub struct RawInputs {
+    pub patterns: Vec<String>,
+    pub exclude: Vec<String>,
+    pub formats: Vec<(String, Format)>,
+    /// Root-relative overlay directories and their effective inputs. A path under one is matched
+    /// against the nearest instead of the fields above.
+    pub subtrees: Vec<(String, RawInputs)>,
+}
+
+/// True if root-relative `rel` is `dir` or lies under it.
+fn within(rel: &str, dir: &str) -> bool {
+    rel.strip_prefix(dir)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+impl RawInputs {
+    /// Inputs that decide for `rel`: the nearest enclosing subtree's, or these.
+    fn nearest(&self, rel: &str) -> &RawInputs {
+        self.subtrees
+            .iter()
+            .filter(|(dir, _)| within(rel, dir))
+            .max_by_key(|(dir, _)| dir.len())
+            .map_or(self, |(_, sub)| sub)
+    }
+
+    /// Format of the root-relative raw input `rel`.
+    pub fn format_of(&self, rel: &str) -> Format {
+        self.nearest(rel)
+            .formats
+            .iter()
+            .find(|(glob, _)| {
+                globset::Glob::new(glob).is_ok_and(|g| g.compile_matcher().is_match(rel))
+            })
+            .map_or(Format::Markdown, |(_, f)| *f)
+    }
+}
+
+/// Compiled [`RawInputs`]: include and exclude sets for the top level and each subtree.
+pub struct Matcher {
+    /// (directory, include, exclude), deepest directory first; the top level ("") comes last.
+    levels: Vec<(String, globset::GlobSet, globset::GlobSet)>,
+}
+
+impl Matcher {
+    pub fn new(inputs: &RawInputs) -> Result<Matcher> {
+        let compile = |dir: &str, inputs: &RawInputs| -> Result<_> {
+            let include = if inputs.patterns.is_empty() {
+                build_globset(
+                    &DEFAULT_RAW_PATTERNS
+                        .iter()
+                        .map(|s| (*s).to_string())
+                        .collect::<Vec<_>>(),
+                )?
+            } else {
+                build_globset(&inputs.patterns)?
+            };
+            Ok((dir.to_string(), include, build_globset(&inputs.exclude)?))
+        };
+        let mut levels = inputs
+            .subtrees
+            .iter()
+            .map(|(dir, sub)| compile(dir, sub))
+            .collect::<Result<Vec<_>>>()?;
+        levels.sort_by_key(|(dir, _, _)| std::cmp::Reverse(dir.len()));
+        levels.push(compile("", inputs)?);
+        Ok(Matcher { levels })
+    }
+
+    fn level(&self, rel: &str) -> &(String, globset::GlobSet, globset::GlobSet) {
+        self.levels
+            .iter()
+            .find(|(dir, _, _)| dir.is_empty() || within(rel, dir))
+            .expect("top level matches every path")
+    }
+
+    /// True if root-relative `rel` (a file or directory) is excluded where it lies.
+    pub fn is_excluded(&self, rel: &str) -> bool {
+        !rel.is_empty() && self.level(rel).2.is_match(rel)
+    }
+
+    /// True if root-relative `rel` is a raw input where it lies.
+    pub fn is_match(&self, rel: &str) -> bool {
+        let (_, include, exclude) = self.level(rel);
+        include.is_match(rel) && !exclude.is_match(rel)
+    }
+}
+
+/// Discover all files under `root` (optionally under `scope` dir) matching `inputs.patterns`, or
+/// the patterns of the nearest overlay subtree for files under one.
+/// Honours .gitignore, .git/info/exclude, `.hyenaignore` and `inputs.exclude` (an excluded
+/// directory is not descended into). `.git/` is always skipped. Walks in parallel; the result is
+/// sorted so output is deterministic.
 pub fn discover_raw_files(
     root: &Path,
     scope: Option<&PathBuf>,
-    patterns: &[String],
+    inputs: &RawInputs,
 ) -> Result<Vec<PathBuf>> {
-    let set = if patterns.is_empty() {
-        build_globset(
-            &DEFAULT_RAW_PATTERNS
-                .iter()
-                .map(|s| (*s).to_string())
-                .collect::<Vec<_>>(),
-        )?
-    } else {
-        build_globset(patterns)?
-    };
-
+    let matcher = Matcher::new(inputs)?;
     let walk_root = scope
         .map(|s| root.join(s))
         .unwrap_or_else(|| root.to_path_buf());
@@ -51,24 +140,96 @@ pub fn discover_raw_files(
         return Ok(Vec::new());
     }
 
-    let mut out = Vec::new();
-    for entry in WalkDir::new(&walk_root)
+    let excluded =
+        |abs: &Path| relative_for_glob(abs, root).is_some_and(|rel| matcher.is_excluded(&rel));
+    let found = Mutex::new(Vec::new());
+    WalkBuilder::new(&walk_root)
+        .hidden(false)
         .follow_links(false)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        if !entry.file_type().is_file() {
-            continue;
-        }
-        let abs = entry.path();
-        let rel = match relative_for_glob(abs, root) {
-            Some(r) => r,
-            None => continue,
-        };
-        if set.is_match(&rel) {
-            out.push(abs.to_path_buf());
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .filter_entry(move |e| e.file_name() != ".git")
+        .build_parallel()
+        .run(|| {
+            Box::new(|entry| {
+                let Ok(entry) = entry else {
+                    return WalkState::Continue;
+                };
+                let abs = entry.path();
+                if excluded(abs) {
+                    return WalkState::Skip;
+                }
+                if entry.file_type().is_some_and(|t| t.is_file())
+                    && relative_for_glob(abs, root).is_some_and(|rel| matcher.is_match(&rel))
+                {
+                    found.lock().unwrap().push(abs.to_path_buf());
+                }
+                WalkState::Continue
+            })
+        });
+    let mut out = found.into_inner().unwrap();
+    out.sort();
+    Ok(out)
+}
+
+/// Directory each include pattern is anchored at: its leading components without glob syntax,
+/// less the file name if the whole pattern is literal. Absolute, deduplicated; subtrees included.
+pub fn pattern_bases(root: &Path, inputs: &RawInputs) -> Vec<PathBuf> {
+    let mut patterns: Vec<&str> = if inputs.patterns.is_empty() {
+        DEFAULT_RAW_PATTERNS.to_vec()
+    } else {
+        inputs.patterns.iter().map(String::as_str).collect()
+    };
+    for (_, sub) in &inputs.subtrees {
+        patterns.extend(sub.patterns.iter().map(String::as_str));
+    }
+    let mut out: Vec<PathBuf> = Vec::new();
+    for p in patterns {
+        let parts: Vec<&str> = p.split('/').collect();
+        let literal = parts
+            .iter()
+            .take_while(|c| !c.contains(['*', '?', '[', '{']))
+            .count()
+            .min(parts.len() - 1);
+        let base = parts[..literal]
+            .iter()
+            .fold(root.to_path_buf(), |b, c| b.join(c));
+        if !out.contains(&base) {
+            out.push(base);
         }
     }
+    out
+}
+
+/// Directories discovery walks into under `dir` (`dir` included), down to `max_depth` levels:
+/// not ignored, not excluded by `inputs` where they lie, not `.git`. Sorted.
+pub fn walked_dirs(
+    root: &Path,
+    dir: &Path,
+    inputs: &RawInputs,
+    max_depth: Option<usize>,
+) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let matcher = Matcher::new(inputs)?;
+    let root_buf = root.to_path_buf();
+    let mut out: Vec<PathBuf> = WalkBuilder::new(dir)
+        .hidden(false)
+        .follow_links(false)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .max_depth(max_depth)
+        .filter_entry(move |e| {
+            e.file_name() != ".git"
+                && !relative_for_glob(e.path(), &root_buf)
+                    .is_some_and(|rel| matcher.is_excluded(&rel))
+        })
+        .build()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_some_and(|t| t.is_dir()))
+        .map(ignore::DirEntry::into_path)
+        .collect();
     out.sort();
     Ok(out)
 }
@@ -103,7 +264,15 @@ mod tests {
         fs::write(root.join("a/b/other.txt"), "x").unwrap();
 
         let patterns = vec!["**/NOTES.md".to_string()];
-        let paths = discover_raw_files(&root, None, &patterns).unwrap();
+        let paths = discover_raw_files(
+            &root,
+            None,
+            &RawInputs {
+                patterns,
+                ..Default::default()
+            },
+        )
+        .unwrap();
         assert_eq!(paths.len(), 2);
         assert!(paths
             .iter()
@@ -124,13 +293,46 @@ mod tests {
         fs::write(root.join("sub/dir/NOTES.md"), "dir").unwrap();
 
         let patterns = vec!["**/NOTES.md".to_string()];
-        let paths = discover_raw_files(&root, Some(&PathBuf::from("sub")), &patterns).unwrap();
+        let paths = discover_raw_files(
+            &root,
+            Some(&PathBuf::from("sub")),
+            &RawInputs {
+                patterns,
+                ..Default::default()
+            },
+        )
+        .unwrap();
         assert_eq!(paths.len(), 2); // sub and sub/dir
-        assert!(paths.iter().all(|p| p.starts_with(&root.join("sub"))));
+        assert!(paths.iter().all(|p| p.starts_with(root.join("sub"))));
 
         fs::remove_dir_all(&root).unwrap();
     }
 
+    #[test]
+    fn walked_dirs_skip_ignored_and_excluded_trees() {
+        let root = std::env::temp_dir().join("hyena_raw_walked_dirs");
+        let _ = fs::remove_dir_all(&root);
+        for d in ["notes/a", "target/debug", "old", ".git/objects"] {
+            fs::create_dir_all(root.join(d)).unwrap();
+        }
+        fs::write(root.join(".hyenaignore"), "target/\n").unwrap();
+        let inputs = RawInputs {
+            patterns: vec!["notes/**/*.md".to_string(), "TODO.md".to_string()],
+            exclude: vec!["old".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            pattern_bases(&root, &inputs),
+            vec![root.join("notes"), root.clone()]
+        );
+        let dirs = walked_dirs(&root, &root, &inputs, None).unwrap();
+        assert_eq!(
+            dirs,
+            vec![root.clone(), root.join("notes"), root.join("notes/a")]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
     #[test]
     fn read_raw_content_formats_path_and_body() {
         let root = std::env::temp_dir().join("hyena_raw_content");
@@ -151,8 +353,42 @@ mod tests {
         let root = std::env::temp_dir().join("hyena_raw_default");
         fs::create_dir_all(&root).unwrap();
         fs::write(root.join("NOTES.md"), "x").unwrap();
-        let paths = discover_raw_files(&root, None, &[]).unwrap();
+        let paths = discover_raw_files(&root, None, &RawInputs::default()).unwrap();
         assert_eq!(paths.len(), 1);
         fs::remove_dir_all(&root).unwrap();
     }
+
+    #[test]
+    fn discover_skips_ignored_and_excluded() {
+        let root = std::env::temp_dir().join("hyena_raw_ignore");
+        let _ = fs::remove_dir_all(&root);
+        for d in ["keep", "target/debug", "vendor/lib", "archive", ".git"] {
+            fs::create_dir_all(root.join(d)).unwrap();
+        }
+        for f in [
+            "NOTES.md",
+            "keep/NOTES.md",
+            "target/debug/NOTES.md",
+            "vendor/lib/NOTES.md",
+            "archive/NOTES.md",
+            ".git/NOTES.md",
+        ] {
+            fs::write(root.join(f), "x").unwrap();
+        }
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::write(root.join(".hyenaignore"), "vendor\n").unwrap();
+
+        let inputs = RawInputs {
+            patterns: vec!["**/NOTES.md".to_string()],
+            exclude: vec!["archive".to_string()],
+            ..Default::default()
+        };
+        let rels: Vec<String> = discover_raw_files(&root, None, &inputs)
+            .unwrap()
+            .iter()
+            .filter_map(|p| relative_for_glob(p, &root))
+            .collect();
+        assert_eq!(rels, vec!["NOTES.md", "keep/NOTES.md"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -0,0 +1,288 @@
+//! Supervised agent runs (`hyena run -- cmd`): launch a command as an actor and enforce policy
+//! budgets (wall clock, scratch bytes, derived entries), killing it when one is exceeded.
+//!
+//! Usage is measured on the logs themselves: bytes and lines added since launch across sealed
+//! segments and the active file (sealing keeps bytes, so rotation mid-run is counted correctly).
+//!
+//! On Unix the command runs in its own process group and a kill takes the whole group, so
+//! processes it started in the background stop too.
+
+use crate::policy::RunLimits;
+use crate::segment;
+use anyhow::{Context, Result};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// How the supervised process ended.
+#[derive(Debug)]
+pub enum Outcome {
+    Exited(ExitStatus),
+    /// Killed by the supervisor; the limit that was exceeded.
+    Killed(String),
+}
+
+#[derive(Debug)]
+pub struct RunReport {
+    pub outcome: Outcome,
+    pub duration: Duration,
+    pub scratch_bytes: u64,
+    pub derived_entries: usize,
+}
+
+/// Total bytes of a log (sealed segments + active file).
+fn log_bytes(active: &Path) -> Result<u64> {
+    let mut total = 0;
+    for f in segment::log_files(active)? {
+        total += std::fs::metadata(&f)
+            .with_context(|| format!("stat {}", f.display()))?
+            .len();
+    }
+    Ok(total)
+}
+
+/// Lines in a log past its first `offset` bytes (reads only the new part).
+fn lines_after(active: &Path, offset: u64) -> Result<usize> {
+    let mut skip = offset;
+    let mut lines = 0;
+    for f in segment::log_files(active)? {
+        let len = std::fs::metadata(&f)?.len();
+        if skip >= len {
+            skip -= len;
+            continue;
+        }
+        let mut file = std::fs::File::open(&f).with_context(|| format!("open {}", f.display()))?;
+        file.seek(SeekFrom::Start(skip))?;
+        skip = 0;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        lines += buf.iter().filter(|b| **b == b'\n').count();
+    }
+    Ok(lines)
+}
+
+/// Kill the child and, on Unix, every process in its group.
+fn kill_group(child: &mut Child) {
+    #[cfg(unix)]
+    // SAFETY: kill(2) has no memory effects; a negative pid names the child's process group.
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.kill();
+}
+
+/// Run `command` with extra `env`, polling every `poll` until it exits, breaks a limit or
+/// `interrupt` is set.
+pub fn supervise(
+    scratch_log: &Path,
+    derived_log: &Path,
+    command: &[String],
+    env: &[(&str, &str)],
+    limits: &RunLimits,
+    poll: Duration,
+    interrupt: &AtomicBool,
+) -> Result<RunReport> {
+    let (program, args) = command.split_first().context("run: no command given")?;
+    let scratch_start = log_bytes(scratch_log)?;
+    let derived_start = log_bytes(derived_log)?;
+    let started = Instant::now();
+    let mut cmd = Command::new(program);
+    cmd.args(args).envs(env.iter().copied());
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("run: start {}", program))?;
+
+    let usage = || -> Result<(u64, usize)> {
+        Ok((
+            log_bytes(scratch_log)?.saturating_sub(scratch_start),
+            lines_after(derived_log, derived_start)?,
+        ))
+    };
+    loop {
+        if let Some(status) = child.try_wait().context("run: wait")? {
+            let (scratch_bytes, derived_entries) = usage()?;
+            return Ok(RunReport {
+                outcome: Outcome::Exited(status),
+                duration: started.elapsed(),
+                scratch_bytes,
+                derived_entries,
+            });
+        }
+        let (scratch_bytes, derived_entries) = usage()?;
+        let elapsed = started.elapsed();
+        let exceeded = if interrupt.load(Ordering::SeqCst) {
+            Some("interrupted".to_string())
+        } else if let Some(max) = limits
+            .max_wall_seconds
+            .filter(|s| elapsed > Duration::from_secs(*s))
+        {
+            Some(format!("max_wall_seconds ({}s)", max))
+        } else if let Some(max) = limits.max_scratch_bytes.filter(|m| scratch_bytes > *m) {
+            Some(format!("max_scratch_bytes ({} > {})", scratch_bytes, max))
+        } else {
+            limits
+                .max_derived_entries
+                .filter(|m| derived_entries > *m)
+                .map(|max| format!("max_derived_entries ({} > {})", derived_entries, max))
+        };
+        if let Some(reason) = exceeded {
+            // The process may have exited in the meantime; either way it is gone after wait.
+            kill_group(&mut child);
+            child.wait().context("run: wait after kill")?;
+            return Ok(RunReport {
+                outcome: Outcome::Killed(reason),
+                duration: elapsed,
+                scratch_bytes,
+                derived_entries,
+            });
+        }
+        std::thread::sleep(poll);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const POLL: Duration = Duration::from_millis(20);
+    static NO_INTERRUPT: AtomicBool = AtomicBool::new(false);
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".into(), "-c".into(), script.into()]
+    }
+
+    #[test]
+    fn reports_exit_code_and_usage() {
+        let dir = std::env::temp_dir().join("hyena_run_exit");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (scratch, derived) = (dir.join("scratch.ndjson"), dir.join("notes.ndjson"));
+        fs::write(&derived, "{\"old\":1}\n").unwrap();
+
+        let script = format!(
+            "printf 'abc\\n' >> {s}; printf '{{}}\\n{{}}\\n' >> {d}; exit 3",
+            s = scratch.display(),
+            d = derived.display()
+        );
+        let report = supervise(
+            &scratch,
+            &derived,
+            &sh(&script),
+            &[],
+            &RunLimits::default(),
+            POLL,
+            &NO_INTERRUPT,
+        )
+        .unwrap();
+        match report.outcome {
+            Outcome::Exited(status) => assert_eq!(status.code(), Some(3)),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(report.scratch_bytes, 4);
+        assert_eq!(report.derived_entries, 2);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn kills_on_wall_clock_and_derived_budget() {
+        let dir = std::env::temp_dir().join("hyena_run_kill");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (scratch, derived) = (dir.join("scratch.ndjson"), dir.join("notes.ndjson"));
+
+        let slow = RunLimits {
+            max_wall_seconds: Some(0),
+            ..Default::default()
+        };
+        let report = supervise(
+            &scratch,
+            &derived,
+            &sh("sleep 5"),
+            &[],
+            &slow,
+            POLL,
+            &NO_INTERRUPT,
+        )
+        .unwrap();
+        assert!(matches!(report.outcome, Outcome::Killed(ref r) if r.starts_with("max_wall")));
+        assert!(report.duration < Duration::from_secs(4));
+
+        let chatty = RunLimits {
+            max_derived_entries: Some(1),
+            ..Default::default()
+        };
+        let script = format!(
+            "while true; do echo '{{}}' >> {}; sleep 0.01; done",
+            derived.display()
+        );
+        let report = supervise(
+            &scratch,
+            &derived,
+            &sh(&script),
+            &[],
+            &chatty,
+            POLL,
+            &NO_INTERRUPT,
+        )
+        .unwrap();
+        assert!(matches!(report.outcome, Outcome::Killed(ref r) if r.starts_with("max_derived")));
+
+        // Background processes the command started die with it.
+        let late = dir.join("late");
+        let script = format!("(sleep 0.3; echo late > {}) & sleep 5", late.display());
+        let report = supervise(
+            &scratch,
+            &derived,
+            &sh(&script),
+            &[],
+            &slow,
+            POLL,
+            &NO_INTERRUPT,
+        )
+        .unwrap();
+        assert!(matches!(report.outcome, Outcome::Killed(_)));
+        std::thread::sleep(Duration::from_millis(600));
+        #[cfg(unix)]
+        assert!(!late.exists(), "grandchild survived the kill");
+
+        let interrupted = AtomicBool::new(true);
+        let report = supervise(
+            &scratch,
+            &derived,
+            &sh("sleep 5"),
+            &[],
+            &RunLimits::default(),
+            POLL,
+            &interrupted,
+        )
+        .unwrap();
+        assert!(matches!(report.outcome, Outcome::Killed(ref r) if r == "interrupted"));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn passes_env_to_child() {
+        let dir = std::env::temp_dir().join("hyena_run_env");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (scratch, derived) = (dir.join("scratch.ndjson"), dir.join("notes.ndjson"));
+        let script = format!("printf \"$HYENA_ACTOR\" >> {}", scratch.display());
+        supervise(
+            &scratch,
+            &derived,
+            &sh(&script),
+            &[("HYENA_ACTOR", "coder")],
+            &RunLimits::default(),
+            POLL,
+            &NO_INTERRUPT,
+        )
+        .unwrap();
+        assert_eq!(fs::read_to_string(&scratch).unwrap(), "coder");
+        fs::remove_dir_all(&dir).ok();
+    }
+}
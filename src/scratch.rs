@@ -1,11 +1,13 @@
-//! Scratch log: append and read `.hyena/agent/scratch.ndjson` (one JSON object per line).
+//! Scratch log: append and read `.hyena/agent/scratch.ndjson` (one typed event per line).
 
-use anyhow::{Context, Result};
-use chrono::Utc;
-use serde::Serialize;
-use std::fs::OpenOptions;
-use std::io::{BufRead, BufReader, Write};
+use crate::clock;
+use crate::event::{self, Event};
+use crate::secrets::Scanner;
+use crate::{segment, thread};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
 use std::path::Path;
+use std::time::Duration;
 
 const SCRATCH_REL: &str = ".hyena/agent/scratch.ndjson";
 
@@ -14,62 +16,137 @@ pub fn scratch_path(root: &Path) -> std::path::PathBuf {
     root.join(SCRATCH_REL)
 }
 
-/// One line in scratch.ndjson.
-#[derive(Debug, Serialize)]
-pub struct ScratchEntry {
-    pub ts: String,
-    pub actor: String,
-    pub kind: String,
-    pub text: String,
+/// Append one entry to scratch.ndjson after `scanner` (the policy's, or the built-in
+/// detectors without one) redacts or rejects secrets in it. Creates parent dirs if needed.
+/// Returns the entry as written: its `redactions` list what was redacted.
+pub fn append_scratch(root: &Path, entry: &Event, scanner: &Scanner) -> Result<Event> {
+    let mut entry = entry.clone();
+    scanner.apply(&mut entry)?;
+    event::append_event(&scratch_path(root), &entry)?;
+    Ok(entry)
 }
 
-/// Append one entry to scratch.ndjson. Creates parent dirs if needed.
-pub fn append_scratch(root: &Path, actor: &str, kind: &str, text: &str) -> Result<()> {
-    let path = scratch_path(root);
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent).with_context(|| format!("create {}", parent.display()))?;
-    }
-    let ts = Utc::now().to_rfc3339();
-    let entry = ScratchEntry {
-        ts,
-        actor: actor.to_string(),
-        kind: kind.to_string(),
-        text: text.to_string(),
-    };
-    let line = serde_json::to_string(&entry).context("serialize scratch entry")?;
-    let mut f = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&path)
-        .with_context(|| format!("open {}", path.display()))?;
-    writeln!(f, "{}", line).with_context(|| format!("write {}", path.display()))?;
-    Ok(())
+/// Parse every scratch line (all segments) into events, skipping lines that are not events.
+pub fn scratch_events(root: &Path) -> Result<Vec<Event>> {
+    Ok(segment::read_lines(&scratch_path(root))?
+        .iter()
+        .filter_map(|l| Event::parse(l))
+        .collect())
 }
 
-/// Read scratch lines, optionally limited to `max`. Returns concatenated output (each line is a JSON object).
-pub fn read_scratch(root: &Path, max: Option<usize>) -> Result<String> {
-    let path = scratch_path(root);
-    if !path.is_file() {
-        return Ok(String::new());
+/// Render the conversation tree containing entry `id`.
+pub fn read_thread(root: &Path, id: &str) -> Result<String> {
+    let events = scratch_events(root)?;
+    thread::render_thread(&events, id)
+        .ok_or_else(|| anyhow::anyhow!("no scratch entry with id {}", id))
+}
+
+/// Which scratch entries to show. An empty filter keeps every non-blank line.
+#[derive(Debug, Default)]
+pub struct ScratchFilter {
+    pub actor: Option<String>,
+    pub kind: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl ScratchFilter {
+    fn is_empty(&self) -> bool {
+        self.actor.is_none() && self.kind.is_none() && self.since.is_none()
     }
-    let f = std::fs::File::open(&path).with_context(|| format!("read {}", path.display()))?;
-    let reader = BufReader::new(f);
-    let mut lines: Vec<String> = reader
-        .lines()
-        .map_while(Result::ok)
-        .filter(|s| !s.trim().is_empty())
+
+    /// True if the line passes every set filter. Lines that are not events only pass an empty filter.
+    pub fn matches(&self, line: &str) -> bool {
+        if line.trim().is_empty() {
+            return false;
+        }
+        if self.is_empty() {
+            return true;
+        }
+        let Some(e) = Event::parse(line) else {
+            return false;
+        };
+        if self.actor.as_deref().is_some_and(|a| a != e.actor) {
+            return false;
+        }
+        if self.kind.as_deref().is_some_and(|k| k != e.kind()) {
+            return false;
+        }
+        if let Some(since) = self.since {
+            match DateTime::parse_from_rfc3339(&e.ts) {
+                Ok(ts) if ts >= since => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Parse `--since`: RFC 3339 timestamp or a plain `YYYY-MM-DD` date (midnight UTC).
+pub fn parse_since(s: &str) -> Result<DateTime<Utc>> {
+    clock::parse_instant(s, "--since")
+}
+
+fn join_lines(lines: &[String]) -> String {
+    lines.join("\n") + if lines.is_empty() { "" } else { "\n" }
+}
+
+/// Read scratch lines matching `filter`, optionally limited to the oldest `max`.
+/// Sealed segments and the active file are read as one log.
+/// Returns concatenated output (each line is a JSON object).
+pub fn read_scratch(root: &Path, max: Option<usize>, filter: &ScratchFilter) -> Result<String> {
+    let mut lines: Vec<String> = segment::read_lines(&scratch_path(root))?
+        .into_iter()
+        .filter(|s| filter.matches(s))
         .collect();
     if let Some(n) = max {
         lines.truncate(n);
     }
-    Ok(lines.join("\n") + if lines.is_empty() { "" } else { "\n" })
+    Ok(join_lines(&lines))
+}
+
+/// Newest `n` lines matching `filter`, oldest first. Reads backwards from the end of the log,
+/// so cost is proportional to what is returned, not to the log size.
+pub fn tail_scratch(root: &Path, n: usize, filter: &ScratchFilter) -> Result<String> {
+    if n == 0 {
+        return Ok(String::new());
+    }
+    let found = segment::tail_lines(&scratch_path(root), n, |l| filter.matches(l))?;
+    Ok(join_lines(&found))
+}
+
+/// Stream lines appended to scratch after this call, calling `on_line` for each match until it
+/// returns false. Polls every `poll` (see [`segment::Follower`] for how rotation is handled).
+pub fn follow_scratch(
+    root: &Path,
+    filter: &ScratchFilter,
+    poll: Duration,
+    mut on_line: impl FnMut(&str) -> bool,
+) -> Result<()> {
+    let mut follower = segment::Follower::new(&scratch_path(root))?;
+    loop {
+        for line in follower.poll()? {
+            if filter.matches(&line) && !on_line(&line) {
+                return Ok(());
+            }
+        }
+        std::thread::sleep(poll);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::event::Body;
     use std::fs;
 
+    fn entry(actor: &str, kind: &str, text: &str) -> Event {
+        Event::new(
+            chrono::Utc::now().to_rfc3339(),
+            actor,
+            Body::from_kind_text(kind, text),
+        )
+    }
+
     #[test]
     fn append_and_read_roundtrip() {
         let root = std::env::temp_dir().join("hyena_scratch_roundtrip");
@@ -78,16 +155,26 @@ mod tests {
         let _ = fs::remove_file(&path);
         let _ = fs::remove_dir_all(root.join(".hyena"));
 
-        append_scratch(&root, "human", "note", "hello world").unwrap();
-        append_scratch(&root, "agent", "thought", "second line").unwrap();
+        append_scratch(
+            &root,
+            &entry("human", "note", "hello world"),
+            &Scanner::default(),
+        )
+        .unwrap();
+        append_scratch(
+            &root,
+            &entry("agent", "thought", "second line"),
+            &Scanner::default(),
+        )
+        .unwrap();
 
-        let out = read_scratch(&root, None).unwrap();
+        let out = read_scratch(&root, None, &ScratchFilter::default()).unwrap();
         assert!(out.contains("hello world"));
         assert!(out.contains("second line"));
         assert!(out.contains("\"actor\":\"human\""));
         assert!(out.contains("\"actor\":\"agent\""));
 
-        let limited = read_scratch(&root, Some(1)).unwrap();
+        let limited = read_scratch(&root, Some(1), &ScratchFilter::default()).unwrap();
         let line_count = limited.lines().filter(|s| !s.is_empty()).count();
         assert_eq!(line_count, 1);
 
@@ -100,7 +187,7 @@ mod tests {
     fn read_scratch_missing_returns_empty() {
         let root = std::env::temp_dir().join("hyena_scratch_missing");
         fs::create_dir_all(&root).unwrap();
-        let out = read_scratch(&root, None).unwrap();
+        let out = read_scratch(&root, None, &ScratchFilter::default()).unwrap();
         assert!(out.is_empty());
         fs::remove_dir(&root).ok();
     }
@@ -109,12 +196,276 @@ mod tests {
     fn scratch_entry_has_ts_and_kind() {
         let root = std::env::temp_dir().join("hyena_scratch_ts");
         fs::create_dir_all(&root).unwrap();
-        append_scratch(&root, "agent", "thought", "x").unwrap();
-        let out = read_scratch(&root, Some(1)).unwrap();
+        append_scratch(&root, &entry("agent", "thought", "x"), &Scanner::default()).unwrap();
+        let out = read_scratch(&root, Some(1), &ScratchFilter::default()).unwrap();
         assert!(out.contains("\"ts\":"));
         assert!(out.contains("\"kind\":\"thought\""));
         fs::remove_file(scratch_path(&root)).ok();
         fs::remove_dir_all(root.join(".hyena")).ok();
         fs::remove_dir_all(&root).ok();
     }
+
+    #[test]
+    fn read_thread_follows_replies() {
+        let root = std::env::temp_dir().join("hyena_scratch_thread");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let q = append_scratch(
+            &root,
+            &entry("planner", "question", "which parser?"),
+            &Scanner::default(),
+        )
+        .unwrap()
+        .id
+        .unwrap();
+        append_scratch(
+            &root,
+            &entry("agent", "note", "unrelated"),
+            &Scanner::default(),
+        )
+        .unwrap();
+        let reply = entry("coder", "note", "pulldown")
+            .with_links(Some(&q), None, &[])
+            .unwrap();
+        let r = append_scratch(&root, &reply, &Scanner::default())
+            .unwrap()
+            .id
+            .unwrap();
+
+        let out = read_thread(&root, &r).unwrap();
+        assert!(out.starts_with(&q));
+        assert!(out.contains("  ") && out.contains("pulldown"));
+        assert!(!out.contains("unrelated"));
+        assert!(read_thread(&root, "01ARZ3NDEKTSV4RRFFQ69G5FAV").is_err());
+        fs::remove_dir_all(&root).ok();
+    }
+
+    fn entry_at(actor: &str, kind: &str, text: &str, ts: &str) -> Event {
+        Event::new(ts.to_string(), actor, Body::from_kind_text(kind, text))
+    }
+
+    #[test]
+    fn tail_returns_newest_matching() {
+        let root = std::env::temp_dir().join("hyena_scratch_tail");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        // Enough lines to span several tail blocks.
+        for i in 0..400 {
+            let actor = if i % 2 == 0 { "planner" } else { "coder" };
+            let text = format!("entry {:03} {}", i, "x".repeat(40));
+            append_scratch(&root, &entry(actor, "note", &text), &Scanner::default()).unwrap();
+        }
+        let all = ScratchFilter::default();
+        let out = tail_scratch(&root, 3, &all).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("entry 397"));
+        assert!(lines[2].contains("entry 399"));
+
+        let planner = ScratchFilter {
+            actor: Some("planner".into()),
+            ..Default::default()
+        };
+        let out = tail_scratch(&root, 2, &planner).unwrap();
+        assert!(out.contains("entry 396") && out.contains("entry 398"));
+        assert!(!out.contains("coder"));
+
+        let whole = tail_scratch(&root, 1000, &all).unwrap();
+        assert_eq!(whole.lines().count(), 400);
+        assert!(whole.starts_with(r#"{"schema_version""#));
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn filter_by_kind_and_since() {
+        let root = std::env::temp_dir().join("hyena_scratch_filter");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        append_scratch(
+            &root,
+            &entry_at("a", "note", "old", "2025-01-01T00:00:00Z"),
+            &Scanner::default(),
+        )
+        .unwrap();
+        append_scratch(
+            &root,
+            &entry_at("a", "question", "new q", "2025-03-01T00:00:00Z"),
+            &Scanner::default(),
+        )
+        .unwrap();
+        append_scratch(
+            &root,
+            &entry_at("a", "note", "new n", "2025-03-02T00:00:00Z"),
+            &Scanner::default(),
+        )
+        .unwrap();
+
+        let f = ScratchFilter {
+            since: Some(parse_since("2025-02-01").unwrap()),
+            ..Default::default()
+        };
+        let out = read_scratch(&root, None, &f).unwrap();
+        assert!(!out.contains("old") && out.contains("new q") && out.contains("new n"));
+
+        let f = ScratchFilter {
+            kind: Some("question".into()),
+            since: Some(parse_since("2025-02-01T00:00:00+00:00").unwrap()),
+            ..Default::default()
+        };
+        let out = read_scratch(&root, None, &f).unwrap();
+        assert_eq!(out.lines().count(), 1);
+        assert!(parse_since("last week").is_err());
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn follow_streams_new_lines() {
+        let root = std::env::temp_dir().join("hyena_scratch_follow");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        append_scratch(
+            &root,
+            &entry("a", "note", "before follow"),
+            &Scanner::default(),
+        )
+        .unwrap();
+
+        let writer_root = root.clone();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            append_scratch(
+                &writer_root,
+                &entry("b", "note", "skip me"),
+                &Scanner::default(),
+            )
+            .unwrap();
+            append_scratch(
+                &writer_root,
+                &entry("a", "note", "after follow"),
+                &Scanner::default(),
+            )
+            .unwrap();
+        });
+        let f = ScratchFilter {
+            actor: Some("a".into()),
+            ..Default::default()
+        };
+        let mut seen = Vec::new();
+        follow_scratch(&root, &f, Duration::from_millis(10), |line| {
+            seen.push(line.to_string());
+            false
+        })
+        .unwrap();
+        writer.join().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert!(seen[0].contains("after follow"));
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn readers_span_sealed_segments() {
+        let root = std::env::temp_dir().join("hyena_scratch_segments");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let first = append_scratch(
+            &root,
+            &entry("a", "note", "sealed one"),
+            &Scanner::default(),
+        )
+        .unwrap()
+        .id
+        .unwrap();
+        segment::maybe_rotate(
+            &scratch_path(&root),
+            &segment::Rotation {
+                max_bytes: Some(1),
+                max_age_hours: None,
+            },
+            chrono::Utc::now(),
+        )
+        .unwrap();
+        append_scratch(
+            &root,
+            &entry("a", "note", "active one"),
+            &Scanner::default(),
+        )
+        .unwrap();
+
+        let all = read_scratch(&root, None, &ScratchFilter::default()).unwrap();
+        let lines: Vec<&str> = all.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("sealed one") && lines[1].contains("active one"));
+        let tail = tail_scratch(&root, 2, &ScratchFilter::default()).unwrap();
+        assert_eq!(tail, all);
+        assert!(read_thread(&root, &first).unwrap().contains("sealed one"));
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn follow_continues_across_seal() {
+        let root = std::env::temp_dir().join("hyena_scratch_follow_seal");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        append_scratch(&root, &entry("a", "note", "before"), &Scanner::default()).unwrap();
+
+        let writer_root = root.clone();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            append_scratch(
+                &writer_root,
+                &entry("a", "note", "late in old file"),
+                &Scanner::default(),
+            )
+            .unwrap();
+            segment::maybe_rotate(
+                &scratch_path(&writer_root),
+                &segment::Rotation {
+                    max_bytes: Some(1),
+                    max_age_hours: None,
+                },
+                chrono::Utc::now(),
+            )
+            .unwrap();
+            append_scratch(
+                &writer_root,
+                &entry("a", "note", "first in new file"),
+                &Scanner::default(),
+            )
+            .unwrap();
+        });
+        let mut seen = Vec::new();
+        follow_scratch(
+            &root,
+            &ScratchFilter::default(),
+            Duration::from_millis(10),
+            |line| {
+                seen.push(line.to_string());
+                seen.len() < 2
+            },
+        )
+        .unwrap();
+        writer.join().unwrap();
+        assert!(seen[0].contains("late in old file"), "{:?}", seen);
+        assert!(seen[1].contains("first in new file"), "{:?}", seen);
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn scratch_entry_is_versioned_event() {
+        let root = std::env::temp_dir().join("hyena_scratch_event");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        append_scratch(
+            &root,
+            &entry("agent", "question", "why?"),
+            &Scanner::default(),
+        )
+        .unwrap();
+        let out = read_scratch(&root, None, &ScratchFilter::default()).unwrap();
+        let e = Event::parse(out.lines().next().unwrap()).unwrap();
+        assert_eq!(e.schema_version, event::SCHEMA_VERSION);
+        assert_eq!(e.kind(), "question");
+        assert_eq!(e.text(), Some("why?"));
+        fs::remove_dir_all(&root).ok();
+    }
 }
@@ -1,10 +1,9 @@
 //! Line-scan search over .notes/notes.ndjson and optionally .hyena/agent/scratch.ndjson.
 
+use crate::{derived, scratch, segment};
 use anyhow::Result;
 use std::path::Path;
 
-const DERIVED_LOG: &str = ".notes/notes.ndjson";
-
 fn scan_file(path: &Path, query: &str, out: &mut Vec<String>) -> Result<()> {
     if !path.is_file() {
         return Ok(());
@@ -22,14 +21,18 @@ fn scan_file(path: &Path, query: &str, out: &mut Vec<String>) -> Result<()> {
     Ok(())
 }
 
-/// Search derived log (and optionally scratch) for lines containing `query`. Returns matching lines.
+/// Search derived log (and optionally scratch), including sealed segments, for lines containing
+/// `query`. Returns matching lines.
 pub fn search(root: &Path, query: &str, include_scratch: bool) -> Result<Vec<String>> {
     let mut out = Vec::new();
-    let derived = root.join(DERIVED_LOG);
-    scan_file(&derived, query, &mut out)?;
+    let mut logs = vec![derived::derived_path(root)];
     if include_scratch {
-        let scratch = root.join(".hyena/agent/scratch.ndjson");
-        scan_file(&scratch, query, &mut out)?;
+        logs.push(scratch::scratch_path(root));
+    }
+    for log in logs {
+        for path in segment::log_files(&log)? {
+            scan_file(&path, query, &mut out)?;
+        }
     }
     Ok(out)
 }
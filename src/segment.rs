@@ -0,0 +1,538 @@
+//! Log segments: one logical append-only log stored as sealed segment files plus an active file.
+//!
+//! `scratch.ndjson` is always the active file. Sealing moves it to the next
+//! `scratch.NNNNNN.ndjson` and appends the segment's SHA-256 to `scratch.segments.ndjson`.
+//! Sealed segments are never modified, so rotation keeps `invariants.append_only`. Appends and
+//! seals hold the log's lock file (`scratch.ndjson.lock`) throughout, so no line can land in a
+//! file that is being sealed.
+
+use crate::digest;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Bytes read per step when tailing backwards.
+const TAIL_BLOCK: usize = 8192;
+
+/// When to seal the active file (policy `rotation:` under a log's filesystem entry).
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Rotation {
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+    #[serde(default)]
+    pub max_age_hours: Option<u64>,
+}
+
+/// One line in the segment manifest.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SealedSegment {
+    pub file: String,
+    pub sha256: String,
+    pub bytes: u64,
+    pub lines: u64,
+    pub sealed_at: String,
+}
+
+fn stem_ext(active: &Path) -> (String, String) {
+    let stem = active
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let ext = active
+        .extension()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    (stem, ext)
+}
+
+/// Manifest path for a log, e.g. `scratch.segments.ndjson`.
+pub fn manifest_path(active: &Path) -> PathBuf {
+    let (stem, ext) = stem_ext(active);
+    active.with_file_name(format!("{}.segments.{}", stem, ext))
+}
+
+/// Lock file for a log, e.g. `scratch.ndjson.lock`. It stays put while the active file is sealed
+/// and replaced, so every writer locks the same inode.
+pub fn lock_path(active: &Path) -> PathBuf {
+    let name = active
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    active.with_file_name(format!("{}.lock", name))
+}
+
+/// Take the log's lock, waiting for other writers; it is released when the file is dropped.
+pub fn lock(active: &Path) -> Result<std::fs::File> {
+    if let Some(parent) = active.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("create {}", parent.display()))?;
+    }
+    let path = lock_path(active);
+    let f = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("open {}", path.display()))?;
+    f.lock()
+        .with_context(|| format!("lock {}", path.display()))?;
+    Ok(f)
+}
+
+fn segment_name(active: &Path, n: u64) -> String {
+    let (stem, ext) = stem_ext(active);
+    format!("{}.{:06}.{}", stem, n, ext)
+}
+
+/// Globs for the sealed segments and the manifest of every log whose active file matches
+/// `active` (a root-relative glob such as `.notes/notes.ndjson`).
+pub fn sealed_globs(active: &str) -> Vec<String> {
+    let (dir, name) = active
+        .rsplit_once('/')
+        .map_or(("", active), |(d, n)| (d, n));
+    let (stem, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+    let dot_ext = if ext.is_empty() {
+        String::new()
+    } else {
+        format!(".{}", ext)
+    };
+    let prefix = if dir.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", dir)
+    };
+    vec![
+        format!("{}{}.{}{}", prefix, stem, "[0-9]".repeat(6), dot_ext),
+        format!("{}{}.segments{}", prefix, stem, dot_ext),
+    ]
+}
+
+/// Sealed segment number from a file name, if it is a segment of this log.
+pub fn segment_number(active: &Path, name: &str) -> Option<u64> {
+    let (stem, ext) = stem_ext(active);
+    let mid = name
+        .strip_prefix(&format!("{}.", stem))?
+        .strip_suffix(&format!(".{}", ext))?;
+    if mid.len() == 6 && mid.bytes().all(|b| b.is_ascii_digit()) {
+        mid.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Sealed segments on disk, oldest first.
+pub fn sealed_segments(active: &Path) -> Result<Vec<PathBuf>> {
+    let Some(dir) = active.parent() else {
+        return Ok(Vec::new());
+    };
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut found: Vec<(u64, PathBuf)> = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("read {}", dir.display()))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(n) = segment_number(active, &name) {
+            found.push((n, entry.path()));
+        }
+    }
+    found.sort();
+    Ok(found.into_iter().map(|(_, p)| p).collect())
+}
+
+/// Every file of the logical log in order: sealed segments, then the active file if present.
+pub fn log_files(active: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = sealed_segments(active)?;
+    if active.is_file() {
+        files.push(active.to_path_buf());
+    }
+    Ok(files)
+}
+
+/// All non-blank lines of the logical log, oldest first.
+pub fn read_lines(active: &Path) -> Result<Vec<String>> {
+    let mut out = Vec::new();
+    for path in log_files(active)? {
+        let f = std::fs::File::open(&path).with_context(|| format!("read {}", path.display()))?;
+        out.extend(
+            BufReader::new(f)
+                .lines()
+                .map_while(Result::ok)
+                .filter(|s| !s.trim().is_empty()),
+        );
+    }
+    Ok(out)
+}
+
+/// Newest `n` lines passing `keep`, oldest first. Reads each file backwards in blocks, newest file
+/// first, so cost is proportional to what is returned rather than to the log size.
+pub fn tail_lines(active: &Path, n: usize, keep: impl Fn(&str) -> bool) -> Result<Vec<String>> {
+    let mut found: Vec<String> = Vec::new();
+    for path in log_files(active)?.iter().rev() {
+        if found.len() >= n {
+            break;
+        }
+        let mut f =
+            std::fs::File::open(path).with_context(|| format!("read {}", path.display()))?;
+        let mut pos = f.seek(SeekFrom::End(0))?;
+        let mut carry: Vec<u8> = Vec::new();
+        let mut block = vec![0u8; TAIL_BLOCK];
+        while pos > 0 && found.len() < n {
+            let len = TAIL_BLOCK.min(pos as usize);
+            pos -= len as u64;
+            f.seek(SeekFrom::Start(pos))?;
+            f.read_exact(&mut block[..len])?;
+            // Prepend this block to the unfinished line carried over from the previous one.
+            let mut buf = block[..len].to_vec();
+            buf.extend_from_slice(&carry);
+            let mut parts: Vec<&[u8]> = buf.split(|b| *b == b'\n').collect();
+            // The first part may continue into the previous block unless we are at the file start.
+            carry = if pos > 0 {
+                parts.remove(0).to_vec()
+            } else {
+                Vec::new()
+            };
+            for part in parts.iter().rev() {
+                let line = String::from_utf8_lossy(part);
+                if !line.trim().is_empty() && keep(&line) {
+                    found.push(line.into_owned());
+                    if found.len() == n {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    found.reverse();
+    Ok(found)
+}
+
+/// Sealed segments recorded in the manifest, in the order they were sealed.
+pub fn read_manifest(active: &Path) -> Result<Vec<SealedSegment>> {
+    let path = manifest_path(active);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let s = std::fs::read_to_string(&path).with_context(|| format!("read {}", path.display()))?;
+    s.lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| serde_json::from_str(l).with_context(|| format!("parse {}", path.display())))
+        .collect()
+}
+
+/// Seal the active file as the next segment and record its hash. Returns the segment path, or
+/// None if there was nothing to seal (missing or empty active file). The caller holds the log's
+/// lock from before the link until the manifest line is written; commands seal through
+/// [`maybe_rotate`].
+fn seal_locked(active: &Path, sealed_at: &str) -> Result<Option<PathBuf>> {
+    if std::fs::metadata(active).map(|m| m.len()).unwrap_or(0) == 0 {
+        return Ok(None);
+    }
+    let next = sealed_segments(active)?
+        .last()
+        .and_then(|p| segment_number(active, &p.file_name()?.to_string_lossy()))
+        .unwrap_or(0)
+        + 1;
+    let name = segment_name(active, next);
+    let seg = active.with_file_name(&name);
+    // hard_link refuses to overwrite, so two writers sealing at once cannot clobber a segment.
+    std::fs::hard_link(active, &seg)
+        .with_context(|| format!("seal {} as {}", active.display(), seg.display()))?;
+    std::fs::remove_file(active).with_context(|| format!("remove {}", active.display()))?;
+    let (sha256, bytes, lines) = digest::sha256_file(&seg)?;
+    let record = SealedSegment {
+        file: name,
+        sha256,
+        bytes,
+        lines,
+        sealed_at: sealed_at.to_string(),
+    };
+    let manifest = manifest_path(active);
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&manifest)
+        .with_context(|| format!("open {}", manifest.display()))?;
+    writeln!(f, "{}", serde_json::to_string(&record)?)
+        .with_context(|| format!("write {}", manifest.display()))?;
+    Ok(Some(seg))
+}
+
+/// Timestamp of the first event in the active file, if any.
+fn first_ts(active: &Path) -> Option<DateTime<Utc>> {
+    let f = std::fs::File::open(active).ok()?;
+    let line = BufReader::new(f).lines().map_while(Result::ok).next()?;
+    let v: serde_json::Value = serde_json::from_str(&line).ok()?;
+    DateTime::parse_from_rfc3339(v.get("ts")?.as_str()?)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Seal the active file if it has reached `rotation.max_bytes` or its first entry is older than
+/// `rotation.max_age_hours`. Returns the new segment path if one was sealed. Decides under the
+/// log's lock, so two writers that both found the log due do not seal it twice.
+pub fn maybe_rotate(
+    active: &Path,
+    rotation: &Rotation,
+    now: DateTime<Utc>,
+) -> Result<Option<PathBuf>> {
+    if rotation.max_bytes.is_none() && rotation.max_age_hours.is_none() {
+        return Ok(None);
+    }
+    if std::fs::metadata(active).map(|m| m.len()).unwrap_or(0) == 0 {
+        return Ok(None);
+    }
+    let _lock = lock(active)?;
+    let len = std::fs::metadata(active).map(|m| m.len()).unwrap_or(0);
+    if len == 0 {
+        return Ok(None);
+    }
+    let too_big = rotation.max_bytes.is_some_and(|max| len >= max);
+    let too_old = rotation.max_age_hours.is_some_and(|h| {
+        first_ts(active).is_some_and(|first| now - first >= chrono::Duration::hours(h as i64))
+    });
+    if too_big || too_old {
+        seal_locked(active, &now.to_rfc3339())
+    } else {
+        Ok(None)
+    }
+}
+
+/// Check sealed segments against the manifest. Returns one message per problem (empty = ok).
+pub fn verify(active: &Path) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+    let manifest = read_manifest(active)?;
+    let on_disk = sealed_segments(active)?;
+    for rec in &manifest {
+        let path = active.with_file_name(&rec.file);
+        if !path.is_file() {
+            problems.push(format!("{}: sealed segment missing", path.display()));
+            continue;
+        }
+        let (sha256, bytes, _) = digest::sha256_file(&path)?;
+        if sha256 != rec.sha256 {
+            problems.push(format!(
+                "{}: sha256 mismatch (manifest {}, file {}, {} bytes)",
+                path.display(),
+                rec.sha256,
+                sha256,
+                bytes
+            ));
+        }
+    }
+    for path in on_disk {
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned());
+        if !manifest.iter().any(|r| Some(&r.file) == name.as_ref()) {
+            problems.push(format!("{}: segment not in manifest", path.display()));
+        }
+    }
+    Ok(problems)
+}
+
+/// Bytes of `path` from `offset` to the end.
+pub fn read_from(path: &Path, offset: u64) -> Result<String> {
+    let mut f = std::fs::File::open(path).with_context(|| format!("read {}", path.display()))?;
+    f.seek(SeekFrom::Start(offset))?;
+    let mut chunk = String::new();
+    f.read_to_string(&mut chunk)?;
+    Ok(chunk)
+}
+
+/// Follows a log from where it ends at creation. When the active file is sealed, the rest of the
+/// sealed segment is drained before following the new active file; a file that shrinks otherwise
+/// is re-read from the start.
+pub struct Follower {
+    active: PathBuf,
+    offset: u64,
+    sealed: usize,
+    partial: String,
+}
+
+impl Follower {
+    pub fn new(active: &Path) -> Result<Follower> {
+        Ok(Follower {
+            active: active.to_path_buf(),
+            offset: std::fs::metadata(active).map(|m| m.len()).unwrap_or(0),
+            sealed: sealed_segments(active)?.len(),
+            partial: String::new(),
+        })
+    }
+
+    /// Complete lines appended since the last call; a half-written line waits for its newline.
+    pub fn poll(&mut self) -> Result<Vec<String>> {
+        let segments = sealed_segments(&self.active)?;
+        if segments.len() > self.sealed {
+            // The file we were reading was sealed, maybe more than once since: finish the first
+            // segment from where we stopped and read any later ones whole.
+            for (i, seg) in segments[self.sealed..].iter().enumerate() {
+                let from = if i == 0 { self.offset } else { 0 };
+                self.partial.push_str(&read_from(seg, from)?);
+            }
+            self.sealed = segments.len();
+            self.offset = 0;
+        }
+        let len = std::fs::metadata(&self.active)
+            .map(|m| m.len())
+            .unwrap_or(0);
+        if len < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+        }
+        if len > self.offset {
+            let chunk = read_from(&self.active, self.offset)?;
+            self.offset += chunk.len() as u64;
+            self.partial.push_str(&chunk);
+        }
+        let mut lines = Vec::new();
+        while let Some(i) = self.partial.find('\n') {
+            let line: String = self.partial.drain(..=i).collect();
+            let line = line.trim_end_matches('\n');
+            if !line.trim().is_empty() {
+                lines.push(line.to_string());
+            }
+        }
+        Ok(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn fresh(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("log.ndjson")
+    }
+
+    fn seal(active: &Path, sealed_at: &str) -> Result<Option<PathBuf>> {
+        let _lock = lock(active)?;
+        seal_locked(active, sealed_at)
+    }
+
+    fn append(active: &Path, line: &str) {
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(active)
+            .unwrap();
+        writeln!(f, "{}", line).unwrap();
+    }
+
+    #[test]
+    fn seal_rolls_active_into_numbered_segments() {
+        let active = fresh("hyena_segment_seal");
+        append(&active, r#"{"n":1}"#);
+        let seg = seal(&active, "t").unwrap().unwrap();
+        assert!(seg.ends_with("log.000001.ndjson"));
+        assert!(!active.exists());
+        append(&active, r#"{"n":2}"#);
+        seal(&active, "t").unwrap();
+        append(&active, r#"{"n":3}"#);
+
+        assert_eq!(sealed_segments(&active).unwrap().len(), 2);
+        let lines = read_lines(&active).unwrap();
+        assert_eq!(lines, vec![r#"{"n":1}"#, r#"{"n":2}"#, r#"{"n":3}"#]);
+        let tail = tail_lines(&active, 2, |_| true).unwrap();
+        assert_eq!(tail, vec![r#"{"n":2}"#, r#"{"n":3}"#]);
+        assert!(verify(&active).unwrap().is_empty());
+        assert_eq!(read_manifest(&active).unwrap()[1].lines, 1);
+        fs::remove_dir_all(active.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn follower_reads_every_segment_sealed_between_polls() {
+        let active = fresh("hyena_segment_follow");
+        append(&active, r#"{"n":1}"#);
+        let mut follower = Follower::new(&active).unwrap();
+        append(&active, r#"{"n":2}"#);
+        seal(&active, "t").unwrap();
+        append(&active, r#"{"n":3}"#);
+        seal(&active, "t").unwrap();
+        append(&active, r#"{"n":4}"#);
+        assert_eq!(
+            follower.poll().unwrap(),
+            vec![r#"{"n":2}"#, r#"{"n":3}"#, r#"{"n":4}"#]
+        );
+        assert!(follower.poll().unwrap().is_empty());
+        fs::remove_dir_all(active.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn rotate_by_size_and_age() {
+        let active = fresh("hyena_segment_rotate");
+        let by_size = Rotation {
+            max_bytes: Some(10),
+            max_age_hours: None,
+        };
+        append(&active, r#"{"ts":"2025-01-01T00:00:00Z"}"#);
+        let now = DateTime::parse_from_rfc3339("2025-01-01T01:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert!(maybe_rotate(&active, &by_size, now).unwrap().is_some());
+
+        append(&active, r#"{"ts":"2025-01-01T00:00:00Z"}"#);
+        let by_age = Rotation {
+            max_bytes: None,
+            max_age_hours: Some(2),
+        };
+        assert!(maybe_rotate(&active, &by_age, now).unwrap().is_none());
+        let later = now + chrono::Duration::hours(1);
+        assert!(maybe_rotate(&active, &by_age, later).unwrap().is_some());
+        assert_eq!(sealed_segments(&active).unwrap().len(), 2);
+        fs::remove_dir_all(active.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn rotation_is_decided_under_the_lock() {
+        let active = fresh("hyena_segment_rotate_locked");
+        let by_size = Rotation {
+            max_bytes: Some(40),
+            max_age_hours: None,
+        };
+        append(
+            &active,
+            r#"{"ts":"2025-01-01T00:00:00Z","text":"long enough"}"#,
+        );
+        let held = lock(&active).unwrap();
+        let rotator = {
+            let active = active.clone();
+            std::thread::spawn(move || maybe_rotate(&active, &by_size, Utc::now()).unwrap())
+        };
+        // While it waits, another writer seals the log and starts a new active file.
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        seal_locked(&active, "t").unwrap();
+        append(&active, "{}");
+        drop(held);
+        assert_eq!(rotator.join().unwrap(), None);
+        assert_eq!(sealed_segments(&active).unwrap().len(), 1);
+        fs::remove_dir_all(active.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn verify_detects_tampering_and_strays() {
+        let active = fresh("hyena_segment_verify");
+        append(&active, r#"{"n":1}"#);
+        let seg = seal(&active, "t").unwrap().unwrap();
+        append(&seg, r#"{"n":"sneaky"}"#);
+        fs::write(active.with_file_name("log.000007.ndjson"), "{}\n").unwrap();
+        let problems = verify(&active).unwrap();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].contains("sha256 mismatch"));
+        assert!(problems[1].contains("not in manifest"));
+        fs::remove_dir_all(active.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn segment_names_do_not_match_other_files() {
+        let active = Path::new("/x/scratch.ndjson");
+        assert_eq!(segment_number(active, "scratch.000012.ndjson"), Some(12));
+        assert_eq!(segment_number(active, "scratch.segments.ndjson"), None);
+        assert_eq!(segment_number(active, "scratch.ndjson"), None);
+        assert_eq!(segment_number(active, "notes.000001.ndjson"), None);
+    }
+}
@@ -0,0 +1,439 @@
+//! Local HTTP/JSON API: `hyena serve --http ADDR` answers the read commands without a process per
+//! request.
+//!
+//! Every request needs `Authorization: Bearer <token>` (see [`crate::tokens`]); the token's actor
+//! is checked against the policy, loaded fresh for each request, exactly as `--actor` is on the
+//! command line. Endpoints (all GET, JSON out):
+//! - `/context?path=&max_lines=` — nearest NOTES.md excerpt,
+//! - `/raw?scope=` — raw inputs the actor may read,
+//! - `/derived?scope_contains=&status=&min_confidence=&sort=&latest=&max=`,
+//! - `/scratch?max=&tail=&actor=&kind=&since=`,
+//! - `/search?q=&include_scratch=&status=&min_confidence=&sort=`,
+//! - `/events?log=derived|scratch` — server-sent events, one per new log line (both logs if no
+//!   `log`), with a keep-alive comment when idle.
+//!
+//! Binds to loopback addresses only unless `--allow-remote` is given.
+
+use crate::derived::{ClaimFilter, SortBy};
+use crate::{
+    context, derived, event, overlay, paths, policy, raw, scratch, search, segment, tokens,
+};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often `/events` checks the logs for new lines.
+const EVENTS_POLL: Duration = Duration::from_millis(250);
+/// Idle time after which `/events` sends a keep-alive comment (and notices closed clients).
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// A request the client got wrong (400), as opposed to a policy denial (403) or a failure (500).
+#[derive(Debug)]
+struct BadRequest(String);
+
+impl std::fmt::Display for BadRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BadRequest {}
+
+fn bad_request(msg: impl Into<String>) -> anyhow::Error {
+    BadRequest(msg.into()).into()
+}
+
+fn status_of(e: &anyhow::Error) -> u16 {
+    if e.downcast_ref::<BadRequest>().is_some() {
+        400
+    } else if e.to_string().starts_with("policy:") {
+        403
+    } else {
+        500
+    }
+}
+
+/// Percent-decode a query component (`+` is a space).
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    Err(_) => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Split a request URL into its path and query parameters.
+fn parse_url(url: &str) -> (&str, HashMap<String, String>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let params = query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (k, v) = p.split_once('=').unwrap_or((p, ""));
+            (decode(k), decode(v))
+        })
+        .collect();
+    (path, params)
+}
+
+/// Typed access to query parameters; parse failures are 400s.
+struct Params(HashMap<String, String>);
+
+impl Params {
+    fn str(&self, key: &str) -> Option<&str> {
+        self.0
+            .get(key)
+            .map(String::as_str)
+            .filter(|v| !v.is_empty())
+    }
+
+    fn usize(&self, key: &str) -> Result<Option<usize>> {
+        self.str(key)
+            .map(|v| {
+                v.parse()
+                    .map_err(|_| bad_request(format!("{} must be a number, got '{}'", key, v)))
+            })
+            .transpose()
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.0
+            .get(key)
+            .is_some_and(|v| matches!(v.as_str(), "" | "1" | "true" | "yes"))
+    }
+
+    fn claims(&self) -> Result<ClaimFilter> {
+        Ok(ClaimFilter {
+            status: self
+                .str("status")
+                .map(|s| {
+                    s.parse()
+                        .map_err(|e: anyhow::Error| bad_request(e.to_string()))
+                })
+                .transpose()?,
+            min_confidence: self
+                .str("min_confidence")
+                .map(|c| event::parse_confidence(c).map_err(|e| bad_request(e.to_string())))
+                .transpose()?,
+            sort: match self.str("sort") {
+                None => None,
+                Some("confidence") => Some(SortBy::Confidence),
+                Some("status") => Some(SortBy::Status),
+                Some(other) => {
+                    return Err(bad_request(format!(
+                        "sort must be confidence or status, got '{}'",
+                        other
+                    )))
+                }
+            },
+            hidden: Vec::new(),
+        })
+    }
+}
+
+/// Log lines as JSON values (lines that are not JSON are skipped).
+fn entries(lines: impl IntoIterator<Item = String>) -> serde_json::Value {
+    let values: Vec<serde_json::Value> = lines
+        .into_iter()
+        .filter_map(|l| serde_json::from_str(&l).ok())
+        .collect();
+    serde_json::json!({ "entries": values })
+}
+
+struct Api {
+    root: PathBuf,
+    policy_path: PathBuf,
+}
+
+impl Api {
+    /// Actor for the request's bearer token, checked against the policy.
+    fn actor(&self, request: &tiny_http::Request) -> Result<String> {
+        let token = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Authorization"))
+            .and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+            .ok_or_else(|| anyhow::anyhow!("missing bearer token"))?;
+        let actor = tokens::load(&self.root)?
+            .actor_for(token.trim())
+            .map(str::to_string)
+            .ok_or_else(|| anyhow::anyhow!("unknown token"))?;
+        if let Some(policy) = policy::load_if_exists(&self.policy_path)? {
+            policy.check_actor(&actor)?;
+        }
+        Ok(actor)
+    }
+
+    /// Subtrees whose raw inputs `actor` may not read; derived entries sourced there are not served.
+    fn hidden(&self, actor: &str) -> Result<Vec<String>> {
+        match policy::load_if_exists(&self.policy_path)? {
+            Some(policy) => overlay::hidden_subtrees(&self.root, &self.policy_path, &policy, actor),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The request's claim filter, hiding what `actor` may not read.
+    fn claims(&self, params: &Params, actor: &str) -> Result<ClaimFilter> {
+        let mut claims = params.claims()?;
+        claims.hidden = self.hidden(actor)?;
+        Ok(claims)
+    }
+
+    /// Canonical form of a path parameter; leaving root is a 400, as on the command line.
+    fn resolve(&self, arg: &str) -> Result<PathBuf> {
+        let policy = policy::load(&self.policy_path)?;
+        paths::resolve_under_root(
+            &paths::canonical_root(&self.root)?,
+            Path::new(arg),
+            policy.symlinks(),
+        )
+        .map_err(|e| bad_request(format!("{:#}", e)))
+    }
+
+    /// Root-relative form of a path parameter.
+    fn relative(&self, arg: &str) -> Result<String> {
+        let policy = policy::load(&self.policy_path)?;
+        paths::relative_under_root(
+            &paths::canonical_root(&self.root)?,
+            Path::new(arg),
+            policy.symlinks(),
+        )
+        .map_err(|e| bad_request(format!("{:#}", e)))
+    }
+
+    fn route(&self, path: &str, actor: &str, params: &Params) -> Result<Option<serde_json::Value>> {
+        let root = self.root.as_path();
+        let value = match path {
+            "/context" => {
+                let start = params.str("path").map(|p| self.resolve(p)).transpose()?;
+                let (notes, text) = context::read_context(
+                    root,
+                    &self.policy_path,
+                    actor,
+                    start,
+                    params.usize("max_lines")?,
+                )?;
+                let rel = raw::relative_for_glob(&notes, root)
+                    .unwrap_or_else(|| notes.display().to_string());
+                serde_json::json!({ "path": rel, "text": text })
+            }
+            "/raw" => {
+                let policy = policy::load(&self.policy_path)?;
+                if !policy.may_read_raw(actor) {
+                    anyhow::bail!("policy: actor '{}' may not read raw inputs", actor);
+                }
+                let scope = params
+                    .str("scope")
+                    .map(|s| self.relative(s).map(PathBuf::from))
+                    .transpose()?;
+                let inputs = overlay::raw_inputs_for(root, &self.policy_path, &policy, actor)?;
+                let mut files = Vec::new();
+                for p in raw::discover_raw_files(root, scope.as_ref(), &inputs)? {
+                    let text = std::fs::read_to_string(&p)
+                        .with_context(|| format!("read {}", p.display()))?;
+                    let rel = raw::relative_for_glob(&p, root).unwrap_or_default();
+                    files.push(serde_json::json!({ "path": rel, "text": text }));
+                }
+                serde_json::json!({ "files": files })
+            }
+            "/derived" => {
+                let out = derived::read_derived(
+                    root,
+                    params.str("scope_contains"),
+                    &self.claims(params, actor)?,
+                    params.flag("latest"),
+                    params.usize("max")?,
+                )?;
+                entries(out.lines().map(str::to_string))
+            }
+            "/scratch" => {
+                let filter = scratch::ScratchFilter {
+                    actor: params.str("actor").map(str::to_string),
+                    kind: params.str("kind").map(str::to_string),
+                    since: params
+                        .str("since")
+                        .map(|s| scratch::parse_since(s).map_err(|e| bad_request(e.to_string())))
+                        .transpose()?,
+                };
+                let out = match params.usize("tail")? {
+                    Some(n) => scratch::tail_scratch(root, n, &filter)?,
+                    None => scratch::read_scratch(root, params.usize("max")?, &filter)?,
+                };
+                entries(out.lines().map(str::to_string))
+            }
+            "/search" => {
+                let q = params
+                    .str("q")
+                    .ok_or_else(|| bad_request("search needs q"))?;
+                let lines = search::search(root, q, params.flag("include_scratch"))?;
+                entries(self.claims(params, actor)?.apply(lines))
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(value))
+    }
+
+    fn handle(&self, request: tiny_http::Request) {
+        let json = |status: u16, body: serde_json::Value| {
+            tiny_http::Response::from_string(body.to_string())
+                .with_status_code(status)
+                .with_header(
+                    tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                        .expect("static header"),
+                )
+        };
+        let error = |status: u16, msg: String| json(status, serde_json::json!({ "error": msg }));
+        let actor = match self.actor(&request) {
+            Ok(a) => a,
+            Err(e) => {
+                let _ = request.respond(error(401, e.to_string()));
+                return;
+            }
+        };
+        if *request.method() != tiny_http::Method::Get {
+            let _ = request.respond(error(405, "only GET is supported".into()));
+            return;
+        }
+        let url = request.url().to_string();
+        let (path, params) = parse_url(&url);
+        let params = Params(params);
+        if path == "/events" {
+            let logs = match params.str("log") {
+                None => vec!["derived", "scratch"],
+                Some(l @ ("derived" | "scratch")) => vec![l],
+                Some(other) => {
+                    let msg = format!("log must be derived or scratch, got '{}'", other);
+                    let _ = request.respond(error(400, msg));
+                    return;
+                }
+            };
+            let _ = self.stream_events(request, &actor, &logs);
+            return;
+        }
+        let response = match self.route(path, &actor, &params) {
+            Ok(Some(v)) => json(200, v),
+            Ok(None) => error(404, format!("no endpoint {}", path)),
+            Err(e) => error(status_of(&e), format!("{:#}", e)),
+        };
+        let _ = request.respond(response);
+    }
+
+    /// Server-sent events for lines appended to `logs`, until the client goes away.
+    fn stream_events(&self, request: tiny_http::Request, actor: &str, logs: &[&str]) -> Result<()> {
+        let visible = ClaimFilter {
+            hidden: self.hidden(actor)?,
+            ..Default::default()
+        };
+        let mut followers = Vec::new();
+        for log in logs {
+            let active = match *log {
+                "derived" => derived::derived_path(&self.root),
+                _ => scratch::scratch_path(&self.root),
+            };
+            followers.push((*log, segment::Follower::new(&active)?));
+        }
+        let mut out = request.into_writer();
+        out.write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+              Connection: keep-alive\r\n\r\n",
+        )?;
+        out.flush()?;
+        let mut last_sent = Instant::now();
+        loop {
+            for (log, follower) in &mut followers {
+                for line in visible.apply(follower.poll()?) {
+                    let id = event::Event::parse(&line).and_then(|e| e.id);
+                    if let Some(id) = id {
+                        writeln!(out, "id: {}", id)?;
+                    }
+                    write!(out, "event: {}\ndata: {}\n\n", log, line)?;
+                    last_sent = Instant::now();
+                }
+            }
+            if last_sent.elapsed() >= KEEP_ALIVE {
+                out.write_all(b": keep-alive\n\n")?;
+                last_sent = Instant::now();
+            }
+            out.flush()?;
+            std::thread::sleep(EVENTS_POLL);
+        }
+    }
+}
+
+/// Refuse non-loopback addresses unless `allow_remote`.
+fn check_bind(addr: &str, allow_remote: bool) -> Result<()> {
+    let resolved: Vec<_> = addr
+        .to_socket_addrs()
+        .with_context(|| format!("resolve {}", addr))?
+        .collect();
+    if resolved.is_empty() {
+        anyhow::bail!("{} resolves to no address", addr);
+    }
+    if !allow_remote && resolved.iter().any(|a| !a.ip().is_loopback()) {
+        anyhow::bail!(
+            "refusing to listen on {}: not a loopback address (pass --allow-remote)",
+            addr
+        );
+    }
+    Ok(())
+}
+
+/// Serve until the process is stopped. Prints `listening on http://ADDR` once bound.
+pub fn serve(root: &Path, policy_path: &Path, addr: &str, allow_remote: bool) -> Result<()> {
+    check_bind(addr, allow_remote)?;
+    let server =
+        tiny_http::Server::http(addr).map_err(|e| anyhow::anyhow!("listen on {}: {}", addr, e))?;
+    match server.server_addr().to_ip() {
+        Some(a) => println!("listening on http://{}", a),
+        None => println!("listening on {}", addr),
+    }
+    std::io::stdout().flush()?;
+    let api = std::sync::Arc::new(Api {
+        root: root.to_path_buf(),
+        policy_path: policy_path.to_path_buf(),
+    });
+    for request in server.incoming_requests() {
+        let api = api.clone();
+        std::thread::spawn(move || api.handle(request));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_queries_and_refuses_remote_binds() {
+        let (path, params) = parse_url("/search?q=use%20ULIDs+now&include_scratch&max=3&bad=%zz");
+        assert_eq!(path, "/search");
+        let params = Params(params);
+        assert_eq!(params.str("q"), Some("use ULIDs now"));
+        assert!(params.flag("include_scratch"));
+        assert_eq!(params.usize("max").unwrap(), Some(3));
+        assert_eq!(params.str("bad"), Some("%zz"));
+        assert_eq!(status_of(&params.usize("q").unwrap_err()), 400);
+        assert!(check_bind("127.0.0.1:0", false).is_ok());
+        assert!(check_bind("0.0.0.0:0", false).is_err());
+        assert!(check_bind("0.0.0.0:0", true).is_ok());
+    }
+}
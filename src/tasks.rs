@@ -0,0 +1,285 @@
+//! Tasks: Markdown checkboxes (`- [ ] …`, `- [x] …`) in raw inputs, extracted by ingest.
+//!
+//! An item may carry `@owner` and `due:YYYY-MM-DD`; both are taken out of its text. Its ID hashes
+//! the file, the enclosing headings and the text, so it survives ticking the box, changing the
+//! annotations and moving within its section, and repeats of the same item get distinct IDs.
+//! Ingest keeps each file's current items in `.hyena/ingest.json` and appends a `task` event to
+//! the derived log whenever one appears or changes, and one marked `removed` when it disappears.
+
+use crate::chunk::{self, Chunk};
+use crate::digest;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// One checkbox item as last ingested.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskItem {
+    pub id: String,
+    /// Root-relative raw input.
+    pub source: String,
+    /// 1-based line in `source`.
+    pub line: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub heading_path: Vec<String>,
+    pub text: String,
+    pub done: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+}
+
+impl TaskItem {
+    /// Same item with nothing but its position changed.
+    pub fn same_state(&self, other: &TaskItem) -> bool {
+        (&self.text, self.done, &self.owner, &self.due)
+            == (&other.text, other.done, &other.owner, &other.due)
+    }
+}
+
+/// `- [ ] rest` / `* [x] rest` -> (done, rest).
+fn checkbox(line: &str) -> Option<(bool, &str)> {
+    let t = line.trim_start();
+    let t = t
+        .strip_prefix("- ")
+        .or_else(|| t.strip_prefix("* "))
+        .or_else(|| t.strip_prefix("+ "))?;
+    let done = match t.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    let rest = &t[3..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some((done, rest.trim()))
+}
+
+/// Split `@owner` and `due:DATE` (or `due: DATE`) out of an item's text.
+fn annotations(rest: &str) -> (String, Option<String>, Option<String>) {
+    let (mut owner, mut due) = (None, None);
+    let mut words = Vec::new();
+    let mut tokens = rest.split_whitespace().peekable();
+    while let Some(word) = tokens.next() {
+        if let Some(name) = word.strip_prefix('@') {
+            let name = name.trim_end_matches([',', ';', '.']);
+            if owner.is_none()
+                && !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            {
+                owner = Some(name.to_string());
+                continue;
+            }
+        }
+        if let Some(value) = word.strip_prefix("due:") {
+            let value = if value.is_empty() {
+                tokens.peek().copied().unwrap_or_default()
+            } else {
+                value
+            };
+            let value = value.trim_end_matches([',', ';']);
+            if due.is_none() && NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok() {
+                if word == "due:" {
+                    tokens.next();
+                }
+                due = Some(value.to_string());
+                continue;
+            }
+        }
+        words.push(word);
+    }
+    (words.join(" "), owner, due)
+}
+
+/// Checkbox items (outside fenced code) in the chunks of a raw input as its format parses it (see
+/// [`crate::formats::parse`]), in file order.
+pub fn extract(source: &str, chunks: &[Chunk]) -> Vec<TaskItem> {
+    let mut out = Vec::new();
+    let mut repeats: HashMap<String, usize> = HashMap::new();
+    for Chunk {
+        heading_path,
+        start_line,
+        text,
+        ..
+    } in chunks
+    {
+        let mut in_fence = false;
+        for (i, line) in text.lines().enumerate() {
+            if chunk::is_fence(line) {
+                in_fence = !in_fence;
+            }
+            let Some((done, rest)) = checkbox(line).filter(|_| !in_fence) else {
+                continue;
+            };
+            let (text, owner, due) = annotations(rest);
+            if text.is_empty() {
+                continue;
+            }
+            let key = format!("{}\n{}\n{}", source, heading_path.join("\n"), text);
+            let n = repeats.entry(key.clone()).or_default();
+            *n += 1;
+            let hashed = if *n == 1 {
+                key
+            } else {
+                format!("{}\n#{}", key, n)
+            };
+            out.push(TaskItem {
+                id: digest::sha256_hex(hashed.as_bytes())[..12].to_string(),
+                source: source.to_string(),
+                line: *start_line + i,
+                heading_path: heading_path.clone(),
+                text,
+                done,
+                owner,
+                due,
+            });
+        }
+    }
+    out
+}
+
+/// Which tasks `hyena tasks` shows.
+#[derive(Debug, Default)]
+pub struct TaskFilter {
+    /// Some(false) open only, Some(true) done only.
+    pub done: Option<bool>,
+    pub owner: Option<String>,
+    /// Only tasks due on or before this date (tasks without `due:` are left out).
+    pub due_by: Option<NaiveDate>,
+    /// Root-relative directory the source must be in.
+    pub scope: Option<String>,
+}
+
+impl TaskFilter {
+    pub fn matches(&self, t: &TaskItem) -> bool {
+        if self.done.is_some_and(|d| d != t.done) {
+            return false;
+        }
+        if self
+            .owner
+            .as_ref()
+            .is_some_and(|o| t.owner.as_ref() != Some(o))
+        {
+            return false;
+        }
+        if let Some(by) = self.due_by {
+            let due = t
+                .due
+                .as_deref()
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+            if due.is_none_or(|d| d > by) {
+                return false;
+            }
+        }
+        if let Some(scope) = self.scope.as_deref().filter(|s| *s != ".") {
+            if !t
+                .source
+                .strip_prefix(scope)
+                .is_some_and(|r| r.starts_with('/'))
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// One line per task: `[ ] id  path:line  text  @owner  due:DATE`.
+pub fn render(t: &TaskItem) -> String {
+    let mut s = format!(
+        "[{}] {}  {}:{}  {}",
+        if t.done { "x" } else { " " },
+        t.id,
+        t.source,
+        t.line,
+        t.text
+    );
+    if let Some(owner) = &t.owner {
+        s.push_str(&format!("  @{}", owner));
+    }
+    if let Some(due) = &t.due {
+        s.push_str(&format!("  due:{}", due));
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{self, Format};
+
+    fn extract(source: &str, md: &str) -> Vec<TaskItem> {
+        super::extract(source, &chunk::chunk_markdown(md))
+    }
+
+    #[test]
+    fn extracts_checkboxes_with_annotations() {
+        let md = "# Plan\n- [ ] write parser @ann due:2025-07-01\n* [x] pick format\n\
+                  - [ ] write parser\n```\n- [ ] not a task\n```\n## Later\n- [ ]  due: 2025-08-01 ship it\n- not a task\n";
+        let tasks = extract("NOTES.md", md);
+        assert_eq!(tasks.len(), 4);
+        assert_eq!(tasks[0].text, "write parser");
+        assert_eq!(tasks[0].owner.as_deref(), Some("ann"));
+        assert_eq!(tasks[0].due.as_deref(), Some("2025-07-01"));
+        assert_eq!((tasks[0].line, tasks[0].done), (2, false));
+        assert!(tasks[1].done);
+        // Same text under the same heading: distinct IDs.
+        assert_ne!(tasks[0].id, tasks[2].id);
+        assert_eq!(tasks[3].heading_path, vec!["Plan", "Later"]);
+        assert_eq!(tasks[3].text, "ship it");
+        assert_eq!(tasks[3].due.as_deref(), Some("2025-08-01"));
+    }
+
+    #[test]
+    fn heading_paths_follow_the_file_format() {
+        let org = "#+TITLE: Plan\n* TODO Ship :work:\n- [ ] write parser\n";
+        let parsed = formats::parse("plan.org", org, Format::Org);
+        let tasks = super::extract("plan.org", &parsed.chunks);
+        assert_eq!(tasks[0].heading_path, vec!["Ship"]);
+        assert_eq!(tasks[0].line, 3);
+    }
+
+    #[test]
+    fn id_is_stable_across_ticks_annotations_and_moves() {
+        let before = extract("a/NOTES.md", "# Plan\n- [ ] ship it\n");
+        let after = extract("a/NOTES.md", "intro\n\n# Plan\nnote\n- [x] ship it @bo\n");
+        assert_eq!(before[0].id, after[0].id);
+        assert!(!before[0].same_state(&after[0]));
+        let elsewhere = extract("b/NOTES.md", "# Plan\n- [ ] ship it\n");
+        assert_ne!(before[0].id, elsewhere[0].id);
+    }
+
+    #[test]
+    fn filter_by_status_owner_due_and_scope() {
+        let tasks = extract(
+            "team/NOTES.md",
+            "- [ ] a @ann due:2025-01-10\n- [x] b @ann\n- [ ] c due:2025-03-01\n",
+        );
+        let count = |f: &TaskFilter| tasks.iter().filter(|t| f.matches(t)).count();
+        assert_eq!(count(&TaskFilter::default()), 3);
+        let open = TaskFilter {
+            done: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(count(&open), 2);
+        let ann = TaskFilter {
+            owner: Some("ann".into()),
+            ..Default::default()
+        };
+        assert_eq!(count(&ann), 2);
+        let due = TaskFilter {
+            due_by: NaiveDate::from_ymd_opt(2025, 2, 1),
+            ..Default::default()
+        };
+        assert_eq!(count(&due), 1);
+        let other = TaskFilter {
+            scope: Some("tea".into()),
+            ..Default::default()
+        };
+        assert_eq!(count(&other), 0);
+    }
+}
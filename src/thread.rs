@@ -0,0 +1,125 @@
+//! Threads: reply (`parent_id`) and correction (`supersedes`) trees over log events.
+
+use crate::event::Event;
+use std::collections::{HashMap, HashSet};
+
+/// Tree parent of an entry: the entry it replies to, else the entry it supersedes.
+fn tree_parent(e: &Event) -> Option<&str> {
+    e.parent_id.as_deref().or(e.supersedes.as_deref())
+}
+
+/// Render the whole thread containing `id` as an indented tree (file order among siblings).
+/// Returns None if no event has that id.
+pub fn render_thread(events: &[Event], id: &str) -> Option<String> {
+    let by_id: HashMap<&str, &Event> = events
+        .iter()
+        .filter_map(|e| e.id.as_deref().map(|i| (i, e)))
+        .collect();
+    by_id.get(id)?;
+
+    // Walk up to the thread root; stop on missing parents or cycles.
+    let mut root = id;
+    let mut seen = HashSet::from([id]);
+    while let Some(parent) = by_id.get(root).and_then(|e| tree_parent(e)) {
+        if !by_id.contains_key(parent) || !seen.insert(parent) {
+            break;
+        }
+        root = parent;
+    }
+
+    let mut children: HashMap<&str, Vec<&Event>> = HashMap::new();
+    let mut superseded_by: HashMap<&str, &str> = HashMap::new();
+    for e in events {
+        let Some(eid) = e.id.as_deref() else { continue };
+        if let Some(p) = tree_parent(e) {
+            if p != eid {
+                children.entry(p).or_default().push(e);
+            }
+        }
+        if let Some(old) = e.supersedes.as_deref() {
+            superseded_by.insert(old, eid);
+        }
+    }
+
+    let mut out = String::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![(by_id[root], 0usize)];
+    while let Some((e, depth)) = stack.pop() {
+        let eid = e.id.as_deref().unwrap_or_default();
+        if !visited.insert(eid) {
+            continue;
+        }
+        out.push_str(&"  ".repeat(depth));
+        out.push_str(&format!(
+            "{} {} {}: {}",
+            eid,
+            e.actor,
+            e.kind(),
+            e.text().unwrap_or("")
+        ));
+        if let Some(old) = e.supersedes.as_deref() {
+            out.push_str(&format!(" (supersedes {})", old));
+        }
+        if let Some(new) = superseded_by.get(eid) {
+            out.push_str(&format!(" [superseded by {}]", new));
+        }
+        out.push('\n');
+        if let Some(kids) = children.get(eid) {
+            for kid in kids.iter().rev() {
+                stack.push((kid, depth + 1));
+            }
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Body;
+
+    fn note(text: &str, parent: Option<&Event>, supersedes: Option<&Event>) -> Event {
+        Event::new("t".into(), "agent", Body::from_kind_text("note", text))
+            .with_links(
+                parent.and_then(|p| p.id.as_deref()),
+                supersedes.and_then(|p| p.id.as_deref()),
+                &[],
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn renders_tree_from_any_member() {
+        let root = note("question", None, None);
+        let a = note("answer a", Some(&root), None);
+        let b = note("answer b", Some(&root), None);
+        let a1 = note("follow-up", Some(&a), None);
+        let other = note("unrelated", None, None);
+        let events = vec![root.clone(), a.clone(), b, a1.clone(), other];
+
+        let out = render_thread(&events, a1.id.as_deref().unwrap()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].ends_with("note: question"));
+        assert!(lines[1].starts_with("  ") && lines[1].ends_with("answer a"));
+        assert!(lines[2].starts_with("    ") && lines[2].ends_with("follow-up"));
+        assert!(lines[3].starts_with("  ") && lines[3].ends_with("answer b"));
+        assert!(!out.contains("unrelated"));
+    }
+
+    #[test]
+    fn marks_supersessions() {
+        let old = note("typo", None, None);
+        let fix = note("fixed", None, Some(&old));
+        let events = vec![old.clone(), fix.clone()];
+        let out = render_thread(&events, old.id.as_deref().unwrap()).unwrap();
+        assert!(out.contains(&format!("[superseded by {}]", fix.id.as_deref().unwrap())));
+        assert!(out.contains(&format!("(supersedes {})", old.id.as_deref().unwrap())));
+    }
+
+    #[test]
+    fn unknown_id_is_none() {
+        let events = vec![note("x", None, None)];
+        assert!(render_thread(&events, "01ARZ3NDEKTSV4RRFFQ69G5FAV").is_none());
+    }
+}
@@ -0,0 +1,114 @@
+//! API tokens for `hyena serve`: each maps a bearer token to the actor its requests act as.
+//!
+//! `hyena token issue ACTOR` prints a new token once; `.hyena/tokens.json` keeps only its SHA-256,
+//! so the file does not grant access if it leaks. `hyena token revoke ACTOR` drops the actor's
+//! tokens.
+
+use crate::digest;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const TOKENS_REL: &str = ".hyena/tokens.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TokenStore {
+    #[serde(default)]
+    pub tokens: Vec<TokenEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenEntry {
+    pub actor: String,
+    pub sha256: String,
+    pub issued_at: String,
+}
+
+pub fn tokens_path(root: &Path) -> PathBuf {
+    root.join(TOKENS_REL)
+}
+
+pub fn load(root: &Path) -> Result<TokenStore> {
+    let path = tokens_path(root);
+    if !path.is_file() {
+        return Ok(TokenStore::default());
+    }
+    let s = std::fs::read_to_string(&path).with_context(|| format!("read {}", path.display()))?;
+    serde_json::from_str(&s).with_context(|| format!("parse {}", path.display()))
+}
+
+fn save(root: &Path, store: &TokenStore) -> Result<()> {
+    let path = tokens_path(root);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(store)? + "\n")
+        .with_context(|| format!("write {}", tmp.display()))?;
+    std::fs::rename(&tmp, &path).with_context(|| format!("write {}", path.display()))
+}
+
+/// New token for `actor` (160 random bits from two ULIDs); only its hash is stored.
+pub fn issue(root: &Path, actor: &str, issued_at: String) -> Result<String> {
+    let random = |u: ulid::Ulid| u.random();
+    let token = format!(
+        "hyena_{:020x}{:020x}",
+        random(ulid::Ulid::new()),
+        random(ulid::Ulid::new())
+    );
+    let mut store = load(root)?;
+    store.tokens.push(TokenEntry {
+        actor: actor.to_string(),
+        sha256: digest::sha256_hex(token.as_bytes()),
+        issued_at,
+    });
+    save(root, &store)?;
+    Ok(token)
+}
+
+/// Drop every token of `actor`; returns how many there were.
+pub fn revoke(root: &Path, actor: &str) -> Result<usize> {
+    let mut store = load(root)?;
+    let before = store.tokens.len();
+    store.tokens.retain(|t| t.actor != actor);
+    let removed = before - store.tokens.len();
+    if removed > 0 {
+        save(root, &store)?;
+    }
+    Ok(removed)
+}
+
+impl TokenStore {
+    /// Actor a presented token belongs to.
+    pub fn actor_for(&self, token: &str) -> Option<&str> {
+        let sha = digest::sha256_hex(token.as_bytes());
+        self.tokens
+            .iter()
+            .find(|t| t.sha256 == sha)
+            .map(|t| t.actor.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn issue_lookup_and_revoke() {
+        let root = std::env::temp_dir().join("hyena_tokens");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let a = issue(&root, "agent", "t".into()).unwrap();
+        let b = issue(&root, "editor", "t".into()).unwrap();
+        assert_ne!(a, b);
+        assert!(!fs::read_to_string(tokens_path(&root)).unwrap().contains(&a));
+        let store = load(&root).unwrap();
+        assert_eq!(store.actor_for(&a), Some("agent"));
+        assert_eq!(store.actor_for(&b), Some("editor"));
+        assert_eq!(store.actor_for("hyena_guess"), None);
+        assert_eq!(revoke(&root, "agent").unwrap(), 1);
+        assert_eq!(load(&root).unwrap().actor_for(&a), None);
+        fs::remove_dir_all(&root).ok();
+    }
+}
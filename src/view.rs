@@ -0,0 +1,475 @@
+//! Materialized view of the derived log: its live entries, for reading without a full replay.
+//!
+//! `hyena snapshot-derived` writes the live entries to `.hyena/views/derived.ndjson` and, in
+//! `derived.meta.json`, the log's key at build time plus each thread's live entries. The key is
+//! the sealed segments' hashes from the segment manifest plus the active file's length and hash;
+//! only the active file, which rotation keeps small, is read. An entry is live unless a later
+//! entry supersedes it; a `tombstone` withdraws the entry it supersedes and is not live itself; a
+//! `task` event with a `task_id` gives way to the next event for the same task, and one marked
+//! `removed` is not live. The log is never touched.
+//! `read derived --latest` uses the view while its key matches the log; if the active file only
+//! grew since, the new lines are applied on top of the view; otherwise the log is replayed.
+
+use crate::event::{Body, Event, Payload};
+use crate::{derived, digest, segment};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Where views are kept.
+pub const VIEW_DIR: &str = ".hyena/views";
+
+/// Identifies the derived log's content without reading all of it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogKey {
+    /// Hash over the sealed segments' SHA-256s, as recorded in the segment manifest.
+    pub sealed: String,
+    /// Length of the active file.
+    pub bytes: u64,
+    /// SHA-256 of the active file.
+    pub active_sha256: String,
+}
+
+/// Thread root id -> ids of its live entries, in log order.
+pub type Threads = BTreeMap<String, Vec<String>>;
+
+/// `derived.meta.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ViewMeta {
+    pub log: LogKey,
+    pub built_at: String,
+    /// Lines in the log at build time.
+    pub log_lines: usize,
+    /// Live entries (lines in the view).
+    pub entries: usize,
+    #[serde(default)]
+    pub threads: Threads,
+}
+
+pub fn view_path(root: &Path) -> PathBuf {
+    root.join(VIEW_DIR).join("derived.ndjson")
+}
+
+pub fn meta_path(root: &Path) -> PathBuf {
+    root.join(VIEW_DIR).join("derived.meta.json")
+}
+
+/// SHA-256 of `active`'s first `len` bytes (of nothing if it does not exist).
+fn prefix_hash(active: &Path, len: u64) -> Result<String> {
+    if !active.exists() {
+        return Ok(digest::sha256_hex(b""));
+    }
+    digest::sha256_prefix(active, len)
+}
+
+/// Key of the derived log as it is on disk now.
+pub fn log_key(root: &Path) -> Result<LogKey> {
+    let active = derived::derived_path(root);
+    let mut hashes = String::new();
+    for seg in segment::read_manifest(&active)? {
+        hashes.push_str(&seg.sha256);
+        hashes.push('\n');
+    }
+    let bytes = std::fs::metadata(&active).map(|m| m.len()).unwrap_or(0);
+    Ok(LogKey {
+        sealed: digest::sha256_hex(hashes.as_bytes()),
+        bytes,
+        active_sha256: prefix_hash(&active, bytes)?,
+    })
+}
+
+/// Lines appended to the active file since the log had key `old`, or None if anything else
+/// changed (a segment was sealed, or the old content was rewritten).
+fn appended_since(root: &Path, old: &LogKey, now: &LogKey) -> Result<Option<Vec<String>>> {
+    let active = derived::derived_path(root);
+    if now.sealed != old.sealed
+        || now.bytes <= old.bytes
+        || prefix_hash(&active, old.bytes)? != old.active_sha256
+    {
+        return Ok(None);
+    }
+    let new = segment::read_from(&active, old.bytes)?;
+    Ok(Some(
+        new.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(str::to_string)
+            .collect(),
+    ))
+}
+
+/// Tree parent: the entry replied to, else the one superseded (as in [`crate::thread`]).
+fn tree_parent(e: &Event) -> Option<&str> {
+    e.parent_id.as_deref().or(e.supersedes.as_deref())
+}
+
+/// Live entries of `lines` (log order) and each thread's live ids. Lines that are not events are
+/// dropped.
+pub fn materialize(lines: &[String]) -> (Vec<String>, Threads) {
+    let events: Vec<(Event, &String)> = lines
+        .iter()
+        .filter_map(|l| Event::parse(l).map(|e| (e, l)))
+        .collect();
+    let mut dead: HashSet<&str> = HashSet::new();
+    let mut last_of_task: HashMap<&str, usize> = HashMap::new();
+    for (i, (e, _)) in events.iter().enumerate() {
+        if let Some(old) = e.supersedes.as_deref() {
+            dead.insert(old);
+            if matches!(e.body, Body::Known(Payload::Tombstone { .. })) {
+                dead.extend(e.id.as_deref());
+            }
+        }
+        if let Body::Known(Payload::Task {
+            task_id: Some(task),
+            ..
+        }) = &e.body
+        {
+            last_of_task.insert(task, i);
+        }
+    }
+    let live = |i: usize, e: &Event| {
+        let stale_task = match &e.body {
+            Body::Known(Payload::Task {
+                task_id: Some(task),
+                removed,
+                ..
+            }) => *removed || last_of_task.get(task.as_str()) != Some(&i),
+            _ => false,
+        };
+        !stale_task && e.id.as_deref().is_none_or(|id| !dead.contains(id))
+    };
+
+    let by_id: HashMap<&str, &Event> = events
+        .iter()
+        .filter_map(|(e, _)| e.id.as_deref().map(|id| (id, e)))
+        .collect();
+    let has_children: HashSet<&str> = events.iter().filter_map(|(e, _)| tree_parent(e)).collect();
+    let mut entries = Vec::new();
+    let mut threads: Threads = BTreeMap::new();
+    for (i, (e, line)) in events.iter().enumerate() {
+        if !live(i, e) {
+            continue;
+        }
+        entries.push((*line).clone());
+        let Some(id) = e.id.as_deref() else {
+            continue;
+        };
+        // Walk up to the thread root; stop on missing parents or cycles.
+        let mut root = id;
+        let mut seen = HashSet::from([id]);
+        while let Some(parent) = by_id.get(root).and_then(|e| tree_parent(e)) {
+            if !by_id.contains_key(parent) || !seen.insert(parent) {
+                break;
+            }
+            root = parent;
+        }
+        if root != id || has_children.contains(id) {
+            threads
+                .entry(root.to_string())
+                .or_default()
+                .push(id.to_string());
+        }
+    }
+    (entries, threads)
+}
+
+/// Live entries and threads after applying `new` lines (appended to the log after the view was
+/// built) to a view's `entries` and `threads`, as [`materialize`] over the whole log would give
+/// them. None if a new entry replies to or supersedes one the view no longer knows the thread of;
+/// only the full log can place it.
+pub fn apply(
+    entries: &[String],
+    threads: &Threads,
+    new: &[String],
+) -> Option<(Vec<String>, Threads)> {
+    // Dead entries are gone from the view, and nothing they did can be undone by a later line,
+    // so the live entries come out the same as from the whole log.
+    let all: Vec<String> = entries.iter().chain(new).cloned().collect();
+    let (live, _) = materialize(&all);
+
+    // Thread root of every entry the view knows: listed ones from `threads`, the rest are roots.
+    let mut root_of: HashMap<String, String> = HashMap::new();
+    for (root, ids) in threads {
+        root_of.insert(root.clone(), root.clone());
+        for id in ids {
+            root_of.insert(id.clone(), root.clone());
+        }
+    }
+    for e in entries.iter().filter_map(|l| Event::parse(l)) {
+        if let Some(id) = e.id {
+            root_of.entry(id.clone()).or_insert(id);
+        }
+    }
+    let mut listed: HashSet<String> = threads.values().flatten().cloned().collect();
+    let mut out = threads.clone();
+    for e in new.iter().filter_map(|l| Event::parse(l)) {
+        let Some(id) = e.id.clone() else {
+            continue;
+        };
+        let root = match tree_parent(&e).filter(|p| *p != id) {
+            None => id.clone(),
+            Some(parent) => {
+                let root = root_of.get(parent)?.clone();
+                // A root that gets its first child starts a thread.
+                if root == parent && listed.insert(root.clone()) {
+                    out.entry(root.clone()).or_default().insert(0, root.clone());
+                }
+                root
+            }
+        };
+        if root != id && listed.insert(id.clone()) {
+            out.entry(root.clone()).or_default().push(id.clone());
+        }
+        root_of.insert(id, root);
+    }
+    let live_ids: HashSet<String> = live
+        .iter()
+        .filter_map(|l| Event::parse(l).and_then(|e| e.id))
+        .collect();
+    for ids in out.values_mut() {
+        ids.retain(|id| live_ids.contains(id));
+    }
+    out.retain(|_, ids| !ids.is_empty());
+    Some((live, out))
+}
+
+/// Write via temp file + rename so readers never see half a file. The temp name is unique, so
+/// concurrent builds do not write into each other's file.
+fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let tmp = path.with_extension(format!("{}.tmp", ulid::Ulid::new()));
+    std::fs::write(&tmp, content).with_context(|| format!("write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("write {}", path.display()))
+}
+
+/// Live entries and log line count of the log as it had `key`, on top of the existing view when
+/// the log only grew since it was built, else by replaying the log.
+fn current(root: &Path, key: &LogKey) -> Result<(Vec<String>, Threads, usize)> {
+    if let Some(meta) = load_meta(root)? {
+        if let Some(new) = appended_since(root, &meta.log, key)? {
+            if let Some((entries, threads)) = apply(&read_view(root)?, &meta.threads, &new) {
+                return Ok((entries, threads, meta.log_lines + new.len()));
+            }
+        }
+    }
+    let lines = segment::read_lines(&derived::derived_path(root))?;
+    let (entries, threads) = materialize(&lines);
+    Ok((entries, threads, lines.len()))
+}
+
+/// Build the view from the current log and write it under [`VIEW_DIR`].
+pub fn build(root: &Path, built_at: String) -> Result<ViewMeta> {
+    // Take the key again after reading, so a line appended meanwhile is not in the view
+    // without being in its key.
+    let (key, (entries, threads, log_lines)) = loop {
+        let key = log_key(root)?;
+        let read = current(root, &key)?;
+        if log_key(root)? == key {
+            break (key, read);
+        }
+    };
+    let dir = root.join(VIEW_DIR);
+    std::fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
+    let body = entries.join("\n") + if entries.is_empty() { "" } else { "\n" };
+    write_atomic(&view_path(root), &body)?;
+    let meta = ViewMeta {
+        log: key,
+        built_at,
+        log_lines,
+        entries: entries.len(),
+        threads,
+    };
+    write_atomic(
+        &meta_path(root),
+        &(serde_json::to_string_pretty(&meta).context("serialize view meta")? + "\n"),
+    )?;
+    Ok(meta)
+}
+
+/// The view's meta, if there is a readable view.
+fn load_meta(root: &Path) -> Result<Option<ViewMeta>> {
+    let path = meta_path(root);
+    if !path.is_file() || !view_path(root).is_file() {
+        return Ok(None);
+    }
+    let s = std::fs::read_to_string(&path).with_context(|| format!("read {}", path.display()))?;
+    Ok(serde_json::from_str(&s).ok())
+}
+
+fn read_view(root: &Path) -> Result<Vec<String>> {
+    let path = view_path(root);
+    let s = std::fs::read_to_string(&path).with_context(|| format!("read {}", path.display()))?;
+    Ok(s.lines().map(str::to_string).collect())
+}
+
+/// The view's meta if it was built from the log as it is now.
+pub fn fresh_meta(root: &Path) -> Result<Option<ViewMeta>> {
+    let Some(meta) = load_meta(root)? else {
+        return Ok(None);
+    };
+    Ok((log_key(root)? == meta.log).then_some(meta))
+}
+
+/// Live derived entries: from the view if fresh, from the view plus what was appended since if
+/// the log only grew, else by replaying the log.
+pub fn latest_lines(root: &Path) -> Result<Vec<String>> {
+    if fresh_meta(root)?.is_some() {
+        return read_view(root);
+    }
+    Ok(current(root, &log_key(root)?)?.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn entry(kind: &str, text: &str) -> Event {
+        Event::new(
+            "2025-01-01T00:00:00Z".into(),
+            "agent",
+            Body::from_kind_text(kind, text),
+        )
+    }
+
+    fn texts(lines: &[String]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|l| Event::parse(l).unwrap().text().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn materialize_applies_supersessions_tombstones_and_task_updates() {
+        let a = entry("decision", "use sqlite");
+        let mut b = entry("decision", "use postgres");
+        b.supersedes = a.id.clone();
+        let mut reply = entry("note", "agreed");
+        reply.parent_id = b.id.clone();
+        let c = entry("question", "which port?");
+        let mut gone = entry("tombstone", "asked by mistake");
+        gone.supersedes = c.id.clone();
+        let task = |done: bool| {
+            let mut e = entry("task", "ship it");
+            if let Body::Known(Payload::Task {
+                task_id, done: d, ..
+            }) = &mut e.body
+            {
+                *task_id = Some("abc123".into());
+                *d = done;
+            }
+            e
+        };
+        let lines: Vec<String> = [
+            a.clone(),
+            b.clone(),
+            reply.clone(),
+            c,
+            gone,
+            task(false),
+            task(true),
+        ]
+        .iter()
+        .map(|e| serde_json::to_string(e).unwrap())
+        .collect();
+
+        let (live, threads) = materialize(&lines);
+        assert_eq!(texts(&live), vec!["use postgres", "agreed", "ship it"]);
+        assert!(Event::parse(&live[2]).unwrap().body == task(true).body);
+        let root = a.id.unwrap();
+        assert_eq!(threads[&root], vec![b.id.unwrap(), reply.id.unwrap()]);
+        assert_eq!(threads.len(), 1);
+    }
+
+    #[test]
+    fn apply_matches_a_full_replay() {
+        let a = entry("decision", "use sqlite");
+        let mut b = entry("decision", "use postgres");
+        b.supersedes = a.id.clone();
+        let q = entry("question", "which port?");
+        let lone = entry("note", "lone");
+        let mut reply = entry("note", "agreed");
+        reply.parent_id = b.id.clone();
+        let mut answer = entry("note", "5432");
+        answer.parent_id = q.id.clone();
+        let mut gone = entry("tombstone", "never mind");
+        gone.supersedes = lone.id.clone();
+        let lines = |es: &[&Event]| -> Vec<String> {
+            es.iter()
+                .map(|e| serde_json::to_string(e).unwrap())
+                .collect()
+        };
+        let old = lines(&[&a, &b, &q, &lone]);
+        let new = lines(&[&reply, &answer, &gone]);
+        let (entries, threads) = materialize(&old);
+        let all = [old.clone(), new.clone()].concat();
+        assert_eq!(apply(&entries, &threads, &new), Some(materialize(&all)));
+        let q_id = q.id.clone().unwrap();
+        assert_eq!(
+            materialize(&all).1[&q_id],
+            vec![q_id.clone(), answer.id.clone().unwrap()]
+        );
+
+        // A reply to an entry that is gone from the view needs the full log.
+        let mut late = entry("note", "re: sqlite");
+        late.parent_id = a.id.clone();
+        let (entries, _) = materialize(&lines(&[&a, &b]));
+        assert_eq!(apply(&entries, &Threads::new(), &lines(&[&late])), None);
+    }
+
+    #[test]
+    fn view_is_used_while_fresh_and_replayed_when_stale() {
+        let root = std::env::temp_dir().join("hyena_view_fresh");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let first = entry("note", "first");
+        derived::append_derived(&root, &first, &crate::secrets::Scanner::default()).unwrap();
+        let meta = build(&root, "t".into()).unwrap();
+        assert_eq!((meta.log_lines, meta.entries), (1, 1));
+        assert!(fresh_meta(&root).unwrap().is_some());
+        // Rewriting the active file in place at the same length is noticed too.
+        let path = derived::derived_path(&root);
+        let log = fs::read_to_string(&path).unwrap();
+        fs::write(&path, log.replace("first", "furst")).unwrap();
+        assert!(fresh_meta(&root).unwrap().is_none());
+        assert_eq!(texts(&latest_lines(&root).unwrap()), vec!["furst"]);
+        fs::write(&path, log).unwrap();
+        assert!(fresh_meta(&root).unwrap().is_some());
+
+        let mut fix = entry("note", "first, fixed");
+        fix.supersedes = first.id.clone();
+        derived::append_derived(&root, &fix, &crate::secrets::Scanner::default()).unwrap();
+        assert!(fresh_meta(&root).unwrap().is_none());
+        // Only appended to: the new line is applied on top of the view.
+        let key = log_key(&root).unwrap();
+        assert_eq!(
+            appended_since(&root, &meta.log, &key)
+                .unwrap()
+                .map(|l| l.len()),
+            Some(1)
+        );
+        assert_eq!(texts(&latest_lines(&root).unwrap()), vec!["first, fixed"]);
+        // Sealing changes the key's segment part: replayed from the log.
+        segment::maybe_rotate(
+            &derived::derived_path(&root),
+            &segment::Rotation {
+                max_bytes: Some(1),
+                max_age_hours: None,
+            },
+            chrono::Utc::now(),
+        )
+        .unwrap();
+        let sealed = log_key(&root).unwrap();
+        assert_eq!(appended_since(&root, &meta.log, &sealed).unwrap(), None);
+        assert_eq!(texts(&latest_lines(&root).unwrap()), vec!["first, fixed"]);
+        let rebuilt = build(&root, "t".into()).unwrap();
+        assert_eq!((rebuilt.log_lines, rebuilt.entries), (2, 1));
+        // The log itself keeps both entries.
+        assert_eq!(
+            segment::read_lines(&derived::derived_path(&root))
+                .unwrap()
+                .len(),
+            2
+        );
+        fs::remove_dir_all(&root).ok();
+    }
+}
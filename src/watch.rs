@@ -0,0 +1,221 @@
+//! Watch: re-ingest raw inputs as they change (inotify via `notify`) until stopped.
+//!
+//! Only directories raw inputs can be in are watched, one by one: those discovery walks under
+//! each include pattern's fixed prefix (so ignored trees such as `target/` and `.git/` are not),
+//! plus ones created there while watching. File events are filtered by the raw_inputs globs and
+//! debounced; each cycle ingests only the changed files and is recorded in the derived log as an
+//! `ingest_run` event. A cycle that fails is reported and watching goes on.
+
+use crate::clock::Clock;
+use crate::ingest::{self, IngestStats};
+use crate::raw::{self, RawInputs};
+use crate::secrets::Scanner;
+use anyhow::{Context, Result};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// How often the loop wakes to check the stop flag and the debounce deadline.
+const TICK: Duration = Duration::from_millis(50);
+
+/// Run one ingest cycle; ingest itself logs an `ingest_run` event if anything changed.
+fn cycle(
+    root: &Path,
+    inputs: &RawInputs,
+    only: Option<&[PathBuf]>,
+    actor: &str,
+    clock: Clock,
+    scanner: &Scanner,
+) -> Result<IngestStats> {
+    ingest::ingest(root, inputs, only, actor, &clock.ts(), scanner)
+}
+
+/// Watch `dirs` one level deep each; a directory that cannot be watched is reported and skipped.
+fn watch_dirs(watcher: &mut RecommendedWatcher, dirs: &[PathBuf]) {
+    for dir in dirs {
+        if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+            eprintln!("watch: {}: {}", dir.display(), e);
+        }
+    }
+}
+
+/// Watch `dir`, created while watching, if discovery would walk into it. Returns the raw inputs
+/// it already holds.
+fn new_dir(
+    watcher: &mut RecommendedWatcher,
+    root: &Path,
+    inputs: &RawInputs,
+    dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let walked = match dir.parent() {
+        Some(parent) => raw::walked_dirs(root, parent, inputs, Some(1))?,
+        None => Vec::new(),
+    };
+    if !walked.iter().any(|d| d == dir) {
+        return Ok(Vec::new());
+    }
+    watch_dirs(watcher, &raw::walked_dirs(root, dir, inputs, None)?);
+    let scope = raw::relative_for_glob(dir, root).map(PathBuf::from);
+    raw::discover_raw_files(root, scope.as_ref(), inputs)
+}
+
+/// Report a cycle's outcome: `on_cycle` if it changed something, stderr if it failed.
+fn report(result: Result<IngestStats>, on_cycle: &mut impl FnMut(&IngestStats)) {
+    match result {
+        Ok(stats) if !stats.files_changed.is_empty() || !stats.files_removed.is_empty() => {
+            on_cycle(&stats)
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("watch: ingest failed: {:#}", e),
+    }
+}
+
+/// Catch up with a full ingest, then watch the directories raw inputs can be in until `stop` is
+/// set. `on_cycle` is called after every cycle that changed something.
+#[allow(clippy::too_many_arguments)]
+pub fn watch(
+    root: &Path,
+    inputs: &RawInputs,
+    actor: &str,
+    clock: Clock,
+    scanner: &Scanner,
+    debounce: Duration,
+    stop: &AtomicBool,
+    mut on_cycle: impl FnMut(&IngestStats),
+) -> Result<()> {
+    let matcher = raw::Matcher::new(inputs)?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("start file watcher")?;
+    let bases = raw::pattern_bases(root, inputs);
+    for base in &bases {
+        // A base that does not exist yet is picked up when it is created under an ancestor.
+        match base.ancestors().find(|d| d.is_dir()) {
+            Some(dir) if dir == base => {
+                watch_dirs(&mut watcher, &raw::walked_dirs(root, base, inputs, None)?)
+            }
+            Some(dir) => watch_dirs(&mut watcher, &[dir.to_path_buf()]),
+            None => {}
+        }
+    }
+    let in_scope = |dir: &Path| {
+        bases
+            .iter()
+            .any(|b| dir.starts_with(b) || b.starts_with(dir))
+    };
+
+    report(
+        cycle(root, inputs, None, actor, clock, scanner),
+        &mut on_cycle,
+    );
+
+    let mut pending: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut last_event = Instant::now();
+    while !stop.load(Ordering::SeqCst) {
+        match rx.recv_timeout(TICK) {
+            // Ingest's own reads show up as access events; only changes count.
+            Ok(Ok(event)) if matches!(event.kind, EventKind::Access(_)) => {}
+            Ok(Ok(event)) => {
+                let created = matches!(event.kind, EventKind::Create(_));
+                for path in event.paths {
+                    if created && path.is_dir() && in_scope(&path) {
+                        match new_dir(&mut watcher, root, inputs, &path) {
+                            Ok(found) if !found.is_empty() => {
+                                pending.extend(found);
+                                last_event = Instant::now();
+                            }
+                            Ok(_) => {}
+                            Err(e) => eprintln!("watch: {}: {:#}", path.display(), e),
+                        }
+                        continue;
+                    }
+                    let matches = raw::relative_for_glob(&path, root)
+                        .is_some_and(|rel| matcher.is_match(&rel));
+                    if matches {
+                        pending.insert(path);
+                        last_event = Instant::now();
+                    }
+                }
+            }
+            Ok(Err(e)) => eprintln!("watch: {}", e),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+        if !pending.is_empty() && last_event.elapsed() >= debounce {
+            let only: Vec<PathBuf> = std::mem::take(&mut pending).into_iter().collect();
+            report(
+                cycle(root, inputs, Some(&only), actor, clock, scanner),
+                &mut on_cycle,
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::derived;
+    use std::fs;
+    use std::sync::Arc;
+
+    #[test]
+    fn watch_ingests_changed_notes_and_logs_cycle() {
+        let root = std::env::temp_dir().join("hyena_watch_cycle");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("NOTES.md"), "# Start\nhello\n").unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let (done_tx, done_rx) = mpsc::channel();
+        let handle = {
+            let (root, stop) = (root.clone(), stop.clone());
+            std::thread::spawn(move || {
+                let patterns = RawInputs {
+                    patterns: vec!["**/NOTES.md".to_string()],
+                    ..Default::default()
+                };
+                watch(
+                    &root,
+                    &patterns,
+                    "human",
+                    Clock::default(),
+                    &Scanner::default(),
+                    Duration::from_millis(100),
+                    &stop,
+                    |s| {
+                        done_tx.send(s.files_changed.clone()).unwrap();
+                    },
+                )
+            })
+        };
+        let first = done_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(first, vec!["NOTES.md"]);
+
+        fs::write(root.join("sub/NOTES.md"), "# New\nfresh\n").unwrap();
+        fs::write(root.join("sub/other.txt"), "ignored\n").unwrap();
+        let second = done_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(second, vec!["sub/NOTES.md"]);
+
+        // A file ingest cannot read fails that cycle only.
+        fs::write(root.join("NOTES.md"), [0xff, 0xfe, b'\n']).unwrap();
+        std::thread::sleep(Duration::from_millis(400));
+        // A directory created while watching is watched too.
+        fs::create_dir(root.join("later")).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        fs::write(root.join("later/NOTES.md"), "# Later\nadded\n").unwrap();
+        let third = done_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(third, vec!["later/NOTES.md"]);
+
+        stop.store(true, Ordering::SeqCst);
+        handle.join().unwrap().unwrap();
+        let log = fs::read_to_string(derived::derived_path(&root)).unwrap();
+        assert_eq!(log.matches("\"kind\":\"ingest_run\"").count(), 3);
+        assert!(log.contains("fresh"));
+        assert!(!log.contains("ignored"));
+        fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,215 @@
+//! Workspaces: several Hyena roots queried as one.
+//!
+//! A workspace file (YAML) names each root with an alias:
+//!
+//! ```yaml
+//! roots:
+//!   - alias: docs
+//!     path: ../docs
+//!   - alias: api
+//!     path: services/api
+//! ```
+//!
+//! Relative paths are taken from the workspace file's directory. `--workspace FILE` makes
+//! `search`, `read derived` and `tasks` run in every root under that root's own
+//! `.agent/POLICY.yaml` and label each result with its alias. A root whose directory or policy is
+//! missing or invalid, or whose policy refuses the actor, is skipped and reported; the query fails
+//! only if no root could answer.
+
+use crate::policy::{self, Policy};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Deserialize)]
+struct WorkspaceFile {
+    #[serde(default)]
+    roots: Vec<MemberFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MemberFile {
+    alias: String,
+    path: PathBuf,
+}
+
+/// One root of a workspace.
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub alias: String,
+    pub root: PathBuf,
+}
+
+impl Member {
+    pub fn policy_path(&self) -> PathBuf {
+        self.root.join(".agent/POLICY.yaml")
+    }
+
+    /// The member's policy, if it has a valid one that accepts `actor`.
+    pub fn open(&self, actor: &str) -> Result<Policy> {
+        if !self.root.is_dir() {
+            anyhow::bail!("root {} is not a directory", self.root.display());
+        }
+        let policy_path = self.policy_path();
+        if !policy_path.is_file() {
+            anyhow::bail!("no policy at {}", policy_path.display());
+        }
+        let policy = policy::load(&policy_path)?;
+        policy.check_actor(actor)?;
+        Ok(policy)
+    }
+}
+
+/// Load a workspace file. Aliases must be unique identifiers.
+pub fn load(path: &Path) -> Result<Vec<Member>> {
+    let s = std::fs::read_to_string(path)
+        .with_context(|| format!("read workspace: {}", path.display()))?;
+    let file: WorkspaceFile =
+        serde_yaml::from_str(&s).with_context(|| format!("parse {}", path.display()))?;
+    if file.roots.is_empty() {
+        anyhow::bail!("workspace {} lists no roots", path.display());
+    }
+    let base = path.parent().unwrap_or(Path::new("."));
+    let mut members: Vec<Member> = Vec::new();
+    for m in file.roots {
+        let ok = !m.alias.is_empty()
+            && m.alias
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !ok {
+            anyhow::bail!(
+                "workspace alias '{}' must be A-Z, a-z, 0-9, '-', '_' or '.'",
+                m.alias
+            );
+        }
+        if members.iter().any(|o| o.alias == m.alias) {
+            anyhow::bail!("workspace alias '{}' is listed twice", m.alias);
+        }
+        members.push(Member {
+            alias: m.alias,
+            root: base.join(m.path),
+        });
+    }
+    Ok(members)
+}
+
+/// Label a result line with its root's alias: JSON objects get a leading `"repo"` field (so the
+/// output stays NDJSON), other lines an `alias: ` prefix.
+pub fn label(alias: &str, line: &str) -> String {
+    match line.strip_prefix('{') {
+        Some(rest) => {
+            let sep = if rest.trim_start().starts_with('}') {
+                ""
+            } else {
+                ","
+            };
+            format!(
+                "{{\"repo\":{}{}{}",
+                serde_json::Value::from(alias),
+                sep,
+                rest
+            )
+        }
+        None => format!("{}: {}", alias, line),
+    }
+}
+
+/// Results of a query run in every root.
+#[derive(Debug, Default)]
+pub struct FanOut {
+    /// Labelled result lines, root by root in workspace order.
+    pub lines: Vec<String>,
+    /// Roots that could not answer, with the reason.
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Run `query` in each member whose policy accepts `actor`. Fails only if every member was
+/// skipped.
+pub fn fan_out(
+    members: &[Member],
+    actor: &str,
+    mut query: impl FnMut(&Member, &Policy) -> Result<Vec<String>>,
+) -> Result<FanOut> {
+    let mut out = FanOut::default();
+    for m in members {
+        match m.open(actor).and_then(|policy| query(m, &policy)) {
+            Ok(lines) => out.lines.extend(lines.iter().map(|l| label(&m.alias, l))),
+            Err(e) => out.skipped.push((m.alias.clone(), format!("{:#}", e))),
+        }
+    }
+    if out.skipped.len() == members.len() {
+        let reasons: Vec<String> = out
+            .skipped
+            .iter()
+            .map(|(alias, e)| format!("{}: {}", alias, e))
+            .collect();
+        anyhow::bail!(
+            "no workspace root could answer:\n  {}",
+            reasons.join("\n  ")
+        );
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn label_keeps_json_lines_json() {
+        let l = label("docs", r#"{"id":"01","text":"hi"}"#);
+        let v: serde_json::Value = serde_json::from_str(&l).unwrap();
+        assert_eq!(v["repo"], "docs");
+        assert_eq!(v["text"], "hi");
+        assert_eq!(label("docs", "{}"), r#"{"repo":"docs"}"#);
+        assert_eq!(
+            label("api", "[ ] abc  NOTES.md:3  x"),
+            "api: [ ] abc  NOTES.md:3  x"
+        );
+    }
+
+    #[test]
+    fn fan_out_skips_roots_without_a_usable_policy() {
+        let dir = std::env::temp_dir().join("hyena_workspace_fan_out");
+        let _ = fs::remove_dir_all(&dir);
+        for (name, policy) in [
+            ("good", Some("policy:\n  name: hyena\n")),
+            ("bad", Some("policy:\n  name: other\n")),
+            ("bare", None),
+        ] {
+            fs::create_dir_all(dir.join(name).join(".agent")).unwrap();
+            if let Some(p) = policy {
+                fs::write(dir.join(name).join(".agent/POLICY.yaml"), p).unwrap();
+            }
+        }
+        let ws = dir.join("hyena-workspace.yaml");
+        fs::write(
+            &ws,
+            "roots:\n  - {alias: good, path: good}\n  - {alias: bad, path: bad}\n  - {alias: bare, path: bare}\n  - {alias: gone, path: gone}\n",
+        )
+        .unwrap();
+        let members = load(&ws).unwrap();
+        assert_eq!(members[0].root, dir.join("good"));
+
+        let out = fan_out(&members, "agent", |m, _| {
+            Ok(vec![format!("hit in {}", m.alias)])
+        })
+        .unwrap();
+        assert_eq!(out.lines, vec!["good: hit in good"]);
+        let skipped: Vec<&str> = out.skipped.iter().map(|(a, _)| a.as_str()).collect();
+        assert_eq!(skipped, vec!["bad", "bare", "gone"]);
+        assert!(out.skipped[1].1.contains("no policy"));
+
+        let err = fan_out(&members[1..], "agent", |_, _| Ok(vec![])).unwrap_err();
+        assert!(err.to_string().contains("no workspace root could answer"));
+
+        fs::write(
+            &ws,
+            "roots:\n  - {alias: a, path: x}\n  - {alias: a, path: y}\n",
+        )
+        .unwrap();
+        assert!(load(&ws).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+}